@@ -1,39 +1,70 @@
 use crate::code::instruction_to_bin;
+use crate::data;
 use crate::parser;
 use crate::symbol_table::SymbolTable;
 
+// Words of instruction memory.
+const ROM_SIZE: usize = 32_768;
+
+// Machine code together with the source line each word was assembled from.
+pub struct Assembled {
+    pub machine_code: Vec<String>,
+    // 1-based line in the source of every word, None for the words of the data prologue.
+    pub lines: Vec<Option<usize>>,
+}
+
     // In the process of assembling, we will parse the source code,
     // if the assembly process is successful, we will return the machine code.
 pub fn assemble(source: &str, symbol_table: &mut SymbolTable) -> Result<Vec<String>, String> {
+    Ok(assemble_with_lines(source, symbol_table)?.machine_code)
+}
+
+// Same as assemble, but also keeps track of where each word came from, for debuggers and listings.
+pub fn assemble_with_lines(source: &str, symbol_table: &mut SymbolTable) -> Result<Assembled, String> {
+    // Data directives are expanded first, their initialization code runs before the program.
+    let data_section = data::expand(source, symbol_table)?;
+    let prologue_lines = data_section.prologue.lines().count();
+    if prologue_lines > ROM_SIZE {
+        return Err(format!(
+            "Storing the data takes {} instructions, more than the 32K ROM holds",
+            prologue_lines
+        ));
+    }
+    let source = format!("{}{}", data_section.prologue, data_section.source);
+    let source = source.as_str();
     // Parser makes two pases, in the first pass, it finds all the labels and adds them to the symbol table.
     // In the second pass, it parses the instructions and generates the machine code.
-    parser::find_label(source, symbol_table);
-    let parsed_instruction = parser::parse_lines(source);
+    // The prologue has one instruction per line and no labels.
+    parser::find_label(&data_section.source, symbol_table, prologue_lines as u16)?;
+    let parsed_instruction = parser::parse_numbered_lines(source);
     let mut machine_code: Vec<String> = vec![];
-    for instruction in parsed_instruction {
-        let binary_instruction: String;
+    let mut lines: Vec<Option<usize>> = vec![];
+    for (line, instruction) in parsed_instruction {
         let inst = instruction.clone();
-        match instruction {
-            parser::Instruction::AInstruction(_) => {
-                binary_instruction = instruction_to_bin(&inst, symbol_table);
-            }
-            parser::Instruction::CInstruction { .. } => {
-                binary_instruction = instruction_to_bin(&inst, symbol_table);
-            }
+        let binary_instruction: String = match instruction {
+            parser::Instruction::AInstruction(_) => instruction_to_bin(&inst, symbol_table),
+            parser::Instruction::CInstruction { .. } => instruction_to_bin(&inst, symbol_table),
             parser::Instruction::Label(_) => {
                 // Labels are not converted to machine code, they are just used for reference.
                 continue;
             }
-            parser::Instruction::Variable(_) => {
-                binary_instruction = instruction_to_bin(&inst, symbol_table);
-            }
-        }
+            parser::Instruction::Variable(_) => instruction_to_bin(&inst, symbol_table),
+        };
         if !binary_instruction.is_empty() {
         machine_code.push(binary_instruction);
+        lines.push(line.checked_sub(prologue_lines).filter(|line| *line > 0));
         }
     }
+    data::check_variables(&data_section.placed, symbol_table)?;
+    if machine_code.len() > ROM_SIZE {
+        return Err(format!(
+            "Program does not fit in the 32K ROM: {} words, {} of them store data",
+            machine_code.len(),
+            prologue_lines
+        ));
+    }
     // Placeholder for the machine code output
 
     // Return the machine code as a result
-    Ok(machine_code)
+    Ok(Assembled { machine_code, lines })
 }
\ No newline at end of file
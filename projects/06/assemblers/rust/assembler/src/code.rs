@@ -24,11 +24,11 @@ pub fn instruction_to_bin(instruction: &Instruction, symbol_table: &mut SymbolTa
             if !value_exists {
                 // If the value does not exist, add it to the symbol table
                 let address = symbol_table.add_variable(value.clone());
-                return format!("{:016b}", address);
+                format!("{:016b}", address)
             } else {
                 // If the value exists, get the address from the symbol table
                 let address = symbol_table.get_address(value).unwrap();
-                return format!("{:016b}", address);
+                format!("{:016b}", address)
             }
         }
     }
@@ -48,7 +48,12 @@ pub fn dest_to_bin(dest: Option<&str>) -> &'static str {
 }
 
 pub fn comp_to_bin(comp: &str) -> &'static str {
-    match comp.trim() {
+    comp_code(comp).unwrap_or_else(|| panic!("Invalid comp mnemonic: {}", comp))
+}
+
+// Same as comp_to_bin, but returns None for an unknown mnemonic instead of panicking.
+pub fn comp_code(comp: &str) -> Option<&'static str> {
+    let code = match comp.trim() {
         "0" => "0101010",
         "1" => "0111111",
         "-1" => "0111010",
@@ -67,18 +72,19 @@ pub fn comp_to_bin(comp: &str) -> &'static str {
         "D-1" => "0001110",
         "A-1" => "0110010",
         "M-1" => "1110010",
-        "D+A" => "0000010",
-        "D+M" => "1000010",
+        "D+A" | "A+D" => "0000010",
+        "D+M" | "M+D" => "1000010",
         "D-A" => "0010011",
         "D-M" => "1010011",
         "A-D" => "0000111",
         "M-D" => "1000111",
-        "D&A" => "0000000",
-        "D&M" => "1000000",
-        "D|A" => "0010101",
-        "D|M" => "1010101",
-        _ => panic!("Invalid comp mnemonic: {}", comp),
-    }
+        "D&A" | "A&D" => "0000000",
+        "D&M" | "M&D" => "1000000",
+        "D|A" | "A|D" => "0010101",
+        "D|M" | "M|D" => "1010101",
+        _ => return None,
+    };
+    Some(code)
 }
 
 pub fn jump_to_bin(jump: Option<&str>) -> &'static str {
@@ -93,3 +99,28 @@ pub fn jump_to_bin(jump: Option<&str>) -> &'static str {
         _ => "000",
     }
 }
+
+// Encodes a C-instruction straight into its 16-bit word, None if the comp is unknown.
+pub fn encode_c_instruction(dest: Option<&str>, comp: &str, jump: Option<&str>) -> Option<u16> {
+    let bits = |code: &str| u16::from_str_radix(code, 2).unwrap();
+    let comp_bits = bits(comp_code(comp)?);
+    Some(0b111 << 13 | comp_bits << 6 | bits(dest_to_bin(dest)) << 3 | bits(jump_to_bin(jump)))
+}
+
+// The comp mnemonics in their canonical spelling, as used by the course tools.
+pub const COMP_MNEMONICS: [&str; 28] = [
+    "0", "1", "-1", "D", "A", "M", "!D", "!A", "!M", "-D", "-A", "-M", "D+1", "A+1", "M+1",
+    "D-1", "A-1", "M-1", "D+A", "D+M", "D-A", "D-M", "A-D", "M-D", "D&A", "D&M", "D|A", "D|M",
+];
+
+// Turns a word back into assembly, None if a C-instruction has a comp no mnemonic encodes.
+pub fn disassemble(word: u16) -> Option<String> {
+    if word & 0x8000 == 0 {
+        return Some(format!("@{}", word));
+    }
+    let comp = format!("{:07b}", (word >> 6) & 0x7F);
+    let comp = COMP_MNEMONICS.iter().find(|mnemonic| comp_code(mnemonic) == Some(comp.as_str()))?;
+    let dest = ["", "M=", "D=", "MD=", "A=", "AM=", "AD=", "AMD="][((word >> 3) & 0x7) as usize];
+    let jump = ["", ";JGT", ";JEQ", ";JGE", ";JLT", ";JNE", ";JLE", ";JMP"][(word & 0x7) as usize];
+    Some(format!("{}{}{}", dest, comp, jump))
+}
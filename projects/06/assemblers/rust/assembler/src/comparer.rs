@@ -0,0 +1,133 @@
+use std::fs;
+use std::path::Path;
+
+// Native replacement for tools/TextComparer.sh.
+// A produced .hack is compared against a reference .hack line by line, while a .out is
+// compared against a .cmp as a `|` separated table where cells made of `*` match anything.
+
+// Compares two files on disk, choosing the comparison by the extension of the reference file.
+pub fn compare_files(produced: &str, reference: &str) -> Result<(), String> {
+    let produced_contents = fs::read_to_string(produced)
+        .map_err(|err| format!("Failed to read '{}': {}", produced, err))?;
+    let reference_contents = fs::read_to_string(reference)
+        .map_err(|err| format!("Failed to read '{}': {}", reference, err))?;
+    let is_table = matches!(
+        Path::new(reference).extension().and_then(|ext| ext.to_str()),
+        Some("cmp") | Some("out")
+    );
+    if is_table {
+        compare_table(&produced_contents, &reference_contents)
+    } else {
+        compare_hack(&produced_contents, &reference_contents)
+    }
+}
+
+// Compares machine code line by line. Trailing whitespace and line endings are ignored.
+pub fn compare_hack(produced: &str, reference: &str) -> Result<(), String> {
+    let produced_lines: Vec<&str> = produced.lines().map(str::trim).collect();
+    let reference_lines: Vec<&str> = reference.lines().map(str::trim).collect();
+    for (i, expected) in reference_lines.iter().enumerate() {
+        match produced_lines.get(i) {
+            Some(actual) if actual == expected => {}
+            Some(actual) => {
+                return Err(format!(
+                    "Comparison failure at line {} (ROM[{}]):\n  expected: {}\n  actual:   {}",
+                    i + 1,
+                    i,
+                    expected,
+                    actual
+                ));
+            }
+            None => {
+                return Err(format!(
+                    "Comparison failure at line {} (ROM[{}]): produced file ended, expected {}",
+                    i + 1,
+                    i,
+                    expected
+                ));
+            }
+        }
+    }
+    if produced_lines.len() > reference_lines.len() {
+        let extra = reference_lines.len();
+        return Err(format!(
+            "Comparison failure at line {} (ROM[{}]): unexpected extra line {}",
+            extra + 1,
+            extra,
+            produced_lines[extra]
+        ));
+    }
+    Ok(())
+}
+
+// Compares a test script output against its compare file. The first row of the
+// compare file names the columns, which are used when reporting a mismatch.
+pub fn compare_table(out: &str, cmp: &str) -> Result<(), String> {
+    let out_rows: Vec<&str> = out.lines().filter(|line| !line.trim().is_empty()).collect();
+    let cmp_rows: Vec<&str> = cmp.lines().filter(|line| !line.trim().is_empty()).collect();
+    let column_names: Vec<String> = cmp_rows
+        .first()
+        .map(|header| split_row(header).iter().map(|cell| cell.to_string()).collect())
+        .unwrap_or_default();
+
+    for (i, expected) in cmp_rows.iter().enumerate() {
+        let actual = match out_rows.get(i) {
+            Some(actual) => actual,
+            None => {
+                return Err(format!(
+                    "Comparison failure at line {}: output ended, expected\n  {}",
+                    i + 1,
+                    expected.trim()
+                ));
+            }
+        };
+        let expected_cells = split_row(expected);
+        let actual_cells = split_row(actual);
+        let mut differing: Vec<String> = Vec::new();
+        for column in 0..expected_cells.len().max(actual_cells.len()) {
+            let expected_cell = expected_cells.get(column).copied();
+            let actual_cell = actual_cells.get(column).copied();
+            if !cells_match(expected_cell, actual_cell) {
+                let name = column_names
+                    .get(column)
+                    .cloned()
+                    .unwrap_or_else(|| format!("column {}", column + 1));
+                differing.push(name);
+            }
+        }
+        if !differing.is_empty() {
+            return Err(format!(
+                "Comparison failure at line {} (columns: {}):\n  expected: {}\n  actual:   {}",
+                i + 1,
+                differing.join(", "),
+                expected.trim(),
+                actual.trim()
+            ));
+        }
+    }
+    if out_rows.len() > cmp_rows.len() {
+        let extra = cmp_rows.len();
+        return Err(format!(
+            "Comparison failure at line {}: unexpected extra row\n  {}",
+            extra + 1,
+            out_rows[extra].trim()
+        ));
+    }
+    Ok(())
+}
+
+// Splits a `|a|b|c|` row into its trimmed cells.
+fn split_row(row: &str) -> Vec<&str> {
+    let trimmed = row.trim();
+    let inner = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    inner.split('|').map(str::trim).collect()
+}
+
+fn cells_match(expected: Option<&str>, actual: Option<&str>) -> bool {
+    match (expected, actual) {
+        (Some(expected), _) if !expected.is_empty() && expected.chars().all(|c| c == '*') => true,
+        (Some(expected), Some(actual)) => expected == actual,
+        _ => false,
+    }
+}
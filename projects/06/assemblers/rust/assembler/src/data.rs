@@ -0,0 +1,318 @@
+use crate::symbol_table::SymbolTable;
+
+// Data directives. The Hack ROM can only hold instructions, so data is placed in RAM
+// by a prologue of instructions that runs before the program itself.
+//
+//   .data TABLE        starts a block named TABLE, @TABLE is the address of its first word
+//   .word 1, -1, 0x7F  stores the listed values, a symbol stores its address (e.g. .word SCREEN)
+//   .string "Hi\n"     stores one character per word followed by a terminating 0
+//   .fill 16, 0        stores the value (0 if omitted) the given number of times
+//   .org 2048          places the following data at RAM[2048] onwards
+//
+// Blocks without an .org are allocated by the symbol table's variable allocator, so they
+// come before the program's own variables. Blocks placed with .org may not overlap each
+// other, nor the variables, which the assembler checks once it knows all of them.
+
+pub struct DataSection {
+    // Instructions storing every value, one instruction per line.
+    pub prologue: String,
+    // The source with every data directive replaced by an empty line.
+    pub source: String,
+    // RAM placed by .org, start and end address and the line of the block.
+    pub placed: Vec<(u16, u16, usize)>,
+}
+
+#[derive(Clone)]
+enum Value {
+    Number(u16),
+    Symbol(String),
+}
+
+struct Block {
+    name: Option<String>,
+    // Explicit address given by .org, otherwise allocated once the block is complete.
+    address: Option<u16>,
+    values: Vec<Value>,
+    line: usize,
+}
+
+pub fn expand(source: &str, symbol_table: &mut SymbolTable) -> Result<DataSection, String> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut org: Option<u16> = None;
+    let mut output = String::with_capacity(source.len());
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let code = strip_comment(line).trim();
+        if !code.starts_with('.') {
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        }
+        output.push('\n');
+
+        let (directive, argument) = match code.split_once(char::is_whitespace) {
+            Some((directive, argument)) => (directive, argument.trim()),
+            None => (code, ""),
+        };
+        let error = |message: String| format!("Line {}: {}", line_number, message);
+        match directive {
+            ".org" => {
+                let address = parse_number(argument)
+                    .filter(|address| *address < 32_768)
+                    .ok_or_else(|| error(format!("Invalid .org address '{}'", argument)))?;
+                // Anonymous data after an .org gets a block of its own.
+                org = Some(address);
+            }
+            ".data" => {
+                if !is_symbol(argument) {
+                    return Err(error(format!("Invalid .data name '{}'", argument)));
+                }
+                if symbol_table.contains(argument)
+                    || blocks.iter().any(|b| b.name.as_deref() == Some(argument))
+                {
+                    return Err(error(format!("Symbol '{}' is already defined", argument)));
+                }
+                start_block(&mut blocks, &mut org, Some(argument.to_string()), line_number);
+            }
+            ".word" | ".string" | ".fill" => {
+                let needs_block = blocks.last().is_none() || org.is_some();
+                if needs_block {
+                    if org.is_none() {
+                        return Err(error(format!("{} outside of a .data block", directive)));
+                    }
+                    start_block(&mut blocks, &mut org, None, line_number);
+                }
+                let values = match directive {
+                    ".word" => parse_words(argument),
+                    ".string" => parse_string(argument),
+                    _ => parse_fill(argument),
+                }
+                .map_err(error)?;
+                blocks.last_mut().unwrap().values.extend(values);
+            }
+            _ => return Err(error(format!("Unknown directive '{}'", directive))),
+        }
+    }
+
+    // Place the blocks and generate the code storing their values.
+    let mut prologue = String::new();
+    let mut next_explicit: Option<u32> = None;
+    let mut placed: Vec<(u16, u16, usize)> = Vec::new();
+    for block in blocks {
+        let size = block.values.len() as u32;
+        if size == 0 {
+            // It would share its address with whatever comes next.
+            let name = block.name.as_deref().unwrap_or_default();
+            return Err(format!("Line {}: .data {} holds no values", block.line, name));
+        }
+        // Data following an .org'ed block continues right after it.
+        let explicit = block.address.map(u32::from).or(next_explicit);
+        let address = match explicit {
+            Some(address) => {
+                if address + size > 32_768 {
+                    return Err(format!("Line {}: data does not fit in RAM", block.line));
+                }
+                next_explicit = Some(address + size);
+                let (start, end) = (address as u16, (address + size) as u16);
+                if let Some((other_start, other_end, other_line)) = placed
+                    .iter()
+                    .find(|(other_start, other_end, _)| start < *other_end && *other_start < end)
+                {
+                    return Err(format!(
+                        "Line {}: data at RAM[{}..{}] overlaps the data of line {} at RAM[{}..{}]",
+                        block.line, start, end, other_line, other_start, other_end
+                    ));
+                }
+                placed.push((start, end, block.line));
+                if let Some(name) = &block.name {
+                    symbol_table.add_entry(name.clone(), address as u16);
+                }
+                address as u16
+            }
+            None => {
+                let name = block.name.clone().unwrap_or_default();
+                symbol_table
+                    .allocate(name, size as u16)
+                    .map_err(|e| format!("Line {}: {}", block.line, e))?
+            }
+        };
+        for (offset, value) in block.values.iter().enumerate() {
+            store_value(&mut prologue, address + offset as u16, value);
+        }
+    }
+
+    Ok(DataSection {
+        prologue,
+        source: output,
+        placed,
+    })
+}
+
+// Checks the data placed with .org against the variables, once the program's are known too.
+pub fn check_variables(placed: &[(u16, u16, usize)], symbol_table: &SymbolTable) -> Result<(), String> {
+    let variables_end = symbol_table.variables_end();
+    match placed.iter().find(|(start, end, _)| *start < variables_end && VARIABLES < *end) {
+        Some((start, end, line)) => Err(format!(
+            "Line {}: data at RAM[{}..{}] overlaps the variables at RAM[{}..{}]",
+            line, start, end, VARIABLES, variables_end
+        )),
+        None => Ok(()),
+    }
+}
+
+// The first address of the variable area.
+const VARIABLES: u16 = 16;
+
+// The line without its // comment, which may not start inside a string or character.
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if line[index..].starts_with("//") => return &line[..index],
+            None => {}
+        }
+    }
+    line
+}
+
+// Splits a list of values at the commas that are not a character literal like ','.
+fn split_values(argument: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    let bytes = argument.as_bytes();
+    while index < bytes.len() {
+        if bytes[index] == b'\'' && bytes.get(index + 2) == Some(&b'\'') {
+            index += 3;
+            continue;
+        }
+        if bytes[index] == b',' {
+            values.push(argument[start..index].trim());
+            start = index + 1;
+        }
+        index += 1;
+    }
+    values.push(argument[start..].trim());
+    values
+}
+
+fn start_block(blocks: &mut Vec<Block>, org: &mut Option<u16>, name: Option<String>, line: usize) {
+    blocks.push(Block {
+        name,
+        address: org.take(),
+        values: Vec::new(),
+        line,
+    });
+}
+
+// Loads the value into D and stores it, using the constant forms of comp where possible.
+fn store_value(prologue: &mut String, address: u16, value: &Value) {
+    let comp = match value {
+        Value::Number(0) => "0",
+        Value::Number(1) => "1",
+        Value::Number(0xFFFF) => "-1",
+        Value::Number(number) if *number < 32_768 => {
+            prologue.push_str(&format!("@{}\nD=A\n", number));
+            "D"
+        }
+        Value::Number(number) => {
+            // A-instructions only hold 15 bits, so load the complement and negate it.
+            prologue.push_str(&format!("@{}\nD=!A\n", !number));
+            "D"
+        }
+        Value::Symbol(symbol) => {
+            prologue.push_str(&format!("@{}\nD=A\n", symbol));
+            "D"
+        }
+    };
+    prologue.push_str(&format!("@{}\nM={}\n", address, comp));
+}
+
+fn parse_words(argument: &str) -> Result<Vec<Value>, String> {
+    if argument.is_empty() {
+        return Err(".word expects at least one value".to_string());
+    }
+    split_values(argument).into_iter().map(parse_value).collect()
+}
+
+fn parse_fill(argument: &str) -> Result<Vec<Value>, String> {
+    let parts = split_values(argument);
+    let count = match parts.first().and_then(|count| parse_number(count)) {
+        Some(count) if parts.len() <= 2 => count as usize,
+        _ => return Err(format!("Invalid .fill '{}', expected COUNT[, VALUE]", argument)),
+    };
+    let value = match parts.get(1) {
+        Some(value) => parse_value(value)?,
+        None => Value::Number(0),
+    };
+    Ok(vec![value; count])
+}
+
+// Characters are stored using the Hack character set, where newline is 128.
+fn parse_string(argument: &str) -> Result<Vec<Value>, String> {
+    let inner = argument
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| format!("Invalid .string {}, expected a quoted string", argument))?;
+    let mut values = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let code = match c {
+            '\\' => match chars.next() {
+                Some('n') => 128,
+                Some('b') => 129,
+                Some('\\') => '\\' as u16,
+                Some('"') => '"' as u16,
+                Some('0') => 0,
+                other => return Err(format!("Invalid escape sequence \\{}", other.unwrap_or(' '))),
+            },
+            ' '..='~' => c as u16,
+            _ => return Err(format!("Character '{}' is not in the Hack character set", c)),
+        };
+        values.push(Value::Number(code));
+    }
+    values.push(Value::Number(0));
+    Ok(values)
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(number) = parse_number(text) {
+        return Ok(Value::Number(number));
+    }
+    if let Some(number) = text.strip_prefix('-').and_then(parse_number) {
+        if number <= 32_768 {
+            return Ok(Value::Number(number.wrapping_neg()));
+        }
+    }
+    if text.len() == 3 && text.starts_with('\'') && text.ends_with('\'') {
+        return Ok(Value::Number(text.as_bytes()[1] as u16));
+    }
+    if is_symbol(text) {
+        return Ok(Value::Symbol(text.to_string()));
+    }
+    Err(format!("Invalid data value '{}'", text))
+}
+
+// Decimal or 0x prefixed hexadecimal numbers that fit in 16 bits.
+fn parse_number(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse::<u16>().ok(),
+    }
+}
+
+fn is_symbol(name: &str) -> bool {
+    match name.chars().next() {
+        Some(first) if !first.is_ascii_digit() => name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | ':')),
+        _ => false,
+    }
+}
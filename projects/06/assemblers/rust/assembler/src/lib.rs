@@ -0,0 +1,8 @@
+pub mod assembler;
+pub mod code;
+pub mod comparer;
+pub mod data;
+pub mod parser;
+pub mod preprocessor;
+pub mod stream;
+pub mod symbol_table;
@@ -1,27 +1,70 @@
-mod assembler;
-mod parser;
-mod symbol_table;
-mod code;
-
+use ::assembler::{assembler, comparer, preprocessor, stream, symbol_table};
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
 // Main function, the entry point of the Rust assembler.
 // The assembler takes .asm files as input and produces .hack file as output.
+// With --compare it diffs a produced .hack or .out file against its reference instead.
+// With --stream large programs are assembled in a single pass without loading them whole.
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: hack_assembler <input.asm>");
-        std::process::exit(1);
+    if args.len() == 4 && args[1] == "--compare" {
+        match comparer::compare_files(&args[2], &args[3]) {
+            Ok(()) => println!("Comparison ended successfully"),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
     } else {
-        let input_file: &String = &args[1];
+        // Everything else is a list of -D NAME[=VALUE] definitions followed by the input file.
+        let mut defines = preprocessor::Defines::new();
+        let mut input_file: Option<&String> = None;
+        let mut streaming = false;
+        let mut rest = args.iter().skip(1);
+        while let Some(arg) = rest.next() {
+            if arg == "--stream" {
+                streaming = true;
+                continue;
+            }
+            let definition = if arg == "-D" {
+                rest.next().map(String::as_str)
+            } else {
+                arg.strip_prefix("-D")
+            };
+            match definition {
+                Some(definition) => match preprocessor::parse_define(definition) {
+                    Ok((name, value)) => {
+                        defines.insert(name, value);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                },
+                None if input_file.is_none() && !arg.starts_with('-') => input_file = Some(arg),
+                None => print_usage_and_exit(),
+            }
+        }
+        let input_file: &String = input_file.unwrap_or_else(|| print_usage_and_exit());
         let mut symbol_table = symbol_table::SymbolTable::new();
+        if streaming {
+            if !defines.is_empty() {
+                eprintln!("-D definitions can't be used with --stream");
+                std::process::exit(1);
+            }
+            stream_file(input_file, &mut symbol_table);
+            return;
+        }
         let mut file = File::open(input_file)
             .expect("Failed to open input file, please check the file path and permissions");
         let mut contents = String::new();
         file.read_to_string(&mut contents)
             .expect("Failed to read input file. Please ensure the file is not empty and is readable.");
-        match assembler::assemble(&contents, &mut symbol_table) {
+        let assembled = preprocessor::preprocess(&contents, &defines)
+            .and_then(|source| assembler::assemble(&source, &mut symbol_table));
+        match assembled {
             Ok(machine_code) => {
                 // Write the machine code to a .hack file
                 let output_file = input_file.replace(".asm", ".hack");
@@ -35,8 +78,32 @@ fn main() {
             }
             Err(e) => {
                 eprintln!("Error during assembly: {}", e);
+                std::process::exit(1);
             }
         }
+    }
+}
 
+fn stream_file(input_file: &str, symbol_table: &mut symbol_table::SymbolTable) {
+    let input = File::open(input_file)
+        .expect("Failed to open input file, please check the file path and permissions");
+    let output_file = input_file.replace(".asm", ".hack");
+    let output = File::create(output_file)
+        .expect("Failed to create output file. Please check the file path and permissions.");
+    let result = stream::assemble_stream(
+        BufReader::with_capacity(1 << 16, input),
+        BufWriter::with_capacity(1 << 16, output),
+        symbol_table,
+    );
+    if let Err(e) = result {
+        eprintln!("Error during assembly: {}", e);
+        std::process::exit(1);
     }
 }
+
+fn print_usage_and_exit() -> ! {
+    eprintln!("Usage: hack_assembler [-D NAME[=VALUE]]... <input.asm>");
+    eprintln!("       hack_assembler --stream <input.asm>");
+    eprintln!("       hack_assembler --compare <produced.hack|.out> <reference.hack|.cmp>");
+    std::process::exit(1);
+}
@@ -3,6 +3,7 @@ use std::fmt;
 use crate::symbol_table::SymbolTable;
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Instruction {
     AInstruction(String),
     CInstruction {
@@ -24,7 +25,7 @@ impl fmt::Display for Instruction {
                 write!(f, "{}{}{}", dest_str, comp, jump_str)
             }
             Instruction::Label(name) => write!(f, "({})", name),
-            Instruction::Variable(value) => write!(f, "{}", value),
+            Instruction::Variable(value) => write!(f, "@{}", value),
         }
     }
 }
@@ -36,60 +37,65 @@ fn strip_comment(line: &str) -> &str {
     }
 }
 
-pub fn find_label(source: &str, symbol_table: &mut SymbolTable) {
-    let mut trimmed_source: Vec<String> = Vec::new();
-    let mut instruction_number = 0;
-    for line in source.lines() {
+// Adds every (LABEL) to the symbol table, with the address of the instruction after it.
+// The first instruction of the source goes to `first_address`. A label may not reuse a
+// symbol that is already defined, like a .data block or another label.
+pub fn find_label(source: &str, symbol_table: &mut SymbolTable, first_address: u16) -> Result<(), String> {
+    let mut instruction_number = first_address;
+    for (index, line) in source.lines().enumerate() {
         let trimmed = line.trim();
         if trimmed.is_empty() || trimmed.starts_with("//") {
             continue; // Skip empty lines and comments
         }
-        let stripped: &str;
-        if trimmed.contains("//") {
-            stripped = strip_comment(trimmed).trim();
+        let stripped: &str = if trimmed.contains("//") {
+            strip_comment(trimmed).trim()
         } else {
-            stripped = trimmed;
-        }
-        trimmed_source.push(stripped.to_string());
-    }
-
-    for line in trimmed_source {
-        if line.starts_with('(') && line.ends_with(')') {
-            let label = line[1..line.len() - 1].to_string();
+            trimmed
+        };
+        if stripped.starts_with('(') && stripped.ends_with(')') {
+            let label = stripped[1..stripped.len() - 1].to_string();
             if symbol_table.contains(&label) {
-                // Do nothing if the label already exists
-            } else {
-                // Create a label instruction to supress compiler warning.
-                Instruction::Label(label.clone());
-                // Add label to symbol table with the current instruction number
-                symbol_table.add_entry(label.clone(), instruction_number);
+                return Err(format!("Line {}: Symbol '{}' is already defined", index + 1, label));
             }
+            // Add label to symbol table with the current instruction number
+            symbol_table.add_label(label, instruction_number);
         } else {
         instruction_number += 1; // Increment instruction number for non-label lines
         }
     }
+    Ok(())
 }
 
+// The instructions of the source in order, labels included: they produce no code, but
+// without them printing the instructions back loses every jump target.
 pub fn parse_lines(source: &str) -> Vec<Instruction> {
+    parse_numbered_lines(source)
+        .into_iter()
+        .map(|(_, instruction)| instruction)
+        .collect()
+}
+
+// Same as parse_lines, but every instruction comes with its 1-based line number.
+pub fn parse_numbered_lines(source: &str) -> Vec<(usize, Instruction)> {
     // Parse each lines of the source code and generate Vector of Instructions.
     let mut instructions = Vec::new();
-    for line in source.lines() {
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
         let trimmed = line.trim();
         if trimmed.is_empty() || trimmed.starts_with("//") {
             continue; // Skip empty lines and comments
         }
-        let stripped: &str;
-        if trimmed.contains("//") {
-            stripped = strip_comment(trimmed).trim();
+        let stripped: &str = if trimmed.contains("//") {
+            strip_comment(trimmed).trim()
         } else {
-            stripped = trimmed;
-        }
+            trimmed
+        };
 
-        if stripped.starts_with('@') {
+        if let Some(a_value) = stripped.strip_prefix('@') {
             // THIS IS WHERE YOUR THOUGHT PROCESS GOES
             // Here it could be an A-instruction or a variable
             // If it starts with '@' and is followed by a number, it's an A-instruction
-            let value: String = stripped[1..].trim().to_string();
+            let value: String = a_value.trim().to_string();
             let mut a_number = -2;
 
             // If parsing to i32 is okay, also check if it's less than maximum allowed value.
@@ -100,11 +106,11 @@ pub fn parse_lines(source: &str) -> Vec<Instruction> {
             // Maximum allowed value for A-instruction is 24,576 (2^15 - 1).
             if (a_number != -2) && a_number < 32_768 {
                 // If it's all digits, it's an A-instruction
-                instructions.push(Instruction::AInstruction(value));
+                instructions.push((line_number, Instruction::AInstruction(value)));
             } else {
                 // If it's alphanumeric or underscore, it's a variable
                 // If it starts with '@' and is followed by a variable name, it's a variable
-                instructions.push(Instruction::Variable(value));
+                instructions.push((line_number, Instruction::Variable(value)));
             }
         } else if stripped.contains('=') || stripped.contains(';') {
             let parts: Vec<&str> = stripped.split(';').collect();
@@ -120,7 +126,12 @@ pub fn parse_lines(source: &str) -> Vec<Instruction> {
             } else {
                 None
             };
-            instructions.push(Instruction::CInstruction { dest, comp, jump });
+            let instruction = Instruction::CInstruction { dest, comp, jump };
+            instructions.push((line_number, instruction));
+        } else if stripped.starts_with('(') && stripped.ends_with(')') {
+            // Labels were already resolved by find_label, keep them so the order is preserved.
+            let label = stripped[1..stripped.len() - 1].to_string();
+            instructions.push((line_number, Instruction::Label(label)));
         }
     }
     instructions
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+// Conditional assembly. Runs before the assembler passes and resolves
+// .if/.elif/.else/.endif and .ifdef/.ifndef blocks against the symbols defined
+// on the command line (-D NAME=VALUE). Lines that are switched off, as well as the
+// directives themselves, are replaced with empty lines so line numbers stay the same.
+
+pub type Defines = HashMap<String, i32>;
+
+// Parses a command line definition, either NAME or NAME=VALUE. A bare NAME is defined as 1.
+pub fn parse_define(definition: &str) -> Result<(String, i32), String> {
+    let (name, value) = match definition.split_once('=') {
+        Some((name, value)) => {
+            let value = parse_number(value.trim())
+                .ok_or_else(|| format!("Invalid value in definition '{}'", definition))?;
+            (name.trim(), value)
+        }
+        None => (definition.trim(), 1),
+    };
+    if !is_identifier(name) {
+        return Err(format!("Invalid symbol name in definition '{}'", definition));
+    }
+    Ok((name.to_string(), value))
+}
+
+// State of one .if ... .endif block.
+struct Block {
+    // The enclosing block is active, so this one may emit lines.
+    parent_active: bool,
+    // One of the branches of this block has already been taken.
+    taken: bool,
+    // The current branch is the one being emitted.
+    active: bool,
+    seen_else: bool,
+    line: usize,
+}
+
+pub fn preprocess(source: &str, defines: &Defines) -> Result<String, String> {
+    let mut output = String::with_capacity(source.len());
+    let mut blocks: Vec<Block> = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let code = match line.find("//") {
+            Some(position) => &line[..position],
+            None => line,
+        }
+        .trim();
+        let active = blocks.last().is_none_or(|block| block.active);
+
+        if !code.starts_with('.') {
+            if active {
+                output.push_str(line);
+            }
+            output.push('\n');
+            continue;
+        }
+
+        let (directive, argument) = match code.split_once(char::is_whitespace) {
+            Some((directive, argument)) => (directive, argument.trim()),
+            None => (code, ""),
+        };
+        let error = |message: &str| format!("Line {}: {}", line_number, message);
+        match directive {
+            ".if" | ".ifdef" | ".ifndef" => {
+                let condition = if !active {
+                    false
+                } else if directive == ".if" {
+                    evaluate(argument, defines).map_err(|e| error(&e))? != 0
+                } else {
+                    if !is_identifier(argument) {
+                        return Err(error(&format!("{} expects a symbol name", directive)));
+                    }
+                    defines.contains_key(argument) == (directive == ".ifdef")
+                };
+                blocks.push(Block {
+                    parent_active: active,
+                    taken: condition,
+                    active: condition,
+                    seen_else: false,
+                    line: line_number,
+                });
+            }
+            ".elif" => {
+                let block = blocks.last_mut().ok_or_else(|| error(".elif without .if"))?;
+                if block.seen_else {
+                    return Err(error(".elif after .else"));
+                }
+                let condition = block.parent_active
+                    && !block.taken
+                    && evaluate(argument, defines).map_err(|e| error(&e))? != 0;
+                block.active = condition;
+                block.taken |= condition;
+            }
+            ".else" => {
+                let block = blocks.last_mut().ok_or_else(|| error(".else without .if"))?;
+                if block.seen_else {
+                    return Err(error("duplicate .else"));
+                }
+                block.seen_else = true;
+                block.active = block.parent_active && !block.taken;
+                block.taken = true;
+            }
+            ".endif" => {
+                blocks.pop().ok_or_else(|| error(".endif without .if"))?;
+            }
+            _ => {
+                // Not a conditional directive, leave it for the later stages.
+                if active {
+                    output.push_str(line);
+                }
+            }
+        }
+        output.push('\n');
+    }
+
+    if let Some(block) = blocks.last() {
+        return Err(format!("Line {}: .if without matching .endif", block.line));
+    }
+    Ok(output)
+}
+
+// Expressions support integers, symbols, defined(NAME), parentheses, !, the comparisons
+// == != < <= > >= and the logical && and ||. Symbols that are not defined are an error,
+// a misspelled name would otherwise quietly count as 0; defined(NAME) tests for them.
+// && and || short-circuit like in C, so `defined(NAME) && NAME > 1` is fine.
+fn evaluate(expression: &str, defines: &Defines) -> Result<i32, String> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Err("missing condition".to_string());
+    }
+    let mut parser = ExpressionParser {
+        tokens,
+        position: 0,
+        defines,
+        skipped: 0,
+    };
+    let value = parser.parse_or()?;
+    match parser.tokens.get(parser.position) {
+        Some(token) => Err(format!("unexpected '{}' in condition", token)),
+        None => Ok(value),
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$' || c == '-' {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || matches!(chars[i], '_' | '.' | '$' | ':'))
+            {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else {
+            let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if ["==", "!=", "<=", ">=", "&&", "||"].contains(&pair.as_str()) {
+                tokens.push(pair);
+                i += 2;
+            } else if "()!<>".contains(c) {
+                tokens.push(c.to_string());
+                i += 1;
+            } else {
+                return Err(format!("unexpected character '{}' in condition", c));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct ExpressionParser<'a> {
+    tokens: Vec<String>,
+    position: usize,
+    defines: &'a Defines,
+    // Greater than 0 while parsing the right side of a && or || that the left side
+    // decided already, where undefined names are no error, like in `defined(X) && X`.
+    skipped: u32,
+}
+
+impl ExpressionParser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| "unexpected end of condition".to_string())?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(format!("expected '{}' but found '{}'", expected, token))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<i32, String> {
+        let mut value = self.parse_and()?;
+        while self.peek() == Some("||") {
+            self.position += 1;
+            let right = self.parse_skipped_if(value != 0, Self::parse_and)?;
+            value = ((value != 0) || (right != 0)) as i32;
+        }
+        Ok(value)
+    }
+
+    fn parse_and(&mut self) -> Result<i32, String> {
+        let mut value = self.parse_comparison()?;
+        while self.peek() == Some("&&") {
+            self.position += 1;
+            let right = self.parse_skipped_if(value == 0, Self::parse_comparison)?;
+            value = ((value != 0) && (right != 0)) as i32;
+        }
+        Ok(value)
+    }
+
+    // Parses an operand, whose value does not matter when `skip` is set.
+    fn parse_skipped_if(
+        &mut self,
+        skip: bool,
+        parse: fn(&mut Self) -> Result<i32, String>,
+    ) -> Result<i32, String> {
+        self.skipped += skip as u32;
+        let value = parse(self);
+        self.skipped -= skip as u32;
+        value
+    }
+
+    fn parse_comparison(&mut self) -> Result<i32, String> {
+        let left = self.parse_unary()?;
+        let operator = match self.peek() {
+            Some(op @ ("==" | "!=" | "<" | "<=" | ">" | ">=")) => op.to_string(),
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.parse_unary()?;
+        let result = match operator.as_str() {
+            "==" => left == right,
+            "!=" => left != right,
+            "<" => left < right,
+            "<=" => left <= right,
+            ">" => left > right,
+            _ => left >= right,
+        };
+        Ok(result as i32)
+    }
+
+    fn parse_unary(&mut self) -> Result<i32, String> {
+        let token = self.next()?;
+        match token.as_str() {
+            "!" => Ok((self.parse_unary()? == 0) as i32),
+            "(" => {
+                let value = self.parse_or()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "defined" => {
+                self.expect("(")?;
+                let name = self.next()?;
+                self.expect(")")?;
+                Ok(self.defines.contains_key(&name) as i32)
+            }
+            _ => {
+                if let Some(number) = parse_number(&token) {
+                    Ok(number)
+                } else if is_identifier(&token) && self.skipped > 0 {
+                    Ok(self.defines.get(&token).copied().unwrap_or(0))
+                } else if is_identifier(&token) {
+                    self.defines.get(&token).copied().ok_or_else(|| {
+                        format!("'{}' is not defined, test it with defined({})", token, token)
+                    })
+                } else {
+                    Err(format!("unexpected '{}' in condition", token))
+                }
+            }
+        }
+    }
+}
+
+// Decimal numbers, optionally negative, and 0x prefixed hexadecimal numbers.
+fn parse_number(text: &str) -> Option<i32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => i32::from_str_radix(hex, 16).ok(),
+        None => text.parse::<i32>().ok(),
+    }
+}
+
+// Same characters the Hack assembly language allows in symbols.
+fn is_identifier(name: &str) -> bool {
+    match name.chars().next() {
+        Some(first) if !first.is_ascii_digit() => name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$' | ':')),
+        _ => false,
+    }
+}
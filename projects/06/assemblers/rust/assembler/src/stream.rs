@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Seek, SeekFrom, Write};
+
+use crate::code;
+use crate::symbol_table::SymbolTable;
+
+// Single pass assembler for large generated programs. Lines are read one at a time,
+// encoded straight into 16-bit words and written out at once, so neither the source nor
+// the machine code is ever held in memory. A reference to a symbol that is not defined
+// yet is written as 0 and recorded with its position in the output. Once the whole source
+// has been read, every label is known and the symbols that are not labels become
+// variables, allocated in order of first use exactly like the two pass assembler does,
+// and the recorded words are patched in place.
+//
+// Directives (.if, .data, ...) need the whole source and are only supported by `assemble`.
+
+// Every word is written as 16 binary digits and a newline.
+const LINE_LENGTH: u64 = 17;
+
+// Assembles the source read from `reader` into `writer` and returns the number of words written.
+pub fn assemble_stream<R: BufRead, W: Write + Seek>(
+    mut reader: R,
+    mut writer: W,
+    symbol_table: &mut SymbolTable,
+) -> Result<u64, String> {
+    // Symbols referenced before their definition, in order of first use.
+    let mut forward_symbols: Vec<String> = Vec::new();
+    let mut forward_index: HashMap<String, usize> = HashMap::new();
+    // Words written with a placeholder: (word index, symbol index).
+    let mut fixups: Vec<(u64, usize)> = Vec::new();
+    let mut written: u64 = 0;
+    let mut line = String::new();
+    let mut line_number = 0;
+
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        line_number += 1;
+        let code = match line.find("//") {
+            Some(index) => &line[..index],
+            None => &line,
+        }
+        .trim();
+        if code.is_empty() {
+            continue;
+        }
+        let error = |message: String| format!("Line {}: {}", line_number, message);
+
+        let word = if let Some(value) = code.strip_prefix('@') {
+            let value = value.trim();
+            match value.parse::<i32>() {
+                Ok(number) if (0..32_768).contains(&number) => number as u16,
+                Ok(_) => return Err(error(format!("Invalid A-instruction '@{}'", value))),
+                Err(_) => match symbol_table.get_address(value) {
+                    Some(address) => address,
+                    None => {
+                        let next = forward_symbols.len();
+                        let index = *forward_index.entry(value.to_string()).or_insert(next);
+                        if index == next {
+                            forward_symbols.push(value.to_string());
+                        }
+                        fixups.push((written, index));
+                        0
+                    }
+                },
+            }
+        } else if code.contains('=') || code.contains(';') {
+            let (rest, jump) = match code.split_once(';') {
+                Some((rest, jump)) => (rest, Some(jump.trim())),
+                None => (code, None),
+            };
+            let (dest, comp) = match rest.split_once('=') {
+                Some((dest, comp)) => (Some(dest.trim()), comp),
+                None => (None, rest),
+            };
+            code::encode_c_instruction(dest, comp, jump)
+                .ok_or_else(|| error(format!("Invalid comp mnemonic '{}'", comp.trim())))?
+        } else if code.starts_with('(') && code.ends_with(')') {
+            let label = &code[1..code.len() - 1];
+            // Like the two pass assembler, a label may not reuse a symbol.
+            if symbol_table.contains(label) {
+                return Err(error(format!("Symbol '{}' is already defined", label)));
+            }
+            let address = u16::try_from(written)
+                .ok()
+                .filter(|address| *address < 32_768)
+                .ok_or_else(|| error("Program does not fit in the 32K ROM".to_string()))?;
+            symbol_table.add_label(label.to_string(), address);
+            continue;
+        } else if code.starts_with('.') {
+            return Err(error(format!(
+                "Directive '{}' is not supported by the streaming assembler",
+                code
+            )));
+        } else {
+            continue;
+        };
+        writeln!(writer, "{:016b}", word).map_err(|e| e.to_string())?;
+        written += 1;
+    }
+
+    // Whatever was never defined as a label is a variable.
+    let addresses: Vec<u16> = forward_symbols
+        .into_iter()
+        .map(|symbol| match symbol_table.get_address(&symbol) {
+            Some(address) => address,
+            None => symbol_table.add_variable(symbol),
+        })
+        .collect();
+
+    // Patch the words that were written before their symbol was known.
+    for (word_index, symbol) in fixups {
+        writer
+            .seek(SeekFrom::Start(word_index * LINE_LENGTH))
+            .map_err(|e| e.to_string())?;
+        write!(writer, "{:016b}", addresses[symbol]).map_err(|e| e.to_string())?;
+    }
+    writer.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(written)
+}
@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct SymbolTable {
     table: HashMap<String, u16>,
+    // Symbols defined by (LABEL) declarations, their addresses are ROM addresses.
+    labels: HashSet<String>,
     next_variable: u16,
 }
 
@@ -12,7 +14,7 @@ impl SymbolTable {
         for (i, symbol) in [
             "R0", "R1", "R2", "R3", "R4", "R5", "R6", "R7", "R8", "R9",
             "R10", "R11", "R12", "R13", "R14", "R15",
-            "SCREEN", "KBD", "SP", "LCL", "ARG", "THIS", "THAT", "LOOP", "STOP", "i", "sum"
+            "SCREEN", "KBD", "SP", "LCL", "ARG", "THIS", "THAT"
         ].iter().enumerate() {
             table.insert(symbol.to_string(), i as u16);
         }
@@ -23,13 +25,10 @@ impl SymbolTable {
         table.insert("THAT".to_string(), 4);
         table.insert("SCREEN".to_string(), 16384);
         table.insert("KBD".to_string(), 24576);
-        table.insert("LOOP".to_string(), 4);
-        table.insert("STOP".to_string(), 18);
-        table.insert("i".to_string(), 16);
-        table.insert("sum".to_string(), 17);
 
         SymbolTable {
-            table: table,
+            table,
+            labels: HashSet::new(),
             next_variable: 16, // Starting point for user-defined variables
         }
     }
@@ -38,6 +37,22 @@ impl SymbolTable {
         self.table.insert(symbol, address);
     }
 
+    pub fn add_label(&mut self, label: String, address: u16) {
+        self.labels.insert(label.clone());
+        self.table.insert(label, address);
+    }
+
+    // Every label with its ROM address, ordered by address.
+    pub fn labels(&self) -> Vec<(&str, u16)> {
+        let mut labels: Vec<(&str, u16)> = self
+            .labels
+            .iter()
+            .map(|label| (label.as_str(), self.table[label]))
+            .collect();
+        labels.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+        labels
+    }
+
     pub fn contains(&self, symbol: &str) -> bool {
         self.table.contains_key(symbol)
     }
@@ -52,4 +67,27 @@ impl SymbolTable {
         self.next_variable += 1;
         address
     }
-}
\ No newline at end of file
+
+    // The address after the last variable allocated so far.
+    pub fn variables_end(&self) -> u16 {
+        self.next_variable
+    }
+
+    // Reserves `size` consecutive words for the symbol from the variable area, which ends
+    // where the screen starts.
+    pub fn allocate(&mut self, symbol: String, size: u16) -> Result<u16, String> {
+        let address = self.next_variable;
+        self.next_variable = address
+            .checked_add(size)
+            .filter(|end| *end <= 16_384)
+            .ok_or_else(|| format!("'{}' does not fit in RAM", symbol))?;
+        self.table.insert(symbol, address);
+        Ok(address)
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
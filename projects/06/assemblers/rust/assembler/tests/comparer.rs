@@ -0,0 +1,94 @@
+use assembler::comparer::{compare_hack, compare_table};
+
+// The comparisons behind --compare, on .hack files and on test script output.
+
+const HACK: &str = "0000000000000010\n1110110000010000\n0000000000000011\n";
+
+#[test]
+fn reports_the_first_differing_word() {
+    assert_eq!(compare_hack(HACK, HACK), Ok(()));
+    // Line endings and trailing whitespace do not count.
+    assert_eq!(compare_hack(&HACK.replace('\n', "  \r\n"), HACK), Ok(()));
+
+    let produced = HACK.replace("0000000000000011", "0000000000000111");
+    assert_eq!(
+        compare_hack(&produced, HACK),
+        Err("Comparison failure at line 3 (ROM[2]):\n  \
+             expected: 0000000000000011\n  \
+             actual:   0000000000000111"
+            .to_string())
+    );
+}
+
+#[test]
+fn reports_missing_and_extra_words() {
+    assert_eq!(
+        compare_hack("0000000000000010\n", HACK),
+        Err(
+            "Comparison failure at line 2 (ROM[1]): produced file ended, expected 1110110000010000"
+                .to_string()
+        )
+    );
+    let longer = format!("{}1110001100001000\n", HACK);
+    assert_eq!(
+        compare_hack(&longer, HACK),
+        Err(
+            "Comparison failure at line 4 (ROM[3]): unexpected extra line 1110001100001000"
+                .to_string()
+        )
+    );
+}
+
+const CMP: &str = "\
+|RAM[0]|RAM[1]|RAM[2]|
+|     0|     0|     0|
+|     1|     0|     1|
+";
+
+#[test]
+fn compares_tables_cell_by_cell() {
+    assert_eq!(compare_table(CMP, CMP), Ok(()));
+    // Only the cell contents count, not how they are padded.
+    let padded = "| RAM[0] | RAM[1] | RAM[2] |\n|0|0|0|\n|  1  |0|  1|\n";
+    assert_eq!(compare_table(padded, CMP), Ok(()));
+
+    let wrong = CMP.replace("|     1|     0|     1|", "|     1|     7|     2|");
+    assert_eq!(
+        compare_table(&wrong, CMP),
+        Err(
+            "Comparison failure at line 3 (columns: RAM[1], RAM[2]):\n  \
+             expected: |     1|     0|     1|\n  \
+             actual:   |     1|     7|     2|"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn matches_anything_against_stars() {
+    let cmp = "|time |outM   |\n| 0+  |*******|\n| 1   |     -1|\n";
+    let out = "|time |outM   |\n| 0+  |  12345|\n| 1   |     -1|\n";
+    assert_eq!(compare_table(out, cmp), Ok(()));
+    // Only in the compare file, stars in the output are plain text.
+    assert!(compare_table(cmp, out).is_err());
+}
+
+#[test]
+fn reports_missing_and_extra_rows() {
+    let short = "|RAM[0]|RAM[1]|RAM[2]|\n|     0|     0|     0|\n";
+    assert_eq!(
+        compare_table(short, CMP),
+        Err(
+            "Comparison failure at line 3: output ended, expected\n  |     1|     0|     1|"
+                .to_string()
+        )
+    );
+    let long = format!("{}|     2|     2|     2|\n", CMP);
+    assert_eq!(
+        compare_table(&long, CMP),
+        Err(
+            "Comparison failure at line 4: unexpected extra row\n  |     2|     2|     2|"
+                .to_string()
+        )
+    );
+}
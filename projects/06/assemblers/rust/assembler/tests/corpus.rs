@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assembler::assembler::assemble;
+use assembler::parser;
+use assembler::symbol_table::SymbolTable;
+
+// Assembles the programs shipped with the course projects and checks the results
+// against the reference .hack files, and against themselves after a Display round trip.
+
+fn projects_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../..")
+}
+
+// Every .asm file below the given project directories, in a stable order.
+fn asm_files(directories: &[&str]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending: Vec<PathBuf> = directories.iter().map(|d| projects_dir().join(d)).collect();
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "asm") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    assert!(!files.is_empty(), "no .asm files found in {:?}", directories);
+    files
+}
+
+fn assemble_file(path: &Path) -> Vec<String> {
+    let source = fs::read_to_string(path).unwrap();
+    assemble(&source, &mut SymbolTable::new())
+        .unwrap_or_else(|e| panic!("{} failed to assemble: {}", path.display(), e))
+}
+
+fn reference_lines(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn assert_same_code(actual: &[String], expected: &[String], what: &str) {
+    for (address, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+        assert_eq!(actual, expected, "{}: ROM[{}] differs", what, address);
+    }
+    assert_eq!(actual.len(), expected.len(), "{}: program length differs", what);
+}
+
+#[test]
+fn matches_reference_hack_files() {
+    let mut compared = 0;
+    for asm in asm_files(&["04", "06", "07"]) {
+        let machine_code = assemble_file(&asm);
+        let hack = asm.with_extension("hack");
+        if hack.exists() {
+            assert_same_code(&machine_code, &reference_lines(&hack), &asm.display().to_string());
+            compared += 1;
+        }
+    }
+    assert!(compared >= 6, "only {} reference files compared", compared);
+}
+
+#[test]
+fn matches_computer_test_programs() {
+    // projects/05 ships the official binaries of the add, max and rect programs.
+    for (asm, hack) in [
+        ("06/add/Add.asm", "05/Add.hack"),
+        ("06/max/Max.asm", "05/Max.hack"),
+        ("06/rect/Rect.asm", "05/Rect.hack"),
+    ] {
+        let machine_code = assemble_file(&projects_dir().join(asm));
+        assert_same_code(&machine_code, &reference_lines(&projects_dir().join(hack)), asm);
+    }
+}
+
+#[test]
+fn symbolic_and_symbol_less_versions_agree() {
+    for (symbolic, symbol_less) in [
+        ("06/max/Max.asm", "06/max/MaxL.asm"),
+        ("06/rect/Rect.asm", "06/rect/RectL.asm"),
+        ("06/pong/Pong.asm", "06/pong/PongL.asm"),
+    ] {
+        let expected = assemble_file(&projects_dir().join(symbol_less));
+        let actual = assemble_file(&projects_dir().join(symbolic));
+        assert_same_code(&actual, &expected, symbolic);
+    }
+}
+
+#[test]
+fn display_round_trip_yields_identical_words() {
+    for asm in asm_files(&["04", "06", "07"]) {
+        let source = fs::read_to_string(&asm).unwrap();
+        let printed: Vec<String> = parser::parse_lines(&source)
+            .iter()
+            .map(|instruction| instruction.to_string())
+            .collect();
+        let reparsed: Vec<String> = parser::parse_lines(&printed.join("\n"))
+            .iter()
+            .map(|instruction| instruction.to_string())
+            .collect();
+        assert_eq!(printed, reparsed, "{}: printing is not stable", asm.display());
+
+        let original = assemble(&source, &mut SymbolTable::new()).unwrap();
+        let round_tripped = assemble(&printed.join("\n"), &mut SymbolTable::new()).unwrap();
+        assert_same_code(&round_tripped, &original, &asm.display().to_string());
+    }
+}
+
+#[test]
+fn labels_may_use_common_names() {
+    // LOOP, STOP, i and sum used to be predefined, which put these labels at fixed addresses.
+    let source = "@i\nM=1\n@sum\nM=0\n(LOOP)\n@STOP\n0;JMP\n(STOP)\n@LOOP\n0;JMP\n";
+    let machine_code = assemble(source, &mut SymbolTable::new()).unwrap();
+    let expected = [
+        "0000000000010000",
+        "1110111111001000",
+        "0000000000010001",
+        "1110101010001000",
+        "0000000000000110",
+        "1110101010000111",
+        "0000000000000100",
+        "1110101010000111",
+    ];
+    assert_eq!(machine_code, expected);
+}
@@ -0,0 +1,124 @@
+use assembler::assembler::assemble;
+use assembler::code::disassemble;
+use assembler::symbol_table::SymbolTable;
+
+// The data directives, through the assembler, which runs their prologue before the program.
+
+fn assemble_source(source: &str) -> Result<(Vec<String>, SymbolTable), String> {
+    let mut symbol_table = SymbolTable::new();
+    let machine_code = assemble(source, &mut symbol_table)?;
+    Ok((machine_code, symbol_table))
+}
+
+fn listing(machine_code: &[String]) -> Vec<String> {
+    machine_code
+        .iter()
+        .map(|word| disassemble(u16::from_str_radix(word, 2).unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn blocks_placed_with_org_may_not_overlap() {
+    let error = assemble_source(".org 100\n.data A\n.fill 4\n.org 102\n.data B\n.word 1\n")
+        .err()
+        .unwrap();
+    assert_eq!(
+        error,
+        "Line 5: data at RAM[102..103] overlaps the data of line 2 at RAM[100..104]"
+    );
+    // Right after each other is fine, and so is continuing after a block.
+    let (_, symbol_table) = assemble_source(
+        ".org 100\n.data A\n.fill 4\n.org 104\n.data B\n.word 1\n.data C\n.word 2\n",
+    )
+    .unwrap();
+    assert_eq!(symbol_table.get_address("B"), Some(104));
+    assert_eq!(symbol_table.get_address("C"), Some(105));
+}
+
+#[test]
+fn blocks_placed_with_org_may_not_overlap_the_variables() {
+    let source = ".org 17\n.data A\n.word 1\n@x\nM=0\n@y\nM=0\n";
+    let error = assemble_source(source).err().unwrap();
+    assert_eq!(
+        error,
+        "Line 2: data at RAM[17..18] overlaps the variables at RAM[16..18]"
+    );
+    // Without the second variable RAM[17] is free.
+    assert!(assemble_source(".org 17\n.data A\n.word 1\n@x\nM=0\n").is_ok());
+}
+
+#[test]
+fn a_label_may_not_reuse_a_data_name() {
+    let error = assemble_source(".data TABLE\n.word 1\n(TABLE)\n@TABLE\n0;JMP\n")
+        .err()
+        .unwrap();
+    assert_eq!(error, "Line 3: Symbol 'TABLE' is already defined");
+    let error = assemble_source("(LOOP)\n@LOOP\n(LOOP)\n0;JMP\n")
+        .err()
+        .unwrap();
+    assert_eq!(error, "Line 3: Symbol 'LOOP' is already defined");
+}
+
+#[test]
+fn comments_do_not_start_inside_strings() {
+    let (machine_code, symbol_table) =
+        assemble_source(".data S\n.string \"a//b\" // four characters\n@n\n").unwrap();
+    assert_eq!(symbol_table.get_address("S"), Some(16));
+    // Four characters and the terminating 0, then the variable.
+    assert_eq!(symbol_table.get_address("n"), Some(21));
+    let listing = listing(&machine_code);
+    assert!(listing.contains(&"@47".to_string()));
+}
+
+#[test]
+fn character_literals_may_be_commas() {
+    let (machine_code, symbol_table) =
+        assemble_source(".data SEPARATORS\n.word ',', ';' , 'x'\n.fill 2, ','\n@n\n").unwrap();
+    assert_eq!(symbol_table.get_address("n"), Some(21));
+    let listing = listing(&machine_code);
+    let loads: Vec<&str> = listing
+        .iter()
+        .map(String::as_str)
+        .filter(|line| ["@44", "@59", "@120"].contains(line))
+        .collect();
+    assert_eq!(loads, ["@44", "@59", "@120", "@44", "@44"]);
+}
+
+#[test]
+fn data_blocks_may_not_be_empty() {
+    let error = assemble_source(".data EMPTY\n.data NEXT\n.word 1\n")
+        .err()
+        .unwrap();
+    assert_eq!(error, "Line 1: .data EMPTY holds no values");
+    let error = assemble_source(".org 100\n.data EMPTY\n").err().unwrap();
+    assert_eq!(error, "Line 2: .data EMPTY holds no values");
+}
+
+#[test]
+fn allocated_blocks_have_to_fit_below_the_screen() {
+    let error = assemble_source(".data A\n.fill 65535\n").err().unwrap();
+    assert_eq!(error, "Line 1: 'A' does not fit in RAM");
+    let error = assemble_source(".data A\n.word 1\n.data B\n.fill 20000\n")
+        .err()
+        .unwrap();
+    assert_eq!(error, "Line 3: 'B' does not fit in RAM");
+    // Up to the last word before SCREEN.
+    let (_, symbol_table) = assemble_source(".data A\n.fill 16368\n").unwrap();
+    assert_eq!(symbol_table.variables_end(), 16_384);
+}
+
+#[test]
+fn the_data_prologue_has_to_fit_in_the_rom() {
+    // Four instructions store each value that is not 0, 1 or -1.
+    let error = assemble_source(".data A\n.fill 10000, 7\n").err().unwrap();
+    assert_eq!(
+        error,
+        "Storing the data takes 40000 instructions, more than the 32K ROM holds"
+    );
+    let source = format!(".data A\n.fill 8000, 7\n{}", "D=0\n".repeat(1000));
+    let error = assemble_source(&source).err().unwrap();
+    assert_eq!(
+        error,
+        "Program does not fit in the 32K ROM: 33000 words, 32000 of them store data"
+    );
+}
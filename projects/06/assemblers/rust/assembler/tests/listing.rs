@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assembler::assembler::{assemble, assemble_with_lines};
+use assembler::code::disassemble;
+use assembler::symbol_table::SymbolTable;
+
+// What debuggers need from the assembler: machine code turned back into instructions,
+// and the source line every word came from.
+
+fn projects_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../..")
+}
+
+// Every .asm file below the given project directories.
+fn asm_files(directories: &[&str]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending: Vec<PathBuf> = directories.iter().map(|d| projects_dir().join(d)).collect();
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "asm") {
+                files.push(path);
+            }
+        }
+    }
+    assert!(
+        !files.is_empty(),
+        "no .asm files found in {:?}",
+        directories
+    );
+    files
+}
+
+fn assemble_file(path: &Path) -> Vec<String> {
+    let source = fs::read_to_string(path).unwrap();
+    assemble(&source, &mut SymbolTable::new())
+        .unwrap_or_else(|e| panic!("{} failed to assemble: {}", path.display(), e))
+}
+
+#[test]
+fn disassembling_reference_files_gives_back_the_same_words() {
+    for asm in asm_files(&["04", "06", "07"]) {
+        let machine_code = assemble_file(&asm);
+        let listing: Vec<String> = machine_code
+            .iter()
+            .map(|word| disassemble(u16::from_str_radix(word, 2).unwrap()).unwrap())
+            .collect();
+        let reassembled = assemble(&listing.join("\n"), &mut SymbolTable::new()).unwrap();
+        assert_eq!(reassembled, machine_code, "{}", asm.display());
+    }
+}
+
+#[test]
+fn words_remember_their_source_lines() {
+    let source = ".data TABLE\n.word 7\n// start\n(LOOP)\n@TABLE\nD=M\n\n@LOOP\n0;JMP\n";
+    let mut symbol_table = SymbolTable::new();
+    let assembled = assemble_with_lines(source, &mut symbol_table).unwrap();
+    assert_eq!(assembled.machine_code.len(), assembled.lines.len());
+    // Four prologue words store the data, then the program.
+    assert_eq!(
+        assembled.lines,
+        [None, None, None, None, Some(5), Some(6), Some(8), Some(9)]
+    );
+    assert_eq!(symbol_table.labels(), [("LOOP", 4)]);
+}
@@ -0,0 +1,47 @@
+use assembler::assembler::assemble;
+use assembler::parser::{self, Instruction};
+use assembler::symbol_table::SymbolTable;
+
+// Counts down from R0 to zero. The label comes after code, so its address is not 0.
+const COUNTDOWN: &str = "\
+@R0
+D=M
+(LOOP) // counting
+@R0
+M=M-1
+D=M
+@LOOP
+D;JGT
+";
+
+#[test]
+fn keeps_labels_in_source_order() {
+    let printed: Vec<String> = parser::parse_lines(COUNTDOWN)
+        .iter()
+        .map(Instruction::to_string)
+        .collect();
+    assert_eq!(
+        printed,
+        ["@R0", "D=M", "(LOOP)", "@R0", "M=M-1", "D=M", "@LOOP", "D;JGT"]
+    );
+    assert!(matches!(
+        &parser::parse_numbered_lines(COUNTDOWN)[2],
+        (3, Instruction::Label(label)) if label == "LOOP"
+    ));
+}
+
+#[test]
+fn printed_instructions_assemble_to_the_same_code() {
+    let original = assemble(COUNTDOWN, &mut SymbolTable::new()).unwrap();
+    // Labels take no ROM word.
+    assert_eq!(original.len(), 7);
+    assert_eq!(original[5], format!("{:016b}", 2));
+    let printed: Vec<String> = parser::parse_lines(COUNTDOWN)
+        .iter()
+        .map(Instruction::to_string)
+        .collect();
+    assert_eq!(
+        assemble(&printed.join("\n"), &mut SymbolTable::new()).unwrap(),
+        original
+    );
+}
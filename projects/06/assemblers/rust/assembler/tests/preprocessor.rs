@@ -0,0 +1,153 @@
+use assembler::preprocessor::{parse_define, preprocess, Defines};
+
+// Conditional assembly: which lines survive, and the errors of malformed blocks.
+
+fn defines(definitions: &[&str]) -> Defines {
+    definitions
+        .iter()
+        .map(|definition| parse_define(definition).unwrap())
+        .collect()
+}
+
+// The lines that were kept, without the blank ones left in place of the others.
+fn kept(source: &str, definitions: &[&str]) -> Vec<String> {
+    preprocess(source, &defines(definitions))
+        .unwrap()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn parses_definitions() {
+    assert_eq!(parse_define("DEBUG"), Ok(("DEBUG".to_string(), 1)));
+    assert_eq!(parse_define("LEVEL=3"), Ok(("LEVEL".to_string(), 3)));
+    assert_eq!(parse_define(" SIZE = 0x10 "), Ok(("SIZE".to_string(), 16)));
+    assert_eq!(parse_define("OFFSET=-2"), Ok(("OFFSET".to_string(), -2)));
+    assert_eq!(
+        parse_define("LEVEL=high"),
+        Err("Invalid value in definition 'LEVEL=high'".to_string())
+    );
+    assert_eq!(
+        parse_define("2FAST=1"),
+        Err("Invalid symbol name in definition '2FAST=1'".to_string())
+    );
+}
+
+const NESTED: &str = "\
+.if LEVEL > 1
+  .if FAST
+@fast_high
+  .else
+@slow_high
+  .endif
+.elif LEVEL == 1
+@one
+.else
+  .if FAST
+@fast_low
+  .elif FAST == 0
+@slow_low
+  .endif
+.endif
+";
+
+#[test]
+fn takes_one_branch_of_nested_blocks() {
+    assert_eq!(kept(NESTED, &["LEVEL=2", "FAST=1"]), ["@fast_high"]);
+    assert_eq!(kept(NESTED, &["LEVEL=2", "FAST=0"]), ["@slow_high"]);
+    assert_eq!(kept(NESTED, &["LEVEL=1", "FAST=1"]), ["@one"]);
+    assert_eq!(kept(NESTED, &["LEVEL=0", "FAST=1"]), ["@fast_low"]);
+    assert_eq!(kept(NESTED, &["LEVEL=0", "FAST=0"]), ["@slow_low"]);
+    // Line numbers stay the same.
+    let output = preprocess(NESTED, &defines(&["LEVEL=1", "FAST=1"])).unwrap();
+    assert_eq!(output.lines().count(), NESTED.lines().count());
+    assert_eq!(output.lines().nth(7), Some("@one"));
+}
+
+#[test]
+fn tests_whether_symbols_are_defined() {
+    let source = ".ifdef DEBUG\n@debug\n.endif\n.ifndef DEBUG\n@release\n.endif\n";
+    assert_eq!(kept(source, &["DEBUG=0"]), ["@debug"]);
+    assert_eq!(kept(source, &[]), ["@release"]);
+    let source = ".if defined(DEBUG) && !defined(QUIET)\n@verbose\n.endif\n";
+    assert_eq!(kept(source, &["DEBUG"]), ["@verbose"]);
+    assert!(kept(source, &["DEBUG", "QUIET"]).is_empty());
+}
+
+#[test]
+fn skips_the_right_side_of_a_false_and() {
+    let error = |source: &str| preprocess(source, &defines(&[])).unwrap_err();
+    // The guard keeps an undefined LEVEL from being an error.
+    let source = ".if defined(LEVEL) && LEVEL > 1\n@high\n.endif\n";
+    assert!(kept(source, &[]).is_empty());
+    assert_eq!(kept(source, &["LEVEL=2"]), ["@high"]);
+    assert!(kept(source, &["LEVEL=1"]).is_empty());
+    // Skipped or not, it has to be a valid condition.
+    assert_eq!(
+        error(".if 0 && (LEVEL\n.endif\n"),
+        "Line 1: unexpected end of condition"
+    );
+}
+
+#[test]
+fn skips_the_right_side_of_a_true_or() {
+    let error = |source: &str| preprocess(source, &defines(&[])).unwrap_err();
+    let source = ".if !defined(LEVEL) || LEVEL == 0\n@off\n.endif\n";
+    assert_eq!(kept(source, &[]), ["@off"]);
+    assert_eq!(kept(source, &["LEVEL=0"]), ["@off"]);
+    assert!(kept(source, &["LEVEL=1"]).is_empty());
+    // Only a side that does not matter is skipped.
+    assert_eq!(
+        error(".if defined(LEVEL) || LEVEL > 1\n.endif\n"),
+        "Line 1: 'LEVEL' is not defined, test it with defined(LEVEL)"
+    );
+}
+
+#[test]
+fn follows_operator_precedence() {
+    let condition = |expression: &str, definitions: &[&str]| {
+        let source = format!(".if {}\n@yes\n.else\n@no\n.endif\n", expression);
+        kept(&source, definitions)[0] == "@yes"
+    };
+    let abc = ["A=1", "B=0", "C=0"];
+    // && binds tighter than ||, comparisons tighter than both, ! tighter than all.
+    assert!(condition("A || B && C", &abc));
+    assert!(!condition("(A || B) && C", &abc));
+    assert!(condition("B == 0 && A", &abc));
+    assert!(condition("!B == 1", &abc));
+    assert!(!condition("!(B == 0)", &abc));
+    assert!(condition("0x10 >= 16 && -1 < 0", &[]));
+}
+
+#[test]
+fn reports_malformed_blocks() {
+    let error = |source: &str| preprocess(source, &defines(&["A=1"])).unwrap_err();
+    assert_eq!(
+        error("@0\n.if A\n@1\n"),
+        "Line 2: .if without matching .endif"
+    );
+    assert_eq!(
+        error(".if A\n.else\n.elif A\n.endif\n"),
+        "Line 3: .elif after .else"
+    );
+    assert_eq!(error("@0\n.endif\n"), "Line 2: .endif without .if");
+    assert_eq!(
+        error(".if A\n.else\n.else\n.endif\n"),
+        "Line 3: duplicate .else"
+    );
+    assert_eq!(
+        error(".if A && MISSING\n.endif\n"),
+        "Line 1: 'MISSING' is not defined, test it with defined(MISSING)"
+    );
+    assert_eq!(
+        error(".if 1 < 2 == 1\n.endif\n"),
+        "Line 1: unexpected '==' in condition"
+    );
+    // Switched off blocks are not evaluated.
+    assert_eq!(
+        kept(".if 0\n.if MISSING\n@x\n.endif\n.endif\n@y\n", &[]),
+        ["@y"]
+    );
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use assembler::assembler::assemble;
+use assembler::stream::assemble_stream;
+use assembler::symbol_table::SymbolTable;
+
+// Every .asm file below the given project directories.
+fn asm_files(directories: &[&str]) -> Vec<PathBuf> {
+    let projects = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../..");
+    let mut files = Vec::new();
+    let mut pending: Vec<PathBuf> = directories.iter().map(|d| projects.join(d)).collect();
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "asm") {
+                files.push(path);
+            }
+        }
+    }
+    assert!(
+        !files.is_empty(),
+        "no .asm files found in {:?}",
+        directories
+    );
+    files
+}
+
+#[test]
+fn streaming_assembler_matches_two_pass_assembler() {
+    for asm in asm_files(&["04", "06", "07"]) {
+        let source = fs::read_to_string(&asm).unwrap();
+        let mut output = Cursor::new(Vec::new());
+        let written = assemble_stream(source.as_bytes(), &mut output, &mut SymbolTable::new())
+            .unwrap_or_else(|e| panic!("{} failed to assemble: {}", asm.display(), e));
+        let streamed: Vec<String> = String::from_utf8(output.into_inner())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let expected = assemble(&source, &mut SymbolTable::new()).unwrap();
+        assert_eq!(written as usize, streamed.len());
+        assert_eq!(streamed, expected, "{}", asm.display());
+    }
+}
+
+// A generated program of almost two megabytes, the size of a translated OS and game once
+// the translator's comments are in, with forward jumps and variables mixed throughout.
+fn generated_program() -> String {
+    let mut source = String::new();
+    for block in 0..4000 {
+        source.push_str(&format!("// block {} {}\n", block, "-".repeat(400)));
+        source.push_str(&format!("@var{}\nD=M\n", block % 500));
+        source.push_str(&format!("@BLOCK{}\nD;JGT\n", (block + 1) % 4000));
+        source.push_str(&format!("@counter\nM=M+1\n(BLOCK{})\n", block));
+        source.push_str(&format!("@{}\nD=A\n", block));
+    }
+    source
+}
+
+#[test]
+fn streams_a_large_program_like_the_two_pass_assembler() {
+    let source = generated_program();
+    assert!(source.len() > 1_600_000);
+
+    let mut output = Cursor::new(Vec::new());
+    let mut symbol_table = SymbolTable::new();
+    let written = assemble_stream(source.as_bytes(), &mut output, &mut symbol_table).unwrap();
+    let output = output.into_inner();
+    let streamed: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+    let mut expected_table = SymbolTable::new();
+    let expected = assemble(&source, &mut expected_table).unwrap();
+    assert_eq!(written, 32_000);
+    assert_eq!(streamed, expected);
+    // Variables get their addresses in order of first use, after every label is known.
+    for symbol in ["var0", "counter", "var1", "var499", "BLOCK0", "BLOCK3999"] {
+        assert_eq!(
+            symbol_table.get_address(symbol),
+            expected_table.get_address(symbol),
+            "{}",
+            symbol
+        );
+    }
+    assert_eq!(symbol_table.get_address("var499"), Some(516));
+}
+
+#[test]
+fn labels_may_not_reuse_symbols() {
+    let stream = |source: &str| {
+        assemble_stream(
+            source.as_bytes(),
+            Cursor::new(Vec::new()),
+            &mut SymbolTable::new(),
+        )
+        .err()
+        .unwrap()
+    };
+    let two_pass = |source: &str| assemble(source, &mut SymbolTable::new()).err().unwrap();
+    for (source, error) in [
+        (
+            "(R0)\n@R0\n0;JMP\n",
+            "Line 1: Symbol 'R0' is already defined",
+        ),
+        (
+            "(LOOP)\n@LOOP\n(LOOP)\n0;JMP\n",
+            "Line 3: Symbol 'LOOP' is already defined",
+        ),
+    ] {
+        assert_eq!(stream(source), error);
+        assert_eq!(two_pass(source), error);
+    }
+}
@@ -0,0 +1,46 @@
+#!/bin/sh
+# Benchmarks the emulator cores on the OS based Jack programs of projects/11.
+#
+#   ./bench.sh [--cycles N]
+#
+# Each program is compiled together with the OS from tools/OS by the course's
+# JackCompiler (needs java), translated by projects/07/vm-translator and run by
+# examples/bench.rs. Programs that come out larger than the 32K ROM can't run, the
+# others are still measured but the script then fails, so a benchmark that left
+# programs out is never taken for a complete one. projects/06/pong/Pong.asm, the
+# same kind of program built by a more compact translator, is always included.
+set -e
+
+here=$(cd "$(dirname "$0")" && pwd)
+root=$(cd "$here/../../../../.." && pwd)
+work=$(mktemp -d)
+trap 'rm -rf "$work"' EXIT
+
+cargo build --quiet --release --manifest-path "$root/projects/07/vm-translator/Cargo.toml"
+cargo build --quiet --release --manifest-path "$here/Cargo.toml" --example bench
+translator="$root/projects/07/vm-translator/target/release/vm-translator"
+
+programs="$root/projects/06/pong/Pong.asm"
+too_large=""
+for source in "$root"/projects/11/*/; do
+    name=$(basename "$source")
+    mkdir "$work/$name"
+    cp "$source"/*.jack "$root"/tools/OS/*.vm "$work/$name"
+    sh "$root/tools/JackCompiler.sh" "$work/$name" > /dev/null
+    "$translator" "$work/$name" > /dev/null
+    # Instructions are the lines that are neither labels, comments nor blank.
+    size=$(grep -v -e '^[[:space:]]*(' -e '^[[:space:]]*//' -e '^[[:space:]]*$' "$work/$name/$name.asm" | wc -l)
+    if [ "$size" -gt 32768 ]; then
+        echo "$name: $size instructions do not fit in the 32K ROM" >&2
+        too_large="$too_large $name"
+    else
+        programs="$programs $work/$name/$name.asm"
+    fi
+done
+
+# shellcheck disable=SC2086
+"$here/target/release/examples/bench" "$@" $programs
+if [ -n "$too_large" ]; then
+    echo "Not measured, too large for the ROM:$too_large" >&2
+    exit 1
+fi
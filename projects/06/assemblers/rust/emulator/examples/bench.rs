@@ -0,0 +1,83 @@
+use std::env;
+use std::time::Instant;
+
+use emulator::cpu::Cpu;
+use emulator::decoded::Decoded;
+use emulator::loader;
+use emulator::run;
+use emulator::summary;
+
+// Measures how many instructions per second each core executes on the given programs:
+//
+//   cargo run --release --example bench -- [--cycles N] program.asm...
+//
+// bench.sh builds the OS based programs of projects/11 and runs this on them.
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut cycles: u64 = 200_000_000;
+    let mut programs = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--cycles" => {
+                cycles = rest
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or_else(|| usage())
+            }
+            _ => programs.push(arg),
+        }
+    }
+    if programs.is_empty() {
+        usage();
+    }
+
+    println!(
+        "{:<24} {:>12} {:>9} {:>10} {:>10}",
+        "program", "cycles", "stop", "step MIPS", "MIPS"
+    );
+    for path in programs {
+        let program = loader::load_file(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        let fresh = || {
+            let mut cpu = Cpu::new();
+            cpu.load_rom(&program.words).unwrap();
+            cpu
+        };
+
+        let mut cpu = fresh();
+        let start = Instant::now();
+        let stop = run::run(&mut cpu, cycles, &mut []).unwrap();
+        let step = mips(cpu.cycles, start);
+        let executed = cpu.cycles;
+
+        let mut cpu = fresh();
+        let start = Instant::now();
+        // Decoding is part of the run, like in the emulator.
+        Decoded::new(&cpu.rom).run(&mut cpu, cycles);
+        assert_eq!(cpu.cycles, executed, "{}: the cores disagree", path);
+        let decoded = mips(cpu.cycles, start);
+
+        let name = path.rsplit('/').next().unwrap_or(path);
+        println!(
+            "{:<24} {:>12} {:>9} {:>10.0} {:>10.0}",
+            name,
+            executed,
+            summary::status(stop, false),
+            step,
+            decoded
+        );
+    }
+}
+
+fn mips(cycles: u64, start: Instant) -> f64 {
+    cycles as f64 / start.elapsed().as_secs_f64() / 1e6
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: bench [--cycles N] <program.hack|program.asm>...");
+    std::process::exit(1);
+}
@@ -0,0 +1,192 @@
+use crate::cpu::Cpu;
+use crate::loader::Program;
+use crate::stack::{ARG, LCL, SP, STACK_BASE};
+use crate::vm::{self, Functions};
+
+// Reconstructs the VM call stack from RAM, innermost call first. Every call leaves a
+// frame below its callee's locals, see code_writer.rs in the VM translator:
+//
+//   ARG ->  arguments
+//           return address   (LCL - 5), the ROM address after the call, a RET_ label
+//           saved LCL        (LCL - 4)
+//           saved ARG        (LCL - 3)
+//           saved THIS       (LCL - 2)
+//           saved THAT       (LCL - 1)
+//   LCL ->  locals
+//           working stack, up to SP or the arguments of the next call
+//
+// so following the saved LCL and ARG from frame to frame leads back to Sys.init:
+//
+//   #0  Prog.Main.fibonacci  ROM[150]  Main.vm:14  lt
+//       args (ARG 270): 2
+//       locals (LCL 276): none
+//       stack: 2 2
+//   #1  Prog.Main.fibonacci  ROM[203] (RET_Prog.Main.fibonacci.9)  Main.vm:21  call Main.fibonacci 1
+//       ...
+//
+// The walk stops at the first frame that does not look like one, so code in the middle
+// of a call or return shows fewer frames. The number of locals comes from the function
+// command in the translator's markers, without them locals show up as part of the stack.
+
+// Stack words shown per frame, the ones nearest the top.
+const STACK_WORDS: usize = 16;
+
+// A function's frame, where it runs and what its part of the stack holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    // None for code outside of any VM function, like the bootstrap.
+    pub function: Option<String>,
+    // The instruction about to run, for callers the address the call returns to.
+    pub pc: u16,
+    pub return_label: Option<String>,
+    pub arg: u16,
+    pub lcl: u16,
+    pub args: Vec<u16>,
+    // None when the number of locals is not known.
+    pub locals: Option<Vec<u16>>,
+    pub stack: Vec<u16>,
+}
+
+pub fn backtrace(cpu: &Cpu, program: &Program) -> Vec<Frame> {
+    let functions = Functions::new(&program.symbols);
+    let returns = vm::return_addresses(&program.symbols);
+    let ram = |address: u16| cpu.ram[address as usize];
+    let mut frames = Vec::new();
+    let (mut pc, mut lcl, mut arg, mut top) = (cpu.pc, ram(LCL), ram(ARG), ram(SP));
+    let mut return_label = None;
+    loop {
+        let function = functions.containing(pc).map(str::to_string);
+        let locals = function
+            .as_deref()
+            .and_then(|function| local_count(program, function));
+        let words = |start: u16, end: u16| -> Vec<u16> {
+            let end = end.max(start) as usize;
+            cpu.ram[start as usize..end.min(cpu.ram.len())].to_vec()
+        };
+        let args = if lcl >= arg.saturating_add(5) {
+            words(arg, lcl - 5)
+        } else {
+            Vec::new()
+        };
+        let stack_start = match locals {
+            Some(count) => lcl.saturating_add(count as u16).min(top.max(lcl)),
+            None => lcl,
+        };
+        frames.push(Frame {
+            function: function.clone(),
+            pc,
+            return_label: return_label.take(),
+            arg,
+            lcl,
+            args,
+            locals: locals.map(|_| words(lcl, stack_start)),
+            stack: words(stack_start, top),
+        });
+
+        // The caller's frame, if this one was called.
+        if function.is_none() || lcl < STACK_BASE + 5 {
+            break;
+        }
+        let return_address = ram(lcl - 5);
+        let saved_lcl = ram(lcl - 4);
+        // The bootstrap's call of Sys.init saves the LCL from before there was a stack.
+        if !returns.contains(&return_address)
+            || functions.containing(return_address).is_none()
+            || !(STACK_BASE + 5..lcl).contains(&saved_lcl)
+        {
+            break;
+        }
+        return_label = program
+            .symbols
+            .labels()
+            .into_iter()
+            .find(|(label, address)| *address == return_address && vm::is_return_label(label))
+            .map(|(label, _)| label.to_string());
+        pc = return_address;
+        top = arg;
+        arg = ram(lcl - 3);
+        lcl = saved_lcl;
+    }
+    frames
+}
+
+// One line per frame plus its arguments, locals and stack.
+pub fn format(frames: &[Frame], program: &Program) -> String {
+    if Functions::new(&program.symbols).is_empty() {
+        return "No VM functions in this program\n".to_string();
+    }
+    let values = |words: &[u16]| match words.len() {
+        0 => "none".to_string(),
+        _ => words
+            .iter()
+            .map(|word| (*word as i16).to_string())
+            .collect::<Vec<String>>()
+            .join(" "),
+    };
+    let mut text = String::new();
+    for (index, frame) in frames.iter().enumerate() {
+        let mut line = format!(
+            "#{:<2} {}  ROM[{}]",
+            index,
+            frame.function.as_deref().unwrap_or("(no function)"),
+            frame.pc
+        );
+        if let Some(label) = &frame.return_label {
+            line.push_str(&format!(" ({})", label));
+        }
+        // Callers are at their call, the return address already belongs to what follows.
+        let code = if index == 0 {
+            frame.pc
+        } else {
+            frame.pc.saturating_sub(1)
+        };
+        if let Some(location) = program.source_map.vm_location(code) {
+            line.push_str(&format!(
+                "  {}:{}  {}",
+                location.file, location.line, location.command
+            ));
+        }
+        text.push_str(&format!("{}\n", line));
+        if frame.function.is_none() {
+            continue;
+        }
+        text.push_str(&format!(
+            "    args (ARG {}): {}\n",
+            frame.arg,
+            values(&frame.args)
+        ));
+        if let Some(locals) = &frame.locals {
+            text.push_str(&format!(
+                "    locals (LCL {}): {}\n",
+                frame.lcl,
+                values(locals)
+            ));
+        }
+        let hidden = frame.stack.len().saturating_sub(STACK_WORDS);
+        match hidden {
+            0 => text.push_str(&format!("    stack: {}\n", values(&frame.stack))),
+            _ => text.push_str(&format!(
+                "    stack: ({} more) {}\n",
+                hidden,
+                values(&frame.stack[hidden..])
+            )),
+        }
+    }
+    text
+}
+
+// The locals of a function, from the `function NAME N` marker at its label. Functions
+// without locals generate no code of their own, the marker there is of their first command.
+fn local_count(program: &Program, function: &str) -> Option<usize> {
+    let start = program
+        .symbols
+        .labels()
+        .into_iter()
+        .find(|(label, _)| *label == function)?
+        .1;
+    let location = program.source_map.vm_location(start)?;
+    match location.command.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["function", _, count] => count.parse().ok(),
+        _ => Some(0),
+    }
+}
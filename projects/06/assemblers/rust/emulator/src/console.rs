@@ -0,0 +1,62 @@
+use std::io::Write;
+
+use crate::cpu::{Cpu, Step, KBD};
+use crate::halt::Stop;
+use crate::run::Observer;
+use crate::terminal::NEWLINE;
+
+// A debug console that only exists in the emulator: programs print diagnostics by writing
+// to two RAM words past the keyboard, which the Hack computer leaves unused.
+//
+//   @65          writing a character code to the console address
+//   D=A          appends the character to the log,
+//   @24577
+//   M=D
+//   @SP          writing a number to the word after it
+//   D=M          appends the number in decimal.
+//   @24578
+//   M=D
+//
+// Printable ASCII is written as is and the Hack newline (128) or 10 end the line. Other
+// codes show up in angle brackets, like <130>. Without --console the words are plain RAM.
+
+pub const CONSOLE: u16 = KBD + 1;
+
+pub struct Console<W: Write> {
+    // Characters go here, numbers to the next word.
+    pub address: u16,
+    pub output: W,
+}
+
+impl<W: Write> Console<W> {
+    // Appends the value of a write to the log, if the write was to the console.
+    pub fn write(&mut self, address: u16, value: u16) -> Result<(), String> {
+        let error = |e: std::io::Error| format!("Failed to write the console: {}", e);
+        if address == self.address {
+            match value {
+                NEWLINE | 10 => writeln!(self.output),
+                32..=126 => write!(self.output, "{}", value as u8 as char),
+                _ => write!(self.output, "<{}>", value),
+            }
+            .map_err(error)?;
+        } else if Some(address) == self.address.checked_add(1) {
+            write!(self.output, "{}", value as i16).map_err(error)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Observer for Console<W> {
+    fn after_step(&mut self, _cpu: &Cpu, step: &Step) -> Result<(), String> {
+        match step.write {
+            Some(write) => self.write(write.address, write.new),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self, _cpu: &Cpu, _stop: Stop) -> Result<(), String> {
+        self.output
+            .flush()
+            .map_err(|e| format!("Failed to write the console: {}", e))
+    }
+}
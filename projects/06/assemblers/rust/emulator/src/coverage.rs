@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use assembler::code::disassemble;
+
+use crate::cpu::{Cpu, Step, ROM_SIZE};
+use crate::loader::Program;
+use crate::profile::{code_lines, label_at, labels, vm_commands};
+use crate::run::Observer;
+use crate::vm::Functions;
+
+// Records which ROM addresses of a program executed and which way its conditional jumps
+// went, then reports it per VM function, VM command kind and label, and as a listing of
+// the source annotated like gcov does:
+//
+//          12:   31:@EQ_TRUE.2
+//          12:   32:D;JEQ  !! jump never taken
+//       #####:   33:@SP
+//           -:   34:(EQ_TRUE.2)
+//
+// A count is how often the line executed, ##### marks code that never did and - lines
+// without code. Jumps that went only one way are flagged, the other way is untested.
+
+const NEVER: &str = "#####";
+
+pub struct Coverage {
+    words: Vec<u16>,
+    counts: Vec<u64>,
+    // How often every conditional jump was taken and not taken.
+    taken: Vec<u64>,
+    not_taken: Vec<u64>,
+    labels: Vec<(String, u16)>,
+    functions: Functions,
+    vm_commands: Vec<Option<String>>,
+    code: Vec<String>,
+    // The .asm source and the address of each of its lines, empty for .hack programs.
+    source: Vec<String>,
+    line_addresses: Vec<Option<u16>>,
+}
+
+// Executed instructions and conditional jumps that went both ways, out of all of them.
+#[derive(Default, Clone, Copy)]
+struct Tally {
+    executed: usize,
+    instructions: usize,
+    both_ways: usize,
+    branches: usize,
+}
+
+impl Coverage {
+    pub fn new(program: &Program) -> Self {
+        let source = program.source_map.source().to_vec();
+        let mut line_addresses = vec![None; source.len()];
+        for address in 0..program.words.len() as u16 {
+            if let Some((line, _)) = program.source_map.asm_line(address) {
+                line_addresses[line - 1] = Some(address);
+            }
+        }
+        Coverage {
+            words: program.words.clone(),
+            counts: vec![0; ROM_SIZE],
+            taken: vec![0; ROM_SIZE],
+            not_taken: vec![0; ROM_SIZE],
+            labels: labels(program),
+            functions: Functions::new(&program.symbols),
+            vm_commands: vm_commands(program),
+            code: code_lines(program),
+            source,
+            line_addresses,
+        }
+    }
+
+    pub fn executed(&self, address: u16) -> u64 {
+        self.counts[address as usize]
+    }
+
+    // How often the jump at the address was taken and how often not.
+    pub fn jumps(&self, address: u16) -> (u64, u64) {
+        (
+            self.taken[address as usize],
+            self.not_taken[address as usize],
+        )
+    }
+
+    // Totals followed by tables per VM function, VM command and label, then the code that
+    // never executed and the jumps that went only one way.
+    pub fn report(&self) -> String {
+        let mut total = Tally::default();
+        let mut by_function: BTreeMap<(u16, String), Tally> = BTreeMap::new();
+        let mut by_command: BTreeMap<String, Tally> = BTreeMap::new();
+        let mut by_label: BTreeMap<(u16, String), Tally> = BTreeMap::new();
+        for address in 0..self.words.len() as u16 {
+            let count = |tally: &mut Tally| self.tally(tally, address);
+            count(&mut total);
+            if !self.functions.is_empty() {
+                let function = self.functions.containing(address).unwrap_or("(program)");
+                count(by_function.entry(self.keyed(function)).or_default());
+            }
+            if let Some(Some(command)) = self.vm_commands.get(address as usize) {
+                count(by_command.entry(command.clone()).or_default());
+            }
+            let label = label_at(&self.labels, address);
+            count(by_label.entry(self.keyed(&label)).or_default());
+        }
+
+        let mut report = format!(
+            "Coverage: {} of {} instructions executed ({}), {} of {} conditional jumps went both ways\n",
+            total.executed,
+            total.instructions,
+            percent(total.executed, total.instructions),
+            total.both_ways,
+            total.branches
+        );
+        let by_address = |rows: BTreeMap<(u16, String), Tally>| {
+            rows.into_iter().map(|((_, name), tally)| (name, tally))
+        };
+        report.push_str(&table("VM function", by_address(by_function)));
+        report.push_str(&table("VM command", by_command.into_iter()));
+        report.push_str(&table("label", by_address(by_label)));
+
+        let mut never = String::new();
+        let mut address = 0;
+        while address < self.words.len() {
+            let start = address;
+            while address < self.words.len() && self.counts[address] == 0 {
+                address += 1;
+            }
+            if address > start {
+                never.push_str(&format!(
+                    "  {:<16} {}\n",
+                    format!("ROM[{}..{}]", start, address),
+                    label_at(&self.labels, start as u16)
+                ));
+            }
+            address += 1;
+        }
+        if !never.is_empty() {
+            report.push_str(&format!("\nNever executed\n{}", never));
+        }
+
+        let one_way: Vec<String> = (0..self.words.len() as u16)
+            .filter_map(|address| {
+                let note = self.one_way(address)?;
+                Some(format!(
+                    "  {:<16} {:<24} {:<12} {}\n",
+                    format!("ROM[{}]", address),
+                    label_at(&self.labels, address),
+                    self.code[address as usize],
+                    note
+                ))
+            })
+            .collect();
+        if !one_way.is_empty() {
+            report.push_str(&format!(
+                "\nJumps that went one way only\n{}",
+                one_way.concat()
+            ));
+        }
+        report
+    }
+
+    // The .asm source annotated with counts, or the disassembly for .hack programs.
+    pub fn listing(&self) -> String {
+        let mut listing = String::new();
+        if self.source.is_empty() {
+            for address in 0..self.words.len() as u16 {
+                let text = disassemble(self.words[address as usize]).unwrap_or_default();
+                listing.push_str(&self.annotate(Some(address), address as usize, &text));
+            }
+        } else {
+            for (index, text) in self.source.iter().enumerate() {
+                listing.push_str(&self.annotate(self.line_addresses[index], index + 1, text));
+            }
+        }
+        listing
+    }
+
+    fn annotate(&self, address: Option<u16>, line: usize, text: &str) -> String {
+        let Some(address) = address else {
+            return format!("{:>9}:{:>5}:{}\n", "-", line, text);
+        };
+        let count = match self.counts[address as usize] {
+            0 => NEVER.to_string(),
+            count => count.to_string(),
+        };
+        match self.one_way(address) {
+            Some(note) => format!("{:>9}:{:>5}:{}  !! {}\n", count, line, text, note),
+            None => format!("{:>9}:{:>5}:{}\n", count, line, text),
+        }
+    }
+
+    // For executed conditional jumps that always or never jumped.
+    fn one_way(&self, address: u16) -> Option<&'static str> {
+        if !is_conditional_jump(self.words[address as usize]) {
+            return None;
+        }
+        match self.jumps(address) {
+            (0, 0) => None,
+            (0, _) => Some("jump never taken"),
+            (_, 0) => Some("jump always taken"),
+            _ => None,
+        }
+    }
+
+    fn tally(&self, tally: &mut Tally, address: u16) {
+        let executed = self.counts[address as usize] > 0;
+        tally.instructions += 1;
+        tally.executed += executed as usize;
+        if is_conditional_jump(self.words[address as usize]) {
+            tally.branches += 1;
+            let (taken, not_taken) = self.jumps(address);
+            tally.both_ways += (taken > 0 && not_taken > 0) as usize;
+        }
+    }
+
+    // Orders functions and labels by where their code starts.
+    fn keyed(&self, name: &str) -> (u16, String) {
+        let start = self
+            .labels
+            .iter()
+            .find(|(label, _)| label == name)
+            .map_or(0, |(_, address)| *address);
+        (start, name.to_string())
+    }
+}
+
+// C-instructions that jump on some results of their computation but not all.
+fn is_conditional_jump(word: u16) -> bool {
+    word & 0x8000 != 0 && !matches!(word & 0x7, 0 | 0x7)
+}
+
+fn table(title: &str, rows: impl Iterator<Item = (String, Tally)>) -> String {
+    let rows: Vec<String> = rows
+        .map(|(name, tally)| {
+            format!(
+                "{:>13} {:>6} {:>9}  {}\n",
+                format!("{}/{}", tally.executed, tally.instructions),
+                percent(tally.executed, tally.instructions),
+                format!("{}/{}", tally.both_ways, tally.branches),
+                name
+            )
+        })
+        .collect();
+    if rows.is_empty() {
+        return String::new();
+    }
+    format!(
+        "\nBy {}\n{:>13} {:>6} {:>9}  {}\n{}",
+        title,
+        "instructions",
+        "%",
+        "branches",
+        title,
+        rows.concat()
+    )
+}
+
+fn percent(part: usize, whole: usize) -> String {
+    format!("{:.1}%", part as f64 * 100.0 / whole.max(1) as f64)
+}
+
+impl Observer for Coverage {
+    fn after_step(&mut self, _cpu: &Cpu, step: &Step) -> Result<(), String> {
+        let address = step.pc as usize;
+        self.counts[address] += 1;
+        if is_conditional_jump(step.instruction) {
+            if step.jumped {
+                self.taken[address] += 1;
+            } else {
+                self.not_taken[address] += 1;
+            }
+        }
+        Ok(())
+    }
+}
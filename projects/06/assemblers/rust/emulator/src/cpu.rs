@@ -0,0 +1,191 @@
+// The Hack computer: 32K words of ROM, 32K words of RAM with the screen and keyboard
+// memory maps, and the A, D and PC registers of the CPU.
+
+pub const ROM_SIZE: usize = 32_768;
+pub const RAM_SIZE: usize = 32_768;
+// The screen is 512x256 pixels, 32 words per row, starting at RAM[16384].
+pub const SCREEN: u16 = 16_384;
+pub const SCREEN_WORDS: usize = 8_192;
+pub const KBD: u16 = 24_576;
+
+// What a single instruction did, so tools can observe execution without hooking into the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    // Address and word of the executed instruction.
+    pub pc: u16,
+    pub instruction: u16,
+    // RAM address read through M, if the computation used M.
+    pub read: Option<u16>,
+    pub write: Option<MemoryWrite>,
+    // The instruction had a jump condition and it was met.
+    pub jumped: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub address: u16,
+    pub old: u16,
+    pub new: u16,
+}
+
+pub struct Cpu {
+    pub rom: Vec<u16>,
+    pub ram: Vec<u16>,
+    pub a: u16,
+    pub d: u16,
+    pub pc: u16,
+    pub cycles: u64,
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Cpu {
+            rom: vec![0; ROM_SIZE],
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+        }
+    }
+
+    // Replaces the ROM with the program, the rest of the ROM is zeroed.
+    pub fn load_rom(&mut self, program: &[u16]) -> Result<(), String> {
+        if program.len() > ROM_SIZE {
+            return Err(format!(
+                "Program has {} instructions, the ROM only holds {}",
+                program.len(),
+                ROM_SIZE
+            ));
+        }
+        self.rom.fill(0);
+        self.rom[..program.len()].copy_from_slice(program);
+        Ok(())
+    }
+
+    // Resets the CPU like the reset pin does: PC goes back to 0, memory is kept.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+    }
+
+    pub fn screen(&self) -> &[u16] {
+        &self.ram[SCREEN as usize..SCREEN as usize + SCREEN_WORDS]
+    }
+
+    // Key code of the currently pressed key, 0 when no key is pressed.
+    pub fn set_key(&mut self, key: u16) {
+        self.ram[KBD as usize] = key;
+    }
+
+    // Executes the instruction at PC.
+    pub fn step(&mut self) -> Step {
+        let pc = self.pc;
+        let instruction = self.rom[(pc & 0x7FFF) as usize];
+        self.cycles += 1;
+
+        if instruction & 0x8000 == 0 {
+            // A-instruction: load the 15-bit constant.
+            self.a = instruction;
+            self.pc = pc.wrapping_add(1) & 0x7FFF;
+            return Step {
+                pc,
+                instruction,
+                read: None,
+                write: None,
+                jumped: false,
+            };
+        }
+
+        // C-instruction: 111a cccc ccdd djjj
+        let address = self.a;
+        let uses_m = instruction & 0x1000 != 0;
+        let m = if uses_m {
+            self.ram[(address & 0x7FFF) as usize]
+        } else {
+            0
+        };
+        let out = alu((instruction >> 6) & 0x7F, self.a, self.d, m);
+
+        let mut write = None;
+        if instruction & 0x0008 != 0 {
+            // M is written at the address A held before this instruction.
+            let index = (address & 0x7FFF) as usize;
+            write = Some(MemoryWrite {
+                address: address & 0x7FFF,
+                old: self.ram[index],
+                new: out,
+            });
+            self.ram[index] = out;
+        }
+        if instruction & 0x0010 != 0 {
+            self.d = out;
+        }
+        if instruction & 0x0020 != 0 {
+            self.a = out;
+        }
+
+        let jumped = jump_taken(instruction & 0x7, out);
+        self.pc = if jumped {
+            // The PC loads the A register as it was before this instruction.
+            address & 0x7FFF
+        } else {
+            pc.wrapping_add(1) & 0x7FFF
+        };
+        Step {
+            pc,
+            instruction,
+            read: if uses_m { Some(address & 0x7FFF) } else { None },
+            write,
+            jumped,
+        }
+    }
+
+    // Executes `cycles` instructions.
+    pub fn run(&mut self, cycles: u64) {
+        for _ in 0..cycles {
+            self.step();
+        }
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The Hack ALU driven by the comp field `a zx nx zy ny f no`.
+// The a bit selects M instead of A as the second operand.
+pub fn alu(comp: u16, a: u16, d: u16, m: u16) -> u16 {
+    let mut x = d;
+    let mut y = if comp & 0x40 != 0 { m } else { a };
+    if comp & 0x20 != 0 {
+        x = 0;
+    }
+    if comp & 0x10 != 0 {
+        x = !x;
+    }
+    if comp & 0x08 != 0 {
+        y = 0;
+    }
+    if comp & 0x04 != 0 {
+        y = !y;
+    }
+    let mut out = if comp & 0x02 != 0 {
+        x.wrapping_add(y)
+    } else {
+        x & y
+    };
+    if comp & 0x01 != 0 {
+        out = !out;
+    }
+    out
+}
+
+// The jump bits `j1 j2 j3` select jumping on a negative, zero and positive result.
+pub fn jump_taken(jump: u16, out: u16) -> bool {
+    let value = out as i16;
+    (jump & 0b100 != 0 && value < 0)
+        || (jump & 0b010 != 0 && value == 0)
+        || (jump & 0b001 != 0 && value > 0)
+}
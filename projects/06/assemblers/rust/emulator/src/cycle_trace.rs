@@ -0,0 +1,201 @@
+use std::fs;
+use std::io::Write;
+
+use assembler::code::disassemble;
+
+use crate::cpu::{Cpu, Step};
+use crate::halt::Stop;
+use crate::run::Observer;
+
+// A record of every executed instruction in a compact binary file, and exporters to CSV
+// and to the Value Change Dump format of waveform viewers like GTKWave. Numbers are
+// big-endian, like in snapshots:
+//
+//   "HACKTRCE" version:u32 cycles-before:u64
+//   per instruction: PC:u16 instruction:u16 A:u16 D:u16 wrote:u8 address:u16 value:u16
+//
+// A and D are the registers after the instruction ran. The address and value of the
+// memory write are 0 when `wrote` is 0. The cycle of a record follows from its position,
+// the first instruction runs in cycle cycles-before + 1, and the instruction text from
+// disassembling the word, so neither takes space.
+
+const MAGIC: &[u8; 8] = b"HACKTRCE";
+const VERSION: u32 = 1;
+const HEADER: usize = 8 + 4 + 8;
+const RECORD: usize = 4 * 2 + 1 + 2 * 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub cycle: u64,
+    pub pc: u16,
+    pub instruction: u16,
+    pub a: u16,
+    pub d: u16,
+    // Address and value of the memory write.
+    pub write: Option<(u16, u16)>,
+}
+
+// Writes the binary trace of a run.
+pub struct CycleTrace<W: Write> {
+    pub output: W,
+}
+
+impl<W: Write> CycleTrace<W> {
+    // Starts the trace of a machine that already ran `cycles` instructions.
+    pub fn new(mut output: W, cycles: u64) -> Result<Self, String> {
+        let mut header = Vec::with_capacity(HEADER);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_be_bytes());
+        header.extend_from_slice(&cycles.to_be_bytes());
+        output.write_all(&header).map_err(write_error)?;
+        Ok(CycleTrace { output })
+    }
+}
+
+impl<W: Write> Observer for CycleTrace<W> {
+    fn after_step(&mut self, cpu: &Cpu, step: &Step) -> Result<(), String> {
+        let (wrote, address, value) = match step.write {
+            Some(write) => (1, write.address, write.new),
+            None => (0, 0, 0),
+        };
+        let mut record = [0; RECORD];
+        for (index, word) in [step.pc, step.instruction, cpu.a, cpu.d].iter().enumerate() {
+            record[2 * index..2 * index + 2].copy_from_slice(&word.to_be_bytes());
+        }
+        record[8] = wrote;
+        record[9..11].copy_from_slice(&address.to_be_bytes());
+        record[11..13].copy_from_slice(&value.to_be_bytes());
+        self.output.write_all(&record).map_err(write_error)
+    }
+
+    fn finish(&mut self, _cpu: &Cpu, _stop: Stop) -> Result<(), String> {
+        self.output.flush().map_err(write_error)
+    }
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("Failed to write the cycle trace: {}", e)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<Record>, String> {
+    if bytes.len() < HEADER || &bytes[..8] != MAGIC {
+        return Err("Not a Hack cycle trace".to_string());
+    }
+    let version = u32::from_be_bytes(bytes[8..12].try_into().unwrap());
+    if version != VERSION {
+        return Err(format!("Unsupported cycle trace version {}", version));
+    }
+    let cycles = u64::from_be_bytes(bytes[12..20].try_into().unwrap());
+    let records = &bytes[HEADER..];
+    if !records.len().is_multiple_of(RECORD) {
+        return Err("Cycle trace is truncated".to_string());
+    }
+    Ok(records
+        .chunks(RECORD)
+        .enumerate()
+        .map(|(index, record)| {
+            let u16_at = |offset: usize| u16::from_be_bytes([record[offset], record[offset + 1]]);
+            Record {
+                cycle: cycles + index as u64 + 1,
+                pc: u16_at(0),
+                instruction: u16_at(2),
+                a: u16_at(4),
+                d: u16_at(6),
+                write: (record[8] != 0).then(|| (u16_at(9), u16_at(11))),
+            }
+        })
+        .collect())
+}
+
+pub fn load(path: &str) -> Result<Vec<Record>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    decode(&bytes).map_err(|e| format!("{}: {}", path, e))
+}
+
+// One line per instruction, numbers in decimal and the instruction in binary like the
+// .cmp files of the hardware simulator, plus its assembly.
+pub fn write_csv<W: Write>(records: &[Record], output: &mut W) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Failed to write the CSV: {}", e);
+    writeln!(output, "cycle,pc,instruction,code,A,D,writeM,addressM,outM").map_err(error)?;
+    for record in records {
+        let (write, address, value) = match record.write {
+            Some((address, value)) => ("1", address.to_string(), (value as i16).to_string()),
+            None => ("0", String::new(), String::new()),
+        };
+        writeln!(
+            output,
+            "{},{},{:016b},{},{},{},{},{},{}",
+            record.cycle,
+            record.pc,
+            record.instruction,
+            disassemble(record.instruction).unwrap_or_default(),
+            record.a as i16,
+            record.d as i16,
+            write,
+            address,
+            value
+        )
+        .map_err(error)?;
+    }
+    output.flush().map_err(error)
+}
+
+// The registers as signals of a `hack` module, one time step per cycle, with the names
+// of CPU.hdl where there is one. Only changes are written, as the format wants.
+pub fn write_vcd<W: Write>(records: &[Record], output: &mut W) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Failed to write the VCD: {}", e);
+    // Identifier, width and name of every signal.
+    const SIGNALS: [(char, u8, &str); 7] = [
+        ('!', 16, "pc"),
+        ('"', 16, "instruction"),
+        ('#', 16, "A"),
+        ('$', 16, "D"),
+        ('%', 1, "writeM"),
+        ('&', 16, "addressM"),
+        ('\'', 16, "outM"),
+    ];
+    writeln!(output, "$timescale 1 ns $end").map_err(error)?;
+    writeln!(output, "$scope module hack $end").map_err(error)?;
+    for (id, width, name) in SIGNALS {
+        writeln!(output, "$var wire {} {} {} $end", width, id, name).map_err(error)?;
+    }
+    writeln!(output, "$upscope $end\n$enddefinitions $end").map_err(error)?;
+
+    let mut last: Option<[u16; 7]> = None;
+    for record in records {
+        // Between writes addressM and outM keep their last values.
+        let (address, value) = match (record.write, last) {
+            (Some(write), _) => write,
+            (None, Some(last)) => (last[5], last[6]),
+            (None, None) => (0, 0),
+        };
+        let values = [
+            record.pc,
+            record.instruction,
+            record.a,
+            record.d,
+            record.write.is_some() as u16,
+            address,
+            value,
+        ];
+        let changes: Vec<String> = SIGNALS
+            .iter()
+            .zip(values)
+            .enumerate()
+            .filter(|(index, _)| last.is_none_or(|last| last[*index] != values[*index]))
+            .map(|(_, ((id, width, _), value))| match width {
+                1 => format!("{}{}", value, id),
+                _ => format!("b{:b} {}", value, id),
+            })
+            .collect();
+        if !changes.is_empty() {
+            writeln!(output, "#{}\n{}", record.cycle, changes.join("\n")).map_err(error)?;
+        }
+        last = Some(values);
+    }
+    // The end of the last cycle, so viewers show it as long as the others.
+    if let Some(record) = records.last() {
+        writeln!(output, "#{}", record.cycle + 1).map_err(error)?;
+    }
+    output.flush().map_err(error)
+}
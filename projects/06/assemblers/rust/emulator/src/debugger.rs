@@ -0,0 +1,653 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+
+use assembler::code::disassemble;
+
+use crate::backtrace;
+use crate::cpu::{Cpu, ROM_SIZE};
+use crate::halt::HaltDetector;
+use crate::history::History;
+use crate::loader::Program;
+use crate::run::Observer;
+use crate::stack::StackChecker;
+use crate::vm;
+use crate::watch::{AccessTrace, Hit, Watchpoint};
+
+// An interactive debugger for Hack programs, with breakpoints on ROM addresses or labels,
+// stepping by instruction or over VM commands, and access to registers and RAM by symbol.
+//
+//   break LOOP        stop before the instruction at the label (or ROM address) runs
+//   next              step over the current VM command, or instruction, running calls to completion
+//   print sum 4       show RAM[sum] and the three words after it
+//   watch change:THAT stop when a write changes THAT
+//   reverse-continue  run backwards to the instruction that made the last such change
+
+const HELP: &str = "\
+break|b TARGET        set a breakpoint at a ROM address, label or VM line like Main.vm:12
+delete|d [TARGET]     delete a breakpoint, or all of them
+breakpoints|info      list the breakpoints
+step|s [N]            execute N instructions (default 1)
+next|n                step over the current VM command (or instruction), running calls to completion
+continue|c [N]        run until a breakpoint, the program halts or N instructions ran
+back|bs [N]           undo the last N instructions (default 1)
+reverse-continue|rc   run backwards until a breakpoint, a watchpoint or the oldest recorded instruction
+history [N]           show how many instructions can be undone, or keep the last N
+print|p WHAT [COUNT]  show A, D, PC, a RAM address or symbol, ROM[n]; COUNT words from WHAT
+set WHAT VALUE        set A, D, PC, or a RAM address or symbol (forgets the history)
+where|w               show the current instruction
+backtrace|bt          show the VM call stack with the arguments and locals of every call
+list|l [TARGET]       show the source around PC or TARGET
+watch [SPEC]          stop on [read:|write:|change:]ADDRESS[..END], or list the watchpoints
+unwatch [N]           delete watchpoint N, or all of them
+check [on|off]        stop when the program breaks the VM's stack conventions
+trace FILE|off        log every memory access to FILE
+reset                 jump back to ROM[0], keeping RAM (forgets the history)
+quit|q                leave the debugger
+An empty line repeats the previous command.";
+
+// Continuing stops after this many instructions unless told otherwise, so a program
+// waiting for a key cannot hang the debugger.
+const CONTINUE_LIMIT: u64 = 100_000_000;
+
+// Instructions kept for running backwards, about 24 MB.
+pub const HISTORY_BUDGET: usize = 1_000_000;
+
+// Why execution stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    Done,
+    Breakpoint,
+    Halted,
+    Limit,
+    Watchpoint(Hit),
+    // The stack checker found a problem.
+    Check,
+    // Running backwards reached the oldest recorded instruction.
+    HistoryStart,
+}
+
+pub enum Reply {
+    Text(String),
+    Quit,
+}
+
+pub struct Debugger {
+    pub cpu: Cpu,
+    program: Program,
+    breakpoints: BTreeSet<u16>,
+    // Return addresses of VM calls, for stepping over them.
+    returns: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    trace: Option<AccessTrace<BufWriter<File>>>,
+    checker: Option<StackChecker>,
+    pub history: History,
+}
+
+impl Debugger {
+    pub fn new(cpu: Cpu, program: Program) -> Self {
+        Debugger {
+            cpu,
+            returns: vm::return_addresses(&program.symbols),
+            program,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            trace: None,
+            checker: None,
+            history: History::new(HISTORY_BUDGET),
+        }
+    }
+
+    pub fn set_stack_checks(&mut self, on: bool) {
+        self.checker = on.then(|| StackChecker::new(&self.program));
+    }
+
+    // The checker follows calls as they happen, it cannot follow jumps in time.
+    fn forget_calls(&mut self) {
+        if let Some(checker) = self.checker.as_mut() {
+            checker.reset();
+        }
+    }
+
+    // Reads commands until `quit` or the end of the input.
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> std::io::Result<()> {
+        writeln!(output, "{}", self.describe(self.cpu.pc))?;
+        let mut previous = String::new();
+        let mut lines = input.lines();
+        loop {
+            write!(output, "(hack) ")?;
+            output.flush()?;
+            let Some(line) = lines.next() else {
+                writeln!(output)?;
+                return Ok(());
+            };
+            let line = line?;
+            let line = if line.trim().is_empty() {
+                previous.clone()
+            } else {
+                line
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.execute(&line) {
+                Ok(Reply::Text(text)) => writeln!(output, "{}", text)?,
+                Ok(Reply::Quit) => return Ok(()),
+                Err(e) => writeln!(output, "{}", e)?,
+            }
+            previous = line;
+        }
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<Reply, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, arguments)) = words.split_first() else {
+            return Ok(Reply::Text(String::new()));
+        };
+        let text = match (command, arguments) {
+            ("break" | "b", [target]) => {
+                let address = self.rom_address(target)?;
+                self.breakpoints.insert(address);
+                format!("Breakpoint at {}", self.name(address))
+            }
+            ("delete" | "d", []) => {
+                self.breakpoints.clear();
+                "Deleted all breakpoints".to_string()
+            }
+            ("delete" | "d", [target]) => {
+                let address = self.rom_address(target)?;
+                if !self.breakpoints.remove(&address) {
+                    return Err(format!("No breakpoint at {}", self.name(address)));
+                }
+                format!("Deleted the breakpoint at {}", self.name(address))
+            }
+            ("breakpoints" | "info", []) => {
+                if self.breakpoints.is_empty() {
+                    "No breakpoints".to_string()
+                } else {
+                    let lines: Vec<String> = self
+                        .breakpoints
+                        .iter()
+                        .map(|address| self.name(*address))
+                        .collect();
+                    lines.join("\n")
+                }
+            }
+            ("step" | "s", []) => {
+                let stop = self.step(1);
+                self.report(stop)
+            }
+            ("step" | "s", [count]) => {
+                let stop = self.step(parse_count(count)?);
+                self.report(stop)
+            }
+            ("next" | "n", []) => {
+                let stop = self.next();
+                self.report(stop)
+            }
+            ("continue" | "c", []) => {
+                let stop = self.run_until(CONTINUE_LIMIT, |_| false);
+                self.report(stop)
+            }
+            ("continue" | "c", [count]) => {
+                let stop = self.run_until(parse_count(count)?, |_| false);
+                self.report(stop)
+            }
+            ("back" | "bs", []) => {
+                let stop = self.back(1);
+                self.report(stop)
+            }
+            ("back" | "bs", [count]) => {
+                let stop = self.back(parse_count(count)?);
+                self.report(stop)
+            }
+            ("reverse-continue" | "rc", []) => {
+                let stop = self.reverse_continue();
+                self.report(stop)
+            }
+            ("history", []) => format!(
+                "{} instructions can be undone, keeping the last {}",
+                self.history.len(),
+                self.history.budget()
+            ),
+            ("history", [budget]) => {
+                let budget = budget
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid count '{}'", budget))?;
+                self.history.set_budget(budget);
+                format!("Keeping the last {} instructions", budget)
+            }
+            ("print" | "p", [what]) => self.print(what, 1)?,
+            ("print" | "p", [what, count]) => self.print(what, parse_count(count)? as usize)?,
+            ("set", [what, value]) => {
+                // Changes made by hand are not in the log, undoing across them would
+                // make up states the program never was in.
+                self.history.clear();
+                self.forget_calls();
+                self.set(what, value)?
+            }
+            ("where" | "w", []) => self.describe(self.cpu.pc),
+            ("backtrace" | "bt", []) => self.backtrace(),
+            ("list" | "l", []) => self.list(self.cpu.pc),
+            ("list" | "l", [target]) => {
+                let address = self.rom_address(target)?;
+                self.list(address)
+            }
+            ("watch", []) => {
+                if self.watchpoints.is_empty() {
+                    "No watchpoints".to_string()
+                } else {
+                    let lines: Vec<String> = self
+                        .watchpoints
+                        .iter()
+                        .enumerate()
+                        .map(|(index, watchpoint)| format!("{}: {}", index + 1, watchpoint))
+                        .collect();
+                    lines.join("\n")
+                }
+            }
+            ("watch", [spec]) => {
+                let watchpoint = Watchpoint::parse(spec, &self.program)?;
+                self.watchpoints.push(watchpoint);
+                format!("Watchpoint {}: {}", self.watchpoints.len(), watchpoint)
+            }
+            ("unwatch", []) => {
+                self.watchpoints.clear();
+                "Deleted all watchpoints".to_string()
+            }
+            ("unwatch", [number]) => {
+                let index = parse_count(number)? as usize - 1;
+                if index >= self.watchpoints.len() {
+                    return Err(format!("No watchpoint {}", number));
+                }
+                let watchpoint = self.watchpoints.remove(index);
+                format!("Deleted watchpoint {}: {}", number, watchpoint)
+            }
+            ("trace", ["off"]) => {
+                if let Some(mut trace) = self.trace.take() {
+                    trace.finish(&self.cpu, crate::halt::Stop::Requested)?;
+                }
+                "Tracing off".to_string()
+            }
+            ("trace", [path]) => {
+                let file = File::create(path)
+                    .map_err(|e| format!("Failed to create '{}': {}", path, e))?;
+                self.trace = Some(AccessTrace {
+                    output: BufWriter::new(file),
+                });
+                format!("Tracing memory accesses to {}", path)
+            }
+            ("check", []) => match self.checker {
+                Some(_) => "Stack checks are on".to_string(),
+                None => "Stack checks are off".to_string(),
+            },
+            ("check", ["on"]) => {
+                self.set_stack_checks(true);
+                "Stack checks on, for calls made from now on".to_string()
+            }
+            ("check", ["off"]) => {
+                self.set_stack_checks(false);
+                "Stack checks off".to_string()
+            }
+            ("reset", []) => {
+                self.cpu.reset();
+                self.history.clear();
+                self.forget_calls();
+                self.describe(self.cpu.pc)
+            }
+            ("help" | "h", []) => HELP.to_string(),
+            ("quit" | "q", []) => return Ok(Reply::Quit),
+            _ => return Err(format!("Unknown command '{}', try help", line.trim())),
+        };
+        Ok(Reply::Text(text))
+    }
+
+    fn report(&self, stop: Stop) -> String {
+        let location = self.describe(self.cpu.pc);
+        match stop {
+            Stop::Done => location,
+            Stop::Breakpoint => format!("Breakpoint reached\n{}", location),
+            Stop::Halted => format!(
+                "Program halted after {} cycles\n{}",
+                self.cpu.cycles, location
+            ),
+            Stop::Limit => format!("Stopped after {} cycles\n{}", self.cpu.cycles, location),
+            Stop::Watchpoint(hit) => format!("Watchpoint: {}\n{}", hit, location),
+            Stop::Check => match self.checker.as_ref().and_then(|c| c.violation.as_ref()) {
+                Some(violation) => format!(
+                    "Stack check failed: {}\n{}\n{}",
+                    violation,
+                    location,
+                    self.backtrace()
+                ),
+                None => location,
+            },
+            Stop::HistoryStart => format!(
+                "Reached the oldest recorded instruction, at cycle {}\n{}",
+                self.cpu.cycles, location
+            ),
+        }
+    }
+
+    // Executes instructions until `done` holds, stopping early at breakpoints and when
+    // the program halts.
+    fn run_until(&mut self, limit: u64, done: impl FnMut(&Cpu) -> bool) -> Stop {
+        let stop = self.run_steps(limit, done);
+        if let Some(trace) = self.trace.as_mut() {
+            // So the trace can be read while the debugger waits for the next command.
+            let _ = trace.output.flush();
+        }
+        stop
+    }
+
+    fn run_steps(&mut self, limit: u64, mut done: impl FnMut(&Cpu) -> bool) -> Stop {
+        // A fresh one every time, RAM may have been changed by hand in between.
+        let mut halt = HaltDetector::new();
+        // Continuing past a failed check reports the next one.
+        if let Some(checker) = self.checker.as_mut() {
+            checker.violation = None;
+        }
+        for _ in 0..limit {
+            let (a, d) = (self.cpu.a, self.cpu.d);
+            let step = self.cpu.step();
+            self.history.record(step, a, d);
+            if let Some(trace) = self.trace.as_mut() {
+                if let Err(e) = trace.after_step(&self.cpu, &step) {
+                    // Stop tracing rather than every command failing from now on.
+                    eprintln!("{}", e);
+                    self.trace = None;
+                }
+            }
+            let hit = self
+                .watchpoints
+                .iter()
+                .find_map(|watchpoint| watchpoint.check(&step, &self.cpu));
+            if let Some(hit) = hit {
+                return Stop::Watchpoint(hit);
+            }
+            if let Some(checker) = self.checker.as_mut() {
+                checker.after_step(&self.cpu, &step).ok();
+                if checker.violation.is_some() {
+                    return Stop::Check;
+                }
+            }
+            if halt.is_halted(&self.cpu, &step) {
+                return Stop::Halted;
+            }
+            if done(&self.cpu) {
+                return Stop::Done;
+            }
+            if self.breakpoints.contains(&self.cpu.pc) {
+                return Stop::Breakpoint;
+            }
+        }
+        Stop::Limit
+    }
+
+    fn step(&mut self, count: u64) -> Stop {
+        let mut executed = 0;
+        match self.run_until(count, |_| {
+            executed += 1;
+            executed == count
+        }) {
+            Stop::Limit => Stop::Done,
+            stop => stop,
+        }
+    }
+
+    // Undoes up to `count` instructions.
+    fn back(&mut self, count: u64) -> Stop {
+        self.forget_calls();
+        for _ in 0..count {
+            if self.history.undo(&mut self.cpu).is_none() {
+                return Stop::HistoryStart;
+            }
+        }
+        Stop::Done
+    }
+
+    // Undoes instructions until PC is at a breakpoint or the undone instruction triggers
+    // a watchpoint. Either way PC ends up at the instruction that would run next.
+    fn reverse_continue(&mut self) -> Stop {
+        self.forget_calls();
+        loop {
+            let Some(step) = self.history.undo(&mut self.cpu) else {
+                return Stop::HistoryStart;
+            };
+            // With the instruction undone, RAM holds what it read, like before it ran.
+            let hit = self
+                .watchpoints
+                .iter()
+                .find_map(|watchpoint| watchpoint.check(&step, &self.cpu));
+            if let Some(hit) = hit {
+                return Stop::Watchpoint(hit);
+            }
+            if self.breakpoints.contains(&self.cpu.pc) {
+                return Stop::Breakpoint;
+            }
+        }
+    }
+
+    // Steps over the VM command at PC when the source map knows it, otherwise over one
+    // instruction. Either way a call runs until it returns.
+    fn next(&mut self) -> Stop {
+        let Some(start) = self.program.source_map.vm_location(self.cpu.pc).cloned() else {
+            return self.step_over_instruction();
+        };
+        loop {
+            let stop = self.step_over_instruction();
+            if stop != Stop::Done
+                || self.program.source_map.vm_location(self.cpu.pc) != Some(&start)
+            {
+                return stop;
+            }
+        }
+    }
+
+    fn step_over_instruction(&mut self) -> Stop {
+        let pc = self.cpu.pc;
+        let instruction = self.cpu.rom[pc as usize];
+        let Some(return_address) = vm::call_return_address(instruction, pc, &self.returns) else {
+            return self.step(1);
+        };
+        // The call sequence has already pointed LCL at the new frame. The return pops the
+        // frame, so SP ends up below it, while returns of recursive calls leave SP above it.
+        let frame = self.cpu.ram[1];
+        self.run_until(CONTINUE_LIMIT, |cpu| {
+            cpu.pc == return_address && cpu.ram[0] < frame
+        })
+    }
+
+    fn print(&self, what: &str, count: usize) -> Result<String, String> {
+        let value = |name: &str, value: u16| format!("{} = {}", name, value as i16);
+        let text = match what {
+            "A" => value("A", self.cpu.a),
+            "D" => value("D", self.cpu.d),
+            "PC" => format!("PC = {}", self.cpu.pc),
+            _ => {
+                if let Some(address) = rom_index(what) {
+                    let address = address?;
+                    let lines: Vec<String> = (address
+                        ..(address as usize + count).min(ROM_SIZE) as u16)
+                        .map(|address| self.describe(address))
+                        .collect();
+                    return Ok(lines.join("\n"));
+                }
+                let address = self.program.ram_address(what)?;
+                let lines: Vec<String> = (0..count)
+                    .filter_map(|offset| {
+                        let address = address.checked_add(offset as u16)?;
+                        let word = *self.cpu.ram.get(address as usize)?;
+                        Some(value(&format!("RAM[{}]", address), word))
+                    })
+                    .collect();
+                if self
+                    .program
+                    .symbols
+                    .labels()
+                    .iter()
+                    .any(|(label, _)| *label == what)
+                {
+                    format!("{} is a label, its RAM word is\n{}", what, lines.join("\n"))
+                } else {
+                    lines.join("\n")
+                }
+            }
+        };
+        Ok(text)
+    }
+
+    fn set(&mut self, what: &str, value: &str) -> Result<String, String> {
+        let number = value
+            .parse::<i32>()
+            .ok()
+            .filter(|value| (-32_768..65_536).contains(value))
+            .map(|value| value as u16);
+        match what {
+            "A" | "D" => {
+                let number = number.ok_or_else(|| format!("Invalid value '{}'", value))?;
+                if what == "A" {
+                    self.cpu.a = number;
+                } else {
+                    self.cpu.d = number;
+                }
+                self.print(what, 1)
+            }
+            "PC" => {
+                self.cpu.pc = self.rom_address(value)?;
+                Ok(self.describe(self.cpu.pc))
+            }
+            _ => {
+                let number = number.ok_or_else(|| format!("Invalid value '{}'", value))?;
+                let address = self.program.ram_address(what)?;
+                self.cpu.ram[address as usize] = number;
+                self.print(what, 1)
+            }
+        }
+    }
+
+    // Source lines around the address, or the disassembled ROM for .hack programs.
+    fn list(&self, address: u16) -> String {
+        let source_map = &self.program.source_map;
+        let marker = |current: bool| if current { "=>" } else { "  " };
+        if let Some((line, _)) = source_map.asm_line(address) {
+            let source = source_map.source();
+            let first = line.saturating_sub(6);
+            let last = (line + 5).min(source.len());
+            return (first..last)
+                .map(|index| {
+                    format!(
+                        "{} {:5}  {}",
+                        marker(index + 1 == line),
+                        index + 1,
+                        source[index]
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+        }
+        let first = address.saturating_sub(5);
+        let last = (address as usize + 6).min(ROM_SIZE) as u16;
+        (first..last)
+            .map(|rom| format!("{} {}", marker(rom == address), self.describe(rom)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn backtrace(&self) -> String {
+        let frames = backtrace::backtrace(&self.cpu, &self.program);
+        backtrace::format(&frames, &self.program)
+            .trim_end()
+            .to_string()
+    }
+
+    // One line for the instruction at the address, plus its VM command when known.
+    pub fn describe(&self, address: u16) -> String {
+        let source_map = &self.program.source_map;
+        let word = self.cpu.rom[address as usize];
+        let code = match source_map.asm_line(address) {
+            Some((line, text)) => format!("{}  (line {})", text.trim(), line),
+            None => disassemble(word).unwrap_or_else(|| format!("{:016b}", word)),
+        };
+        let mut text = format!("{}: {}", self.name(address), code);
+        if let Some(vm) = source_map.vm_location(address) {
+            text.push_str(&format!("\n    {}:{}  {}", vm.file, vm.line, vm.command));
+        }
+        text
+    }
+
+    // ROM[n] followed by the nearest label at or before it, e.g. ROM[12] (LOOP+2).
+    fn name(&self, address: u16) -> String {
+        let labels = self.program.symbols.labels();
+        // Of several labels at the same address, the first by name, so END wins over RET_ labels.
+        let nearest = labels
+            .iter()
+            .rev()
+            .find(|(_, label_address)| *label_address <= address)
+            .and_then(|(_, nearest)| {
+                labels
+                    .iter()
+                    .find(|(_, label_address)| label_address == nearest)
+            });
+        match nearest {
+            Some((label, label_address)) if *label_address == address => {
+                format!("ROM[{}] ({})", address, label)
+            }
+            Some((label, label_address)) => {
+                format!("ROM[{}] ({}+{})", address, label, address - label_address)
+            }
+            None => format!("ROM[{}]", address),
+        }
+    }
+
+    // ROM addresses are numbers, ROM[n] or labels.
+    fn rom_address(&self, text: &str) -> Result<u16, String> {
+        if let Some(address) = rom_index(text) {
+            return address;
+        }
+        if let Some((file, line)) = vm_line(text) {
+            return self
+                .program
+                .source_map
+                .address_of_vm_line(file, line)
+                .ok_or_else(|| format!("No code for {}", text));
+        }
+        text.parse::<u16>()
+            .ok()
+            .filter(|address| (*address as usize) < ROM_SIZE)
+            .or_else(|| {
+                self.program
+                    .symbols
+                    .labels()
+                    .iter()
+                    .find(|(label, _)| *label == text)
+                    .map(|(_, address)| *address)
+            })
+            .ok_or_else(|| format!("Unknown ROM address or label '{}'", text))
+    }
+}
+
+// The file and line of a VM source line, like Main.vm:12.
+fn vm_line(text: &str) -> Option<(&str, usize)> {
+    let (file, line) = text.rsplit_once(':')?;
+    Some((file.strip_suffix(".vm").map(|_| file)?, line.parse().ok()?))
+}
+
+// The index of a ROM[n] reference, None when the text is something else.
+fn rom_index(text: &str) -> Option<Result<u16, String>> {
+    let inner = text.strip_prefix("ROM[")?.strip_suffix(']')?;
+    Some(
+        inner
+            .parse::<u16>()
+            .ok()
+            .filter(|address| (*address as usize) < ROM_SIZE)
+            .ok_or_else(|| format!("Invalid ROM address '{}'", text)),
+    )
+}
+
+fn parse_count(text: &str) -> Result<u64, String> {
+    text.parse::<u64>()
+        .ok()
+        .filter(|count| *count > 0)
+        .ok_or_else(|| format!("Invalid count '{}'", text))
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+use crate::cpu::{Cpu, KBD};
+use crate::halt::HaltDetector;
+
+// Runs two programs that should behave the same, like the output of the VM translator
+// with and without an optimization, and finds the first point where they do not.
+//
+// The programs may take different instructions and cycles to get there, so they are not
+// compared cycle by cycle but by what they do to the observed RAM: the changes to it, in
+// order, have to be the same, and so does whether the programs halt. Writes that store
+// the value already there are no change. By default the observed RAM is the statics,
+// the heap and the screen, everything but the registers, temporaries and the stack,
+// whose use is the code generator's business.
+
+pub const OBSERVED: [(u16, u16); 2] = [(16, 256), (2048, KBD)];
+
+// A write that changed an observed RAM word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub cycle: u64,
+    pub pc: u16,
+    pub address: u16,
+    pub old: u16,
+    pub new: u16,
+}
+
+// What a program did next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Change(Change),
+    // The program halted after this many cycles.
+    Halted(u64),
+    // The program was still running at the cycle limit.
+    CycleLimit(u64),
+}
+
+impl Event {
+    fn matches(&self, other: &Event) -> bool {
+        match (self, other) {
+            (Event::Change(a), Event::Change(b)) => a.address == b.address && a.new == b.new,
+            (Event::Halted(_), Event::Halted(_)) => true,
+            (Event::CycleLimit(_), Event::CycleLimit(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Change(change) => write!(
+                f,
+                "cycle {}: ROM[{}] changed RAM[{}] from {} to {}",
+                change.cycle, change.pc, change.address, change.old as i16, change.new as i16
+            ),
+            Event::Halted(cycles) => write!(f, "halted after {} cycles", cycles),
+            Event::CycleLimit(cycles) => write!(f, "still running after {} cycles", cycles),
+        }
+    }
+}
+
+// The result of running both programs: how many changes they agreed on and where each
+// of them stopped or went its own way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comparison {
+    pub changes: usize,
+    pub first: Event,
+    pub second: Event,
+}
+
+impl Comparison {
+    pub fn diverged(&self) -> bool {
+        !self.first.matches(&self.second)
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.first, self.second) {
+            _ if self.diverged() => write!(
+                f,
+                "Diverged after {} matching changes\n  first:  {}\n  second: {}",
+                self.changes, self.first, self.second
+            ),
+            (Event::Halted(first), Event::Halted(second)) => write!(
+                f,
+                "Same: {} matching changes, both halted, after {} and {} cycles",
+                self.changes, first, second
+            ),
+            (first, second) => write!(
+                f,
+                "Same for {} changes, then {} and {}",
+                self.changes, first, second
+            ),
+        }
+    }
+}
+
+// Runs a program up to its next change of the observed RAM.
+struct Runner<'a> {
+    cpu: &'a mut Cpu,
+    halt: HaltDetector,
+    max_cycles: u64,
+}
+
+impl Runner<'_> {
+    fn next(&mut self, observed: &[(u16, u16)]) -> Event {
+        while self.cpu.cycles < self.max_cycles {
+            let step = self.cpu.step();
+            // Also for changes, which the detector has to see to forget the loops before.
+            let halted = self.halt.is_halted(self.cpu, &step);
+            if let Some(write) = step.write {
+                let watched = observed
+                    .iter()
+                    .any(|(start, end)| (*start..*end).contains(&write.address));
+                if watched && write.old != write.new {
+                    return Event::Change(Change {
+                        cycle: self.cpu.cycles,
+                        pc: step.pc,
+                        address: write.address,
+                        old: write.old,
+                        new: write.new,
+                    });
+                }
+            }
+            if halted {
+                return Event::Halted(self.cpu.cycles);
+            }
+        }
+        Event::CycleLimit(self.cpu.cycles)
+    }
+}
+
+// Runs both machines, each for up to `max_cycles` in total, until they stop or differ
+// in a change of the `observed` RAM ranges, which leave out their end.
+pub fn compare(
+    first: &mut Cpu,
+    second: &mut Cpu,
+    observed: &[(u16, u16)],
+    max_cycles: u64,
+) -> Comparison {
+    let mut first = Runner {
+        cpu: first,
+        halt: HaltDetector::new(),
+        max_cycles,
+    };
+    let mut second = Runner {
+        cpu: second,
+        halt: HaltDetector::new(),
+        max_cycles,
+    };
+    let mut changes = 0;
+    loop {
+        let (a, b) = (first.next(observed), second.next(observed));
+        if !a.matches(&b) || !matches!(a, Event::Change(_)) {
+            return Comparison {
+                changes,
+                first: a,
+                second: b,
+            };
+        }
+        changes += 1;
+    }
+}
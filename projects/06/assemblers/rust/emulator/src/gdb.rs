@@ -0,0 +1,532 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::cpu::{Cpu, Step, RAM_SIZE};
+use crate::halt::HaltDetector;
+use crate::watch::{Access, Watchpoint};
+
+// A stub for the GDB remote serial protocol, so GDB and the front ends built on it can
+// debug Hack programs over TCP or standard input and output.
+//
+// GDB sees a single byte addressed memory with 16-bit little-endian words:
+//
+//   0x00000..0x10000   ROM, word n at byte 2n
+//   0x10000..0x20000   RAM, word n at byte 0x10000 + 2n
+//
+// The registers are A, D and PC, the PC holding the byte address of its ROM word like
+// the addresses of breakpoints. The stub supports reading and writing registers and
+// memory, software and hardware breakpoints, write, read and access watchpoints on RAM,
+// single steps, continuing and interrupting with Ctrl-C.
+
+pub const RAM_BASE: u32 = 0x10000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.nand2tetris.hack.cpu">
+    <reg name="A" bitsize="16" type="uint16" regnum="0"/>
+    <reg name="D" bitsize="16" type="int16" regnum="1"/>
+    <reg name="PC" bitsize="16" type="code_ptr" regnum="2"/>
+  </feature>
+</target>
+"#;
+
+const MEMORY_MAP: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE memory-map PUBLIC "+//IDN gnu.org//DTD GDB Memory Map V1.0//EN" "http://sourceware.org/gdb/gdb-memory-map.dtd">
+<memory-map>
+  <memory type="rom" start="0x0" length="0x10000"/>
+  <memory type="ram" start="0x10000" length="0x10000"/>
+</memory-map>
+"#;
+
+// Instructions run between checks for Ctrl-C while continuing.
+const CHUNK: u64 = 65_536;
+
+// GDB signal numbers for the stop replies.
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+enum Incoming {
+    Packet(String),
+    Interrupt,
+}
+
+// The Z packet types of watchpoints, kept with each watchpoint to name the stop reason.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WatchKind {
+    Write = 2,
+    Read = 3,
+    Access = 4,
+}
+
+struct Stub<W: Write> {
+    cpu: Cpu,
+    output: W,
+    bytes: Receiver<Vec<u8>>,
+    pending: VecDeque<u8>,
+    acks: bool,
+    // The last packet sent, for when GDB asks for it again.
+    sent: String,
+    last_stop: String,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<(WatchKind, Watchpoint)>,
+    // GDB detached or killed the program.
+    done: bool,
+}
+
+// Serves one GDB session on the machine until GDB detaches, kills the program or the
+// connection closes, and returns the machine as it was left.
+pub fn serve<R, W>(cpu: Cpu, mut input: R, output: W) -> Result<Cpu, String>
+where
+    R: Read + Send + 'static,
+    W: Write,
+{
+    // Reading happens on its own thread, so a running program can check for Ctrl-C.
+    let (sender, bytes) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(count) = input.read(&mut buffer) {
+            if count == 0 || sender.send(buffer[..count].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    let mut stub = Stub {
+        cpu,
+        output,
+        bytes,
+        pending: VecDeque::new(),
+        acks: true,
+        sent: String::new(),
+        last_stop: format!("S{:02x}", SIGTRAP),
+        breakpoints: BTreeSet::new(),
+        watchpoints: Vec::new(),
+        done: false,
+    };
+    while let Some(incoming) = stub.receive()? {
+        let Incoming::Packet(packet) = incoming else {
+            // Ctrl-C while the program is stopped anyway.
+            continue;
+        };
+        if let Some(reply) = stub.handle(&packet)? {
+            stub.send(&reply)?;
+        }
+        if packet == "QStartNoAckMode" {
+            // The packet itself was still acknowledged, nothing after it is.
+            stub.acks = false;
+        }
+        if stub.done {
+            break;
+        }
+    }
+    Ok(stub.cpu)
+}
+
+impl<W: Write> Stub<W> {
+    // The reply to a packet, None for packets GDB expects no reply to.
+    fn handle(&mut self, packet: &str) -> Result<Option<String>, String> {
+        let reply = match packet.as_bytes().first() {
+            None => String::new(),
+            Some(b'?') => self.last_stop.clone(),
+            Some(b'g') => self.registers(),
+            Some(b'G') => reply(self.set_registers(&packet[1..])),
+            Some(b'p') => match parse_hex(&packet[1..]).and_then(|number| self.register(number)) {
+                Some(value) => hex_word(value),
+                None => "E01".to_string(),
+            },
+            Some(b'P') => reply(self.set_register(&packet[1..])),
+            Some(b'm') => self.read_memory(&packet[1..]).unwrap_or("E01".to_string()),
+            Some(b'M') => reply(self.write_memory(&packet[1..])),
+            Some(b'Z') => reply(self.insert(&packet[1..])),
+            Some(b'z') => reply(self.remove(&packet[1..])),
+            Some(b's') => self.step(),
+            Some(b'c') => self.resume()?,
+            Some(b'H') => "OK".to_string(),
+            Some(b'k') => {
+                self.done = true;
+                return Ok(None);
+            }
+            Some(b'D') => {
+                self.done = true;
+                "OK".to_string()
+            }
+            _ => self.query(packet)?,
+        };
+        Ok(Some(reply))
+    }
+
+    // Queries and the v packets, an empty reply for anything unsupported.
+    fn query(&mut self, packet: &str) -> Result<String, String> {
+        let reply = if packet.starts_with("qSupported") {
+            "PacketSize=4000;qXfer:features:read+;qXfer:memory-map:read+;QStartNoAckMode+"
+                .to_string()
+        } else if packet == "QStartNoAckMode" {
+            "OK".to_string()
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            transfer(TARGET_XML, range)
+        } else if let Some(range) = packet.strip_prefix("qXfer:memory-map:read::") {
+            transfer(MEMORY_MAP, range)
+        } else if packet == "qAttached" {
+            "1".to_string()
+        } else if packet == "qC" {
+            "QC1".to_string()
+        } else if packet == "qfThreadInfo" {
+            "m1".to_string()
+        } else if packet == "qsThreadInfo" {
+            "l".to_string()
+        } else if packet == "vCont?" {
+            "vCont;c;C;s;S".to_string()
+        } else if let Some(actions) = packet.strip_prefix("vCont;") {
+            // There is only one thread, so its first action is the one that counts.
+            match actions.as_bytes().first() {
+                Some(b's' | b'S') => self.step(),
+                Some(b'c' | b'C') => self.resume()?,
+                _ => "E01".to_string(),
+            }
+        } else {
+            String::new()
+        };
+        Ok(reply)
+    }
+
+    fn registers(&self) -> String {
+        [self.cpu.a, self.cpu.d, self.cpu.pc * 2]
+            .iter()
+            .map(|value| hex_word(*value))
+            .collect()
+    }
+
+    fn register(&self, number: u32) -> Option<u16> {
+        match number {
+            0 => Some(self.cpu.a),
+            1 => Some(self.cpu.d),
+            2 => Some(self.cpu.pc * 2),
+            _ => None,
+        }
+    }
+
+    fn set_registers(&mut self, data: &str) -> Option<()> {
+        let bytes = decode_hex(data)?;
+        if bytes.len() != 6 {
+            return None;
+        }
+        for number in 0..3 {
+            let value = u16::from_le_bytes([bytes[2 * number], bytes[2 * number + 1]]);
+            self.store_register(number as u32, value)?;
+        }
+        Some(())
+    }
+
+    fn set_register(&mut self, assignment: &str) -> Option<()> {
+        let (number, value) = assignment.split_once('=')?;
+        let bytes = decode_hex(value)?;
+        if bytes.len() != 2 {
+            return None;
+        }
+        self.store_register(parse_hex(number)?, u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn store_register(&mut self, number: u32, value: u16) -> Option<()> {
+        match number {
+            0 => self.cpu.a = value,
+            1 => self.cpu.d = value,
+            2 => self.cpu.pc = (value / 2) & 0x7FFF,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    // The word holding the byte address, as a ROM or RAM slot.
+    fn word(&mut self, address: u32) -> Option<&mut u16> {
+        match address.checked_sub(RAM_BASE) {
+            None => self.cpu.rom.get_mut(address as usize / 2),
+            Some(offset) => self.cpu.ram.get_mut(offset as usize / 2),
+        }
+    }
+
+    fn read_memory(&mut self, range: &str) -> Option<String> {
+        let (address, length) = range.split_once(',')?;
+        let (address, length) = (parse_hex(address)?, parse_hex(length)?);
+        let mut hex = String::new();
+        for byte_address in address..address.checked_add(length)? {
+            let word = *self.word(byte_address)?;
+            hex.push_str(&format!(
+                "{:02x}",
+                word.to_le_bytes()[byte_address as usize % 2]
+            ));
+        }
+        Some(hex)
+    }
+
+    fn write_memory(&mut self, packet: &str) -> Option<()> {
+        let (range, data) = packet.split_once(':')?;
+        let (address, length) = range.split_once(',')?;
+        let (address, length) = (parse_hex(address)?, parse_hex(length)?);
+        let bytes = decode_hex(data)?;
+        if bytes.len() != length as usize {
+            return None;
+        }
+        for (offset, byte) in bytes.into_iter().enumerate() {
+            let byte_address = address.checked_add(offset as u32)?;
+            let word = self.word(byte_address)?;
+            let mut le = word.to_le_bytes();
+            le[byte_address as usize % 2] = byte;
+            *word = u16::from_le_bytes(le);
+        }
+        Some(())
+    }
+
+    // Z packets: type, address and kind or length.
+    fn insert(&mut self, packet: &str) -> Option<()> {
+        match parse_z(packet)? {
+            (0 | 1, address, _) => {
+                self.breakpoints.insert(rom_word(address)?);
+            }
+            (kind, address, length) => {
+                for watchpoint in watchpoints(kind, address, length)? {
+                    self.watchpoints.push(watchpoint);
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn remove(&mut self, packet: &str) -> Option<()> {
+        match parse_z(packet)? {
+            (0 | 1, address, _) => {
+                self.breakpoints.remove(&rom_word(address)?);
+            }
+            (kind, address, length) => {
+                for watchpoint in watchpoints(kind, address, length)? {
+                    self.watchpoints.retain(|other| *other != watchpoint);
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn step(&mut self) -> String {
+        let step = self.cpu.step();
+        let stop = self
+            .watch_stop(&step)
+            .unwrap_or_else(|| format!("T{:02x}", SIGTRAP));
+        self.last_stop = stop.clone();
+        stop
+    }
+
+    // Runs until a breakpoint, a watchpoint, the program halting or Ctrl-C.
+    fn resume(&mut self) -> Result<String, String> {
+        let mut halt = HaltDetector::new();
+        let stop = 'running: loop {
+            for _ in 0..CHUNK {
+                let step = self.cpu.step();
+                if let Some(stop) = self.watch_stop(&step) {
+                    break 'running stop;
+                }
+                if halt.is_halted(&self.cpu, &step) {
+                    // Told as console output, GDB has no stop reason for it.
+                    self.send(&format!("O{}", encode_hex(b"Program halted\n")))?;
+                    break 'running format!("T{:02x}", SIGTRAP);
+                }
+                if self.breakpoints.contains(&self.cpu.pc) {
+                    break 'running format!("T{:02x}swbreak:;", SIGTRAP);
+                }
+            }
+            if self.interrupted()? {
+                break format!("T{:02x}", SIGINT);
+            }
+        };
+        self.last_stop = stop.clone();
+        Ok(stop)
+    }
+
+    // The stop reply for a watchpoint the step triggered.
+    fn watch_stop(&self, step: &Step) -> Option<String> {
+        self.watchpoints.iter().find_map(|(kind, watchpoint)| {
+            let hit = watchpoint.check(step, &self.cpu)?;
+            let reason = match kind {
+                WatchKind::Write => "watch",
+                WatchKind::Read => "rwatch",
+                WatchKind::Access => "awatch",
+            };
+            Some(format!(
+                "T{:02x}{}:{:x};",
+                SIGTRAP,
+                reason,
+                RAM_BASE + 2 * hit.address as u32
+            ))
+        })
+    }
+
+    // Checks for Ctrl-C without waiting, keeping anything else for later.
+    fn interrupted(&mut self) -> Result<bool, String> {
+        let closed = loop {
+            match self.bytes.try_recv() {
+                Ok(bytes) => self.pending.extend(bytes),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        match self.pending.iter().position(|byte| *byte == 0x03) {
+            Some(index) => {
+                self.pending.remove(index);
+                Ok(true)
+            }
+            None if closed => Err("GDB closed the connection".to_string()),
+            None => Ok(false),
+        }
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        while self.pending.is_empty() {
+            self.pending.extend(self.bytes.recv().ok()?);
+        }
+        self.pending.pop_front()
+    }
+
+    // The next packet or Ctrl-C, None when the connection closed.
+    fn receive(&mut self) -> Result<Option<Incoming>, String> {
+        loop {
+            let Some(byte) = self.next_byte() else {
+                return Ok(None);
+            };
+            match byte {
+                0x03 => return Ok(Some(Incoming::Interrupt)),
+                b'-' => {
+                    let sent = self.sent.clone();
+                    self.write_packet(&sent)?;
+                }
+                b'$' => {
+                    let mut data = Vec::new();
+                    loop {
+                        match self.next_byte() {
+                            Some(b'#') => break,
+                            Some(byte) => data.push(byte),
+                            None => return Ok(None),
+                        }
+                    }
+                    let checksum = [self.next_byte(), self.next_byte()];
+                    let [Some(high), Some(low)] = checksum else {
+                        return Ok(None);
+                    };
+                    let expected = std::str::from_utf8(&[high, low])
+                        .ok()
+                        .and_then(|text| u8::from_str_radix(text, 16).ok());
+                    if self.acks {
+                        let valid = expected == Some(checksum_of(&data));
+                        self.write_raw(if valid { b"+" } else { b"-" })?;
+                        if !valid {
+                            continue;
+                        }
+                    }
+                    return Ok(Some(Incoming::Packet(
+                        String::from_utf8_lossy(&data).into_owned(),
+                    )));
+                }
+                // Acknowledgments and anything between packets.
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), String> {
+        self.sent = data.to_string();
+        self.write_packet(data)
+    }
+
+    fn write_packet(&mut self, data: &str) -> Result<(), String> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.write_raw(packet.as_bytes())
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.output
+            .write_all(bytes)
+            .and_then(|_| self.output.flush())
+            .map_err(|e| format!("Failed to write to GDB: {}", e))
+    }
+}
+
+fn reply(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => "E01".to_string(),
+    }
+}
+
+// The part of an XML document asked for by a qXfer read, `OFFSET,LENGTH` in hex.
+fn transfer(document: &str, range: &str) -> String {
+    let Some((offset, length)) = range
+        .split_once(',')
+        .and_then(|(offset, length)| Some((parse_hex(offset)?, parse_hex(length)?)))
+    else {
+        return "E01".to_string();
+    };
+    let start = (offset as usize).min(document.len());
+    let end = (start + length as usize).min(document.len());
+    let marker = if end == document.len() { 'l' } else { 'm' };
+    format!("{}{}", marker, &document[start..end])
+}
+
+fn parse_z(packet: &str) -> Option<(u32, u32, u32)> {
+    let mut fields = packet.split(',');
+    let kind = parse_hex(fields.next()?)?;
+    let address = parse_hex(fields.next()?)?;
+    let length = parse_hex(fields.next()?.split(';').next()?)?;
+    Some((kind, address, length))
+}
+
+fn rom_word(address: u32) -> Option<u16> {
+    (address < RAM_BASE && address.is_multiple_of(2)).then_some((address / 2) as u16)
+}
+
+// The watchpoints of a Z2, Z3 or Z4 packet over the RAM words the byte range touches.
+fn watchpoints(kind: u32, address: u32, length: u32) -> Option<Vec<(WatchKind, Watchpoint)>> {
+    let start = address.checked_sub(RAM_BASE)? / 2;
+    let end = (address.checked_add(length)? - RAM_BASE).div_ceil(2);
+    if length == 0 || end > RAM_SIZE as u32 {
+        return None;
+    }
+    let watchpoint = |access| Watchpoint {
+        access,
+        start: start as u16,
+        end: end as u16,
+    };
+    Some(match kind {
+        2 => vec![(WatchKind::Write, watchpoint(Access::Write))],
+        3 => vec![(WatchKind::Read, watchpoint(Access::Read))],
+        4 => vec![
+            (WatchKind::Access, watchpoint(Access::Read)),
+            (WatchKind::Access, watchpoint(Access::Write)),
+        ],
+        _ => return None,
+    })
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn hex_word(value: u16) -> String {
+    encode_hex(&value.to_le_bytes())
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
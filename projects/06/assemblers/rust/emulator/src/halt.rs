@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+
+use crate::cpu::{Cpu, Step, KBD};
+
+// Tells when a program can no longer do anything: besides the `(END) @END 0;JMP` idiom,
+// any loop that comes back to the same PC, A and D without changing RAM or reading the
+// keyboard will repeat forever. The states are sampled at backward jumps, which every
+// loop has, and forgotten whenever RAM changes or the keyboard is read.
+//
+// Loops that count in a register until it wraps around are not caught, they end at
+// the cycle limit like any other long loop.
+
+// Sampled states kept before starting over, so a long idle loop with a changing
+// register does not grow the set without bound.
+const REMEMBERED: usize = 1_024;
+
+// Why a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    // The program reached its final infinite loop, or another loop it cannot leave.
+    Halted,
+    // The cycle limit was reached first.
+    CycleLimit,
+    // An observer asked to stop.
+    Requested,
+}
+
+#[derive(Default)]
+pub struct HaltDetector {
+    seen: HashSet<(u16, u16, u16)>,
+}
+
+impl HaltDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Called after every step, true once the program is stuck in a loop.
+    pub fn is_halted(&mut self, cpu: &Cpu, step: &Step) -> bool {
+        if is_end_loop(cpu, step) {
+            return true;
+        }
+        if step.write.is_some_and(|write| write.old != write.new) || step.read == Some(KBD) {
+            self.forget();
+            return false;
+        }
+        step.jumped && cpu.pc <= step.pc && self.revisits(cpu.pc, cpu.a, cpu.d)
+    }
+
+    // RAM changed or the keyboard was read, the states seen so far may lead elsewhere now.
+    pub fn forget(&mut self) {
+        if !self.seen.is_empty() {
+            self.seen.clear();
+        }
+    }
+
+    // Called at a backward jump with the state after it, true when the state came up
+    // before without RAM changing since.
+    pub fn revisits(&mut self, pc: u16, a: u16, d: u16) -> bool {
+        if self.seen.len() == REMEMBERED {
+            self.seen.clear();
+        }
+        !self.seen.insert((pc, a, d))
+    }
+}
+
+// True when the step jumped back to the `@END` of the `(END) @END 0;JMP` idiom
+// that ends Hack programs, so the program will never do anything else.
+fn is_end_loop(cpu: &Cpu, step: &Step) -> bool {
+    step.jumped
+        && step.instruction & 0x7 == 0x7
+        && cpu.pc.wrapping_add(1) == step.pc
+        && cpu.rom[cpu.pc as usize] == cpu.pc
+}
@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+
+use crate::cpu::{Cpu, Step};
+
+// An undo log of executed instructions, so a debugger can run backwards. Every entry
+// holds the step with the A and D registers from before it; the step itself knows the
+// old PC and the old value of the RAM word it wrote. That is enough to restore the
+// machine as it was before the instruction ran.
+//
+// The log keeps the most recent `budget` instructions and forgets older ones, so memory
+// stays bounded (an entry takes about 24 bytes) however long the program runs.
+
+struct Entry {
+    step: Step,
+    a: u16,
+    d: u16,
+}
+
+pub struct History {
+    entries: VecDeque<Entry>,
+    budget: usize,
+}
+
+impl History {
+    pub fn new(budget: usize) -> Self {
+        History {
+            entries: VecDeque::new(),
+            budget,
+        }
+    }
+
+    // Logs a step, `a` and `d` being the registers before it ran.
+    pub fn record(&mut self, step: Step, a: u16, d: u16) {
+        if self.budget == 0 {
+            return;
+        }
+        if self.entries.len() == self.budget {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry { step, a, d });
+    }
+
+    // Restores the machine to before the most recent step and returns that step, or None
+    // when the log is empty.
+    pub fn undo(&mut self, cpu: &mut Cpu) -> Option<Step> {
+        let entry = self.entries.pop_back()?;
+        if let Some(write) = entry.step.write {
+            cpu.ram[write.address as usize] = write.old;
+        }
+        cpu.a = entry.a;
+        cpu.d = entry.d;
+        cpu.pc = entry.step.pc;
+        cpu.cycles -= 1;
+        Some(entry.step)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    // Changes the budget, forgetting the oldest steps that no longer fit.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        let excess = self.entries.len().saturating_sub(budget);
+        self.entries.drain(..excess);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+
+use crate::replay::{KeyEvent, Replay};
+use crate::terminal::{
+    BACKSPACE, DELETE, DOWN, END, ESCAPE, F1, HOME, INSERT, LEFT, NEWLINE, PAGE_DOWN, PAGE_UP,
+    RIGHT, UP,
+};
+
+// Keyboard scripts: key presses written down by hand, turned into the key events of a
+// replay. Statements are separated by newlines or semicolons, # starts a comment:
+//
+//   at cycle 10000 press LEFT for 5000 cycles
+//   wait 20000 cycles
+//   type "hello\n"
+//
+// A script keeps a current cycle, starting at 0. `at cycle N` moves it forward to N,
+// `wait N cycles` by N. `press KEY` holds the key down from the current cycle on and
+// releases it after HOLD cycles, or as many as given with `for`. `type` presses every
+// character of the text in turn, releasing each for as long as it was held so programs
+// that wait for the release see every key. Keys are names like LEFT, ENTER, SPACE, F1 or
+// single characters.
+
+// How long keys stay down, long enough for the OS to notice a key between two draws.
+pub const HOLD: u64 = 100_000;
+
+pub fn load_script(path: &str) -> Result<Replay, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    let events = parse_script(&text).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(Replay::new(events))
+}
+
+pub fn parse_script(text: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut events = Vec::new();
+    let mut cycle = 0;
+    for (index, line) in text.lines().enumerate() {
+        let tokens = tokenize(line).map_err(|e| format!("Line {}: {}", index + 1, e))?;
+        for statement in tokens.split(|token| *token == Token::Separator) {
+            run_statement(statement, &mut cycle, &mut events)
+                .map_err(|e| format!("Line {}: {}", index + 1, e))?;
+        }
+    }
+    Ok(events)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Separator,
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => break,
+            ';' => tokens.push(Token::Separator),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some(c @ ('"' | '\\')) => text.push(c),
+                            _ => return Err("Unknown escape in text".to_string()),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err("Unterminated text".to_string()),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ';') {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn run_statement(
+    mut tokens: &[Token],
+    cycle: &mut u64,
+    events: &mut Vec<KeyEvent>,
+) -> Result<(), String> {
+    if let [Token::Word(at), Token::Word(unit), Token::Word(number), rest @ ..] = tokens {
+        if at == "at" && unit == "cycle" {
+            let at = parse_cycles(number)?;
+            if at < *cycle {
+                return Err(format!("Cycle {} is before cycle {}", at, cycle));
+            }
+            *cycle = at;
+            tokens = rest;
+        }
+    }
+    let mut press = |key: u16, hold: u64, cycle: &mut u64| {
+        events.push(KeyEvent { cycle: *cycle, key });
+        *cycle += hold;
+        events.push(KeyEvent {
+            cycle: *cycle,
+            key: 0,
+        });
+    };
+    match tokens {
+        [] => {}
+        [Token::Word(wait), Token::Word(number), unit @ ..] if wait == "wait" => {
+            check_unit(unit)?;
+            *cycle += parse_cycles(number)?;
+        }
+        [Token::Word(command), key, hold @ ..] if command == "press" => {
+            let key = match key {
+                Token::Word(name) => key_code(name)?,
+                Token::Text(text) => single_key(text)?,
+                Token::Separator => unreachable!(),
+            };
+            press(key, parse_hold(hold)?, cycle);
+        }
+        [Token::Word(command), Token::Text(text), hold @ ..] if command == "type" => {
+            let hold = parse_hold(hold)?;
+            for c in text.chars() {
+                press(char_code(c)?, hold, cycle);
+                *cycle += hold;
+            }
+        }
+        _ => return Err(format!("Unknown statement '{}'", describe(tokens))),
+    }
+    Ok(())
+}
+
+// An optional `for N cycles`.
+fn parse_hold(tokens: &[Token]) -> Result<u64, String> {
+    match tokens {
+        [] => Ok(HOLD),
+        [Token::Word(word), Token::Word(number), unit @ ..] if word == "for" => {
+            check_unit(unit)?;
+            match parse_cycles(number)? {
+                0 => Err("Keys must be held for at least one cycle".to_string()),
+                hold => Ok(hold),
+            }
+        }
+        _ => Err(format!(
+            "Expected 'for N cycles', found '{}'",
+            describe(tokens)
+        )),
+    }
+}
+
+fn check_unit(tokens: &[Token]) -> Result<(), String> {
+    match tokens {
+        [] => Ok(()),
+        [Token::Word(unit)] if unit == "cycles" || unit == "cycle" => Ok(()),
+        _ => Err(format!("Unexpected '{}'", describe(tokens))),
+    }
+}
+
+fn parse_cycles(text: &str) -> Result<u64, String> {
+    text.replace('_', "")
+        .parse()
+        .map_err(|_| format!("Invalid number of cycles '{}'", text))
+}
+
+// The Hack key code of a key name or a single character.
+pub fn key_code(name: &str) -> Result<u16, String> {
+    let code = match name.to_ascii_uppercase().as_str() {
+        "NEWLINE" | "ENTER" | "RETURN" => NEWLINE,
+        "BACKSPACE" => BACKSPACE,
+        "LEFT" => LEFT,
+        "UP" => UP,
+        "RIGHT" => RIGHT,
+        "DOWN" => DOWN,
+        "HOME" => HOME,
+        "END" => END,
+        "PAGEUP" | "PAGE_UP" => PAGE_UP,
+        "PAGEDOWN" | "PAGE_DOWN" => PAGE_DOWN,
+        "INSERT" => INSERT,
+        "DELETE" => DELETE,
+        "ESC" | "ESCAPE" => ESCAPE,
+        "SPACE" => b' ' as u16,
+        upper => match upper.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+            Some(n @ 1..=12) => F1 + n - 1,
+            _ => return single_key(name),
+        },
+    };
+    Ok(code)
+}
+
+fn single_key(text: &str) -> Result<u16, String> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => char_code(c),
+        _ => Err(format!("Unknown key '{}'", text)),
+    }
+}
+
+// Printable ASCII characters are their own key codes.
+fn char_code(c: char) -> Result<u16, String> {
+    match c {
+        '\n' => Ok(NEWLINE),
+        ' '..='~' => Ok(c as u16),
+        _ => Err(format!("No Hack key for {:?}", c)),
+    }
+}
+
+fn describe(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Word(word) => word.clone(),
+            Token::Text(text) => format!("{:?}", text),
+            Token::Separator => ";".to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,25 @@
+pub mod backtrace;
+pub mod console;
+pub mod coverage;
+pub mod cpu;
+pub mod cycle_trace;
+pub mod debugger;
+pub mod decoded;
+pub mod diff;
+pub mod gdb;
+pub mod halt;
+pub mod history;
+pub mod keyboard;
+pub mod loader;
+pub mod profile;
+pub mod replay;
+pub mod run;
+pub mod screen;
+pub mod script;
+pub mod snapshot;
+pub mod source_map;
+pub mod stack;
+pub mod summary;
+pub mod terminal;
+pub mod vm;
+pub mod watch;
@@ -0,0 +1,139 @@
+use std::fs;
+use std::path::Path;
+
+use assembler::assembler::assemble_with_lines;
+use assembler::preprocessor::{self, Defines};
+use assembler::symbol_table::SymbolTable;
+
+use crate::cpu::RAM_SIZE;
+use crate::source_map::SourceMap;
+
+// A program ready to be loaded into ROM. Programs assembled from source keep their
+// symbol table so labels and variables can be looked up while the program runs,
+// and a source map from ROM addresses back to their lines.
+pub struct Program {
+    pub words: Vec<u16>,
+    pub symbols: SymbolTable,
+    pub source_map: SourceMap,
+}
+
+impl Program {
+    // RAM addresses are numbers, RAM[n] or symbols of the program such as SP or a variable.
+    pub fn ram_address(&self, text: &str) -> Result<u16, String> {
+        let text = text.trim();
+        let inner = text
+            .strip_prefix("RAM[")
+            .and_then(|rest| rest.strip_suffix(']'))
+            .unwrap_or(text);
+        inner
+            .parse::<u16>()
+            .ok()
+            .or_else(|| self.symbols.get_address(inner))
+            .filter(|address| (*address as usize) < RAM_SIZE)
+            .ok_or_else(|| format!("Unknown address '{}'", text))
+    }
+}
+
+// Loads a .hack file, or assembles a .asm file in-process. A .vm file, or a directory
+// of them, goes through the VM translator first, whose markers map the code back to
+// the VM commands.
+pub fn load_file(path: &str) -> Result<Program, String> {
+    if Path::new(path).is_dir() {
+        return load_vm_directory(path);
+    }
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("hack") => parse_hack(&contents),
+        Some("asm") => assemble_source(&contents, &Defines::new()),
+        Some("vm") => translate_vm(path, vec![(file_name(Path::new(path)), contents)]),
+        _ => Err(format!(
+            "'{}' should be a .hack, .asm or .vm file or a directory",
+            path
+        )),
+    }
+}
+
+// Translates the .vm files of the directory, in the order of their names.
+fn load_vm_directory(path: &str) -> Result<Program, String> {
+    let error = |e: std::io::Error| format!("Failed to read '{}': {}", path, e);
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(path).map_err(error)? {
+        let entry_path = entry.map_err(error)?.path();
+        if entry_path.extension().is_some_and(|ext| ext == "vm") {
+            paths.push(entry_path);
+        }
+    }
+    if paths.is_empty() {
+        return Err(format!("No .vm files in '{}'", path));
+    }
+    paths.sort();
+    let mut files = Vec::new();
+    for file in paths {
+        let contents = fs::read_to_string(&file)
+            .map_err(|e| format!("Failed to read '{}': {}", file.display(), e))?;
+        files.push((file_name(&file), contents));
+    }
+    translate_vm(path, files)
+}
+
+// The program is named after the file or directory, like the translator's output.
+fn translate_vm(path: &str, files: Vec<(String, String)>) -> Result<Program, String> {
+    let path = Path::new(path);
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = absolute
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("Program");
+    let source = vm_translator::translate(name, &files)?;
+    assemble_source(&source, &Defines::new())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
+
+// Reads the textual machine code produced by the assembler, one 16 digit word per line.
+pub fn parse_hack(contents: &str) -> Result<Program, String> {
+    let mut words = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.len() != 16 {
+            return Err(format!(
+                "Line {}: expected 16 binary digits, found '{}'",
+                index + 1,
+                line
+            ));
+        }
+        let word = u16::from_str_radix(line, 2)
+            .map_err(|_| format!("Line {}: '{}' is not a binary word", index + 1, line))?;
+        words.push(word);
+    }
+    Ok(Program {
+        words,
+        symbols: SymbolTable::new(),
+        source_map: SourceMap::default(),
+    })
+}
+
+// Runs the assembler's preprocessor and passes over the source.
+pub fn assemble_source(source: &str, defines: &Defines) -> Result<Program, String> {
+    let mut symbols = SymbolTable::new();
+    // The preprocessor blanks the lines it consumes, so line numbers still match the source.
+    let preprocessed = preprocessor::preprocess(source, defines)?;
+    let assembled = assemble_with_lines(&preprocessed, &mut symbols)?;
+    let words = assembled
+        .machine_code
+        .iter()
+        .map(|line| u16::from_str_radix(line, 2).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<u16>, String>>()?;
+    Ok(Program {
+        words,
+        symbols,
+        source_map: SourceMap::new(source, assembled.lines),
+    })
+}
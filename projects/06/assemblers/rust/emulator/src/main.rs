@@ -0,0 +1,614 @@
+use ::emulator::backtrace;
+use ::emulator::console::{self, Console};
+use ::emulator::coverage::Coverage;
+use ::emulator::cpu::Cpu;
+use ::emulator::cycle_trace::{self, CycleTrace};
+use ::emulator::debugger::{self, Debugger};
+use ::emulator::decoded::Decoded;
+use ::emulator::diff;
+use ::emulator::gdb;
+use ::emulator::keyboard;
+use ::emulator::loader::{self, Program};
+use ::emulator::profile::Profiler;
+use ::emulator::replay::{self, Recorder, Replay};
+use ::emulator::run::{self, Observer};
+use ::emulator::screen::ScreenCapture;
+use ::emulator::script;
+use ::emulator::snapshot;
+use ::emulator::stack::StackChecker;
+use ::emulator::summary;
+use ::emulator::terminal::{self, Style};
+use ::emulator::watch::{AccessTrace, Watcher, Watchpoint};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Entry point of the Hack CPU emulator.
+// Loads a .hack file (or assembles a .asm file, or translates a .vm file or a directory
+// of them), runs it until it halts or reaches the cycle limit and prints the registers
+// and the requested RAM locations, or writes them as JSON with --json.
+// With --terminal it runs the program live in the terminal, keys going to the keyboard register.
+// With --debug it starts an interactive debugger on the program instead, with --gdb it
+// waits for GDB on a local TCP port or on standard input and output.
+// Given .tst test scripts instead, it runs them and compares their output like the course tools.
+// --check-stack stops headless runs and the debugger when the program breaks the VM's
+// memory conventions, see stack.rs. Failed checks and --backtrace print the VM call stack.
+// --cycle-trace records every instruction in a binary file, which --export-trace turns
+// into CSV or a VCD file for waveform viewers, see cycle_trace.rs.
+// With --diff it runs two programs side by side and reports where they stop behaving
+// the same, see diff.rs.
+// --console enables a debug console that programs print to through RAM[24577] and up.
+// --coverage and --coverage-listing report which instructions executed, of the program
+// or of the programs the scripts test.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1].ends_with(".tst") {
+        run_scripts(&args[1..]);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--export-trace" {
+        export_trace(&args[2..]);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--diff" {
+        run_diff(&args[2..]);
+        return;
+    }
+    let mut program_file: Option<&String> = None;
+    let mut cycles: Option<u64> = None;
+    let mut settings: Vec<&String> = Vec::new();
+    let mut dumps: Vec<&String> = Vec::new();
+    let mut screen_path: Option<PathBuf> = None;
+    let mut screen_at: Vec<u64> = Vec::new();
+    let mut screen_every: Option<u64> = None;
+    let mut live = false;
+    let mut debug = false;
+    let mut check_stack = false;
+    let mut show_backtrace = false;
+    let mut history = debugger::HISTORY_BUDGET;
+    let mut gdb_port: Option<&String> = None;
+    let mut watches: Vec<&String> = Vec::new();
+    let mut trace_path: Option<&String> = None;
+    let mut cycle_trace_path: Option<&String> = None;
+    let mut profile_path: Option<&String> = None;
+    let mut folded_path: Option<&String> = None;
+    let mut coverage_path: Option<&String> = None;
+    let mut listing_path: Option<&String> = None;
+    let mut console_path: Option<&String> = None;
+    let mut console_address: Option<&String> = None;
+    let mut replay_path: Option<&String> = None;
+    let mut keys_path: Option<&String> = None;
+    let mut record_path: Option<&String> = None;
+    let mut restore_path: Option<&String> = None;
+    let mut snapshot_path: Option<&String> = None;
+    let mut json_path: Option<&String> = None;
+    let mut style = Style::Braille;
+    let mut key_hold = Duration::from_millis(150);
+    let mut speed: Option<u64> = None;
+
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--cycles" => {
+                cycles = Some(parse_number(rest.next()));
+            }
+            "--set" => settings.push(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--dump" => dumps.push(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--screen" => {
+                screen_path = Some(PathBuf::from(
+                    rest.next().unwrap_or_else(|| print_usage_and_exit()),
+                ))
+            }
+            "--screen-at" => screen_at.push(parse_number(rest.next())),
+            "--screen-every" => screen_every = Some(parse_number(rest.next())),
+            "--terminal" => live = true,
+            "--debug" => debug = true,
+            "--check-stack" => check_stack = true,
+            "--backtrace" => show_backtrace = true,
+            "--gdb" => gdb_port = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--history" => history = parse_number(rest.next()) as usize,
+            "--watch" => watches.push(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--profile" => {
+                profile_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--folded" => folded_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--coverage" => {
+                coverage_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--coverage-listing" => {
+                listing_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--console" => {
+                console_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--console-at" => {
+                console_address = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--replay" => replay_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--keys" => keys_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--record" => record_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--restore" => {
+                restore_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--snapshot" => {
+                snapshot_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--json" => json_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--trace" => trace_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--cycle-trace" => {
+                cycle_trace_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--half-blocks" => style = Style::HalfBlocks,
+            "--key-hold" => key_hold = Duration::from_millis(parse_number(rest.next())),
+            "--speed" => speed = Some(parse_number(rest.next())),
+            _ if program_file.is_none() && !arg.starts_with('-') => program_file = Some(arg),
+            _ => print_usage_and_exit(),
+        }
+    }
+    let program_file = program_file.unwrap_or_else(|| print_usage_and_exit());
+
+    let program = loader::load_file(program_file).unwrap_or_else(|e| exit_with_error(&e));
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let mut replay = match (replay_path, keys_path) {
+        (Some(_), Some(_)) => exit_with_error("Use either --replay or --keys, not both"),
+        (Some(path), None) => Some(Replay::load(path).unwrap_or_else(|e| exit_with_error(&e))),
+        (None, Some(path)) => {
+            Some(keyboard::load_script(path).unwrap_or_else(|e| exit_with_error(&e)))
+        }
+        (None, None) => None,
+    };
+    if let Some(path) = restore_path {
+        // The program still provides the symbols, the snapshot holds the machine.
+        let restored = snapshot::load(path).unwrap_or_else(|e| exit_with_error(&e));
+        cpu = restored.cpu;
+        if let Some(replay) = replay.as_mut() {
+            replay.position = restored.replay_position;
+        }
+    }
+    apply_settings(&mut cpu, &settings, &program);
+
+    if console_address.is_some() && console_path.is_none() {
+        exit_with_error("--console-at needs --console");
+    }
+    if console_path.is_some() && (debug || gdb_port.is_some()) {
+        exit_with_error("--console works in headless and --terminal runs");
+    }
+    if live && console_path.is_some_and(|path| path == "-") {
+        exit_with_error("--console needs a file with --terminal, the screen takes standard output");
+    }
+    if cycle_trace_path.is_some() && (live || debug || gdb_port.is_some()) {
+        exit_with_error("--cycle-trace works in headless runs");
+    }
+    let mut console = console_path.map(|path| Console {
+        address: console_address.map_or(console::CONSOLE, |text| parse_address(text, &program)),
+        output: create_output(path),
+    });
+
+    if let Some(port) = gdb_port {
+        let result = if port == "-" {
+            gdb::serve(cpu, io::stdin(), io::stdout())
+        } else {
+            let port: u16 = port.parse().unwrap_or_else(|_| print_usage_and_exit());
+            let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| {
+                exit_with_error(&format!("Failed to listen on port {}: {}", port, e))
+            });
+            eprintln!("Waiting for GDB on 127.0.0.1:{}", port);
+            let (stream, _) = listener
+                .accept()
+                .unwrap_or_else(|e| exit_with_error(&format!("Failed to accept GDB: {}", e)));
+            let input = stream
+                .try_clone()
+                .unwrap_or_else(|e| exit_with_error(&e.to_string()));
+            gdb::serve(cpu, input, stream)
+        };
+        result.unwrap_or_else(|e| exit_with_error(&e));
+        return;
+    }
+
+    if debug {
+        let stdin = std::io::stdin();
+        let mut debugger = Debugger::new(cpu, program);
+        debugger.history.set_budget(history);
+        debugger.set_stack_checks(check_stack);
+        debugger
+            .repl(stdin.lock(), std::io::stdout())
+            .unwrap_or_else(|e| exit_with_error(&e.to_string()));
+        return;
+    }
+
+    if live {
+        let mut options = terminal::Options {
+            style,
+            key_hold,
+            speed,
+            max_cycles: cycles,
+            replay,
+            recorder: record_path.map(|_| Recorder::default()),
+            snapshot: snapshot_path.cloned(),
+            console,
+        };
+        let result = terminal::run(&mut cpu, &mut options);
+        // The recording is worth keeping even when the run failed.
+        if let (Some(path), Some(recorder)) = (record_path, options.recorder) {
+            write_output(path, &replay::format_events(&recorder.events));
+        }
+        result.unwrap_or_else(|e| exit_with_error(&e));
+        return;
+    }
+
+    let mut capture = match screen_path {
+        Some(path) => Some(ScreenCapture {
+            path,
+            at: screen_at,
+            every: screen_every,
+        }),
+        None if !screen_at.is_empty() || screen_every.is_some() => {
+            exit_with_error("--screen-at and --screen-every need --screen <image path>")
+        }
+        None => None,
+    };
+    let mut watcher = Watcher {
+        watchpoints: watches
+            .iter()
+            .map(|spec| Watchpoint::parse(spec, &program).unwrap_or_else(|e| exit_with_error(&e)))
+            .collect(),
+        hit: None,
+    };
+    let mut trace = trace_path.map(|path| AccessTrace {
+        output: create_output(path),
+    });
+    let mut cycle_trace = cycle_trace_path.map(|path| {
+        CycleTrace::new(create_output(path), cpu.cycles).unwrap_or_else(|e| exit_with_error(&e))
+    });
+    let mut profiler =
+        (profile_path.is_some() || folded_path.is_some()).then(|| Profiler::new(&program));
+    let mut coverage =
+        (coverage_path.is_some() || listing_path.is_some()).then(|| Coverage::new(&program));
+    let mut checker = check_stack.then(|| StackChecker::new(&program));
+    let ranges: Vec<(u16, u16)> = dumps
+        .iter()
+        .map(|dump| parse_range(dump, &program))
+        .collect();
+    if record_path.is_some() {
+        exit_with_error("--record needs --terminal, headless runs have no keyboard");
+    }
+    let watching = !watcher.watchpoints.is_empty();
+    let mut observers: Vec<&mut dyn Observer> = vec![&mut watcher];
+    if let Some(replay) = replay.as_mut() {
+        observers.push(replay);
+    }
+    if let Some(profiler) = profiler.as_mut() {
+        observers.push(profiler);
+    }
+    if let Some(coverage) = coverage.as_mut() {
+        observers.push(coverage);
+    }
+    if let Some(console) = console.as_mut() {
+        observers.push(console);
+    }
+    if let Some(checker) = checker.as_mut() {
+        observers.push(checker);
+    }
+    if let Some(capture) = capture.as_mut() {
+        observers.push(capture);
+    }
+    if let Some(trace) = trace.as_mut() {
+        observers.push(trace);
+    }
+    if let Some(cycle_trace) = cycle_trace.as_mut() {
+        observers.push(cycle_trace);
+    }
+    let max_cycles = cycles.unwrap_or(1_000_000);
+    // Without anything watching the instructions, the decoded core runs them much faster.
+    let stop = if observers.len() == 1 && !watching {
+        Decoded::new(&cpu.rom).run(&mut cpu, max_cycles)
+    } else {
+        run::run(&mut cpu, max_cycles, &mut observers).unwrap_or_else(|e| exit_with_error(&e))
+    };
+
+    // JSON on standard output replaces the usual report.
+    let report = json_path.is_none_or(|path| path != "-");
+    let violation = checker.and_then(|checker| checker.violation);
+    if report {
+        println!(
+            "{}  cycles: {}  PC: {}  A: {}  D: {}",
+            summary::status(stop, violation.is_some()),
+            cpu.cycles,
+            cpu.pc,
+            cpu.a as i16,
+            cpu.d as i16
+        );
+    }
+    if let Some(path) = json_path {
+        write_output(
+            path,
+            &summary::to_json(
+                stop,
+                &cpu,
+                watcher.hit.as_ref(),
+                violation.as_ref(),
+                &ranges,
+            ),
+        );
+    }
+    if let Some(path) = snapshot_path {
+        let position = replay.as_ref().map_or(0, |replay| replay.position);
+        snapshot::save(path, &cpu, position).unwrap_or_else(|e| exit_with_error(&e));
+    }
+    if let Some(profiler) = profiler {
+        if let Some(path) = profile_path {
+            write_output(path, &profiler.report());
+        }
+        if let Some(path) = folded_path {
+            write_output(path, &profiler.folded());
+        }
+    }
+    if let Some(coverage) = coverage {
+        if let Some(path) = coverage_path {
+            write_output(path, &coverage.report());
+        }
+        if let Some(path) = listing_path {
+            write_output(path, &coverage.listing());
+        }
+    }
+    if !report {
+        return;
+    }
+    if let Some(hit) = watcher.hit {
+        println!("{}", hit);
+    }
+    if let Some(violation) = &violation {
+        println!("{}", violation);
+    }
+    if show_backtrace || violation.is_some() {
+        print!(
+            "{}",
+            backtrace::format(&backtrace::backtrace(&cpu, &program), &program)
+        );
+    }
+    for (start, end) in ranges {
+        for address in start..end.max(start) {
+            println!("RAM[{}] = {}", address, cpu.ram[address as usize] as i16);
+        }
+    }
+}
+
+fn run_scripts(args: &[String]) {
+    let mut scripts = Vec::new();
+    let mut coverage_path: Option<&String> = None;
+    let mut listing_path: Option<&String> = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--coverage" => {
+                coverage_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            "--coverage-listing" => {
+                listing_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit()))
+            }
+            _ if arg.ends_with(".tst") => scripts.push(arg),
+            _ => print_usage_and_exit(),
+        }
+    }
+    let cover = coverage_path.is_some() || listing_path.is_some();
+
+    let mut failures = 0;
+    // The coverage of every script's program, under the name of the script.
+    let mut reports = String::new();
+    let mut listings = String::new();
+    for script_file in scripts {
+        let path = Path::new(script_file);
+        let result = if cover {
+            script::cover_script(path)
+        } else {
+            script::run_script(path)
+        };
+        match result {
+            Ok(report) => {
+                match report.comparison {
+                    Some(Ok(())) => println!("{}: Comparison ended successfully", script_file),
+                    Some(Err(e)) => {
+                        println!("{}: {}", script_file, e);
+                        failures += 1;
+                    }
+                    None => println!("{}: End of script", script_file),
+                }
+                if let Some(coverage) = report.coverage {
+                    reports.push_str(&format!("==> {} <==\n{}\n", script_file, coverage.report()));
+                    listings.push_str(&format!(
+                        "==> {} <==\n{}\n",
+                        script_file,
+                        coverage.listing()
+                    ));
+                }
+            }
+            Err(e) => {
+                println!("{}: {}", script_file, e);
+                failures += 1;
+            }
+        }
+    }
+    if let Some(path) = coverage_path {
+        write_output(path, &reports);
+    }
+    if let Some(path) = listing_path {
+        write_output(path, &listings);
+    }
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
+
+// Converts a binary cycle trace to CSV and VCD.
+fn export_trace(args: &[String]) {
+    let mut trace_path: Option<&String> = None;
+    let mut csv_path: Option<&String> = None;
+    let mut vcd_path: Option<&String> = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--csv" => csv_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--vcd" => vcd_path = Some(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            _ if trace_path.is_none() && !arg.starts_with('-') => trace_path = Some(arg),
+            _ => print_usage_and_exit(),
+        }
+    }
+    let trace_path = trace_path.unwrap_or_else(|| print_usage_and_exit());
+    if csv_path.is_none() && vcd_path.is_none() {
+        exit_with_error("--export-trace needs --csv or --vcd");
+    }
+    let records = cycle_trace::load(trace_path).unwrap_or_else(|e| exit_with_error(&e));
+    if let Some(path) = csv_path {
+        cycle_trace::write_csv(&records, &mut create_output(path))
+            .unwrap_or_else(|e| exit_with_error(&e));
+    }
+    if let Some(path) = vcd_path {
+        cycle_trace::write_vcd(&records, &mut create_output(path))
+            .unwrap_or_else(|e| exit_with_error(&e));
+    }
+}
+
+// Runs two programs from the same state and reports where they first differ.
+fn run_diff(args: &[String]) {
+    let mut programs: Vec<&String> = Vec::new();
+    let mut settings: Vec<&String> = Vec::new();
+    let mut observed: Vec<&String> = Vec::new();
+    let mut cycles: Option<u64> = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--cycles" => cycles = Some(parse_number(rest.next())),
+            "--set" => settings.push(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            "--observe" => observed.push(rest.next().unwrap_or_else(|| print_usage_and_exit())),
+            _ if programs.len() < 2 && !arg.starts_with('-') => programs.push(arg),
+            _ => print_usage_and_exit(),
+        }
+    }
+    let [first_file, second_file] = programs[..] else {
+        print_usage_and_exit();
+    };
+    let first = loader::load_file(first_file).unwrap_or_else(|e| exit_with_error(&e));
+    let second = loader::load_file(second_file).unwrap_or_else(|e| exit_with_error(&e));
+    // Symbols in addresses are the first program's, the second may have none.
+    let ranges: Vec<(u16, u16)> = match observed.len() {
+        0 => diff::OBSERVED.to_vec(),
+        _ => observed
+            .iter()
+            .map(|range| parse_range(range, &first))
+            .collect(),
+    };
+    let mut cpus = [Cpu::new(), Cpu::new()];
+    for (cpu, program) in cpus.iter_mut().zip([&first, &second]) {
+        cpu.load_rom(&program.words)
+            .unwrap_or_else(|e| exit_with_error(&e));
+        apply_settings(cpu, &settings, &first);
+    }
+    let [first_cpu, second_cpu] = &mut cpus;
+    let comparison = diff::compare(first_cpu, second_cpu, &ranges, cycles.unwrap_or(1_000_000));
+    println!("{}", comparison);
+    if comparison.diverged() {
+        std::process::exit(1);
+    }
+}
+
+// Sets RAM words from ADDRESS=VALUE settings.
+fn apply_settings(cpu: &mut Cpu, settings: &[&String], program: &Program) {
+    for setting in settings {
+        let (address, value) = setting.split_once('=').unwrap_or_else(|| {
+            exit_with_error(&format!("Expected ADDRESS=VALUE, found '{}'", setting))
+        });
+        let address = parse_address(address, program);
+        let value = value
+            .trim()
+            .parse::<i32>()
+            .ok()
+            .filter(|value| (-32_768..65_536).contains(value))
+            .unwrap_or_else(|| exit_with_error(&format!("Invalid value in '{}'", setting)));
+        cpu.ram[address as usize] = value as u16;
+    }
+}
+
+// START..END, leaving out END, or a single ADDRESS.
+fn parse_range(text: &str, program: &Program) -> (u16, u16) {
+    match text.split_once("..") {
+        Some((start, end)) => (parse_address(start, program), parse_address(end, program)),
+        None => {
+            let address = parse_address(text, program);
+            (address, address + 1)
+        }
+    }
+}
+
+// Writes to the file, or to standard output for "-".
+fn write_output(path: &str, contents: &str) {
+    if path == "-" {
+        print!("{}", contents);
+    } else {
+        std::fs::write(path, contents)
+            .unwrap_or_else(|e| exit_with_error(&format!("Failed to write '{}': {}", path, e)));
+    }
+}
+
+// A buffered writer on the file, or on standard output for "-".
+fn create_output(path: &str) -> Box<dyn Write> {
+    let output: Box<dyn Write> = if path == "-" {
+        Box::new(io::stdout())
+    } else {
+        let file = File::create(path)
+            .unwrap_or_else(|e| exit_with_error(&format!("Failed to create '{}': {}", path, e)));
+        Box::new(file)
+    };
+    Box::new(BufWriter::new(output))
+}
+
+fn parse_address(text: &str, program: &Program) -> u16 {
+    program
+        .ram_address(text)
+        .unwrap_or_else(|e| exit_with_error(&e))
+}
+
+fn parse_number(value: Option<&String>) -> u64 {
+    value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| print_usage_and_exit())
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn print_usage_and_exit() -> ! {
+    eprintln!(
+        "Usage: emulator <PROGRAM> [--cycles N] [--set ADDRESS=VALUE]... [--dump START[..END]]..."
+    );
+    eprintln!(
+        "           [--screen IMAGE.png|IMAGE.pbm] [--screen-at CYCLE]... [--screen-every N]"
+    );
+    eprintln!("           [--watch [read:|write:|change:]ADDRESS[..END]]... [--trace FILE|-]");
+    eprintln!("           [--profile FILE|-] [--folded FILE|-] [--json FILE|-]");
+    eprintln!("           [--coverage FILE|-] [--coverage-listing FILE|-] [--console FILE|-] [--console-at ADDRESS]");
+    eprintln!("           [--check-stack] [--backtrace] [--cycle-trace FILE]");
+    eprintln!(
+        "           [--replay FILE|--keys SCRIPT] [--restore SNAPSHOT] [--snapshot SNAPSHOT]"
+    );
+    eprintln!("       emulator <PROGRAM> --terminal [--half-blocks] [--key-hold MS] [--speed N] [--cycles N]");
+    eprintln!(
+        "           [--record FILE] [--replay FILE|--keys SCRIPT] [--restore SNAPSHOT] [--snapshot SNAPSHOT]"
+    );
+    eprintln!("           [--console FILE] [--console-at ADDRESS]");
+    eprintln!(
+        "       emulator <PROGRAM> --debug [--history N] [--check-stack] [--set ADDRESS=VALUE]..."
+    );
+    eprintln!("       emulator <PROGRAM> --gdb PORT|- [--set ADDRESS=VALUE]...");
+    eprintln!("       emulator <script.tst>... [--coverage FILE|-] [--coverage-listing FILE|-]");
+    eprintln!("       emulator --export-trace <TRACE> [--csv FILE|-] [--vcd FILE|-]");
+    eprintln!("       emulator --diff <FIRST> <SECOND> [--observe START[..END]]... [--cycles N] [--set ADDRESS=VALUE]...");
+    eprintln!("PROGRAM is a .hack, .asm or .vm file, or a directory of .vm files.");
+    std::process::exit(1);
+}
@@ -0,0 +1,299 @@
+use std::collections::{BTreeSet, HashMap};
+
+use assembler::code::disassemble;
+
+use crate::cpu::{Cpu, Step, ROM_SIZE};
+use crate::loader::Program;
+use crate::run::Observer;
+use crate::vm::{self, Functions};
+
+// Counts executed instructions per ROM address and aggregates them per label, per VM
+// function and per VM command kind. Calls are followed as they happen, which gives a
+// call tree and folded stacks for flamegraph tools:
+//
+//   (program);Sys.init;Main.main;Math.multiply 5120
+
+const ROOT: &str = "(program)";
+const HOTTEST: usize = 20;
+
+struct Node {
+    function: String,
+    parent: Option<usize>,
+    children: HashMap<String, usize>,
+    // Instructions executed in the function itself.
+    own: u64,
+    calls: u64,
+}
+
+pub struct Profiler {
+    counts: Vec<u64>,
+    total: u64,
+    labels: Vec<(String, u16)>,
+    functions: Functions,
+    returns: BTreeSet<u16>,
+    // Kind of the VM command each address was translated from, e.g. push or call.
+    vm_commands: Vec<Option<String>>,
+    // Source text or disassembly of every address of the program.
+    code: Vec<String>,
+    nodes: Vec<Node>,
+    // Call tree node of every active call with the address it returns to.
+    stack: Vec<(usize, u16)>,
+}
+
+impl Profiler {
+    pub fn new(program: &Program) -> Self {
+        Profiler {
+            counts: vec![0; ROM_SIZE],
+            total: 0,
+            labels: labels(program),
+            functions: Functions::new(&program.symbols),
+            returns: vm::return_addresses(&program.symbols),
+            vm_commands: vm_commands(program),
+            code: code_lines(program),
+            nodes: vec![Node {
+                function: ROOT.to_string(),
+                parent: None,
+                children: HashMap::new(),
+                own: 0,
+                calls: 1,
+            }],
+            stack: Vec::new(),
+        }
+    }
+
+    fn current_node(&self) -> usize {
+        self.stack.last().map_or(0, |(node, _)| *node)
+    }
+
+    fn enter(&mut self, function: &str, return_address: u16) {
+        let parent = self.current_node();
+        let node = match self.nodes[parent].children.get(function) {
+            Some(node) => *node,
+            None => {
+                self.nodes.push(Node {
+                    function: function.to_string(),
+                    parent: Some(parent),
+                    children: HashMap::new(),
+                    own: 0,
+                    calls: 0,
+                });
+                let node = self.nodes.len() - 1;
+                self.nodes[parent]
+                    .children
+                    .insert(function.to_string(), node);
+                node
+            }
+        };
+        self.nodes[node].calls += 1;
+        self.stack.push((node, return_address));
+    }
+
+    // The flat profile followed by the call tree.
+    pub fn report(&self) -> String {
+        let mut report = format!("Profile of {} instructions\n", self.total);
+
+        let executed = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(address, count)| (address as u16, *count));
+        let mut by_label: HashMap<String, u64> = HashMap::new();
+        let mut by_function: HashMap<String, u64> = HashMap::new();
+        let mut by_command: HashMap<String, u64> = HashMap::new();
+        for (address, count) in executed.clone() {
+            *by_label.entry(self.label_of(address)).or_default() += count;
+            if !self.functions.is_empty() {
+                let function = self.functions.containing(address).unwrap_or(ROOT);
+                *by_function.entry(function.to_string()).or_default() += count;
+            }
+            if let Some(Some(command)) = self.vm_commands.get(address as usize) {
+                *by_command.entry(command.clone()).or_default() += count;
+            }
+        }
+        report.push_str(&self.table("VM function", by_function));
+        report.push_str(&self.table("VM command", by_command));
+        report.push_str(&self.table("label", by_label));
+
+        let mut hottest: Vec<(u16, u64)> = executed.collect();
+        hottest.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        report.push_str(&format!(
+            "\nHottest instructions\n{:>12} {:>6}  {:<10} {:<24} code\n",
+            "count", "%", "address", "label"
+        ));
+        for (address, count) in hottest.into_iter().take(HOTTEST) {
+            let code = self.code.get(address as usize).map_or("", String::as_str);
+            report.push_str(&format!(
+                "{:>12} {:>6}  {:<10} {:<24} {}\n",
+                count,
+                self.percent(count),
+                format!("ROM[{}]", address),
+                self.label_of(address),
+                code
+            ));
+        }
+
+        if self.nodes.len() > 1 {
+            report.push_str(&format!(
+                "\nCall tree\n{:>12} {:>6} {:>12} {:>8}  function\n",
+                "total", "%", "self", "calls"
+            ));
+            self.write_tree(&mut report, 0, 0);
+        }
+        report
+    }
+
+    // One line per call stack: the functions from the root down, then the instructions
+    // executed in the innermost one.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = (0..self.nodes.len())
+            .filter(|node| self.nodes[*node].own > 0)
+            .map(|node| {
+                let mut path = vec![self.nodes[node].function.as_str()];
+                let mut parent = self.nodes[node].parent;
+                while let Some(node) = parent {
+                    path.push(self.nodes[node].function.as_str());
+                    parent = self.nodes[node].parent;
+                }
+                path.reverse();
+                format!("{} {}", path.join(";"), self.nodes[node].own)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    fn table(&self, title: &str, counts: HashMap<String, u64>) -> String {
+        if counts.is_empty() {
+            return String::new();
+        }
+        let mut rows: Vec<(String, u64)> = counts.into_iter().collect();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut table = format!("\nBy {}\n{:>12} {:>6}  {}\n", title, "count", "%", title);
+        for (name, count) in rows {
+            table.push_str(&format!(
+                "{:>12} {:>6}  {}\n",
+                count,
+                self.percent(count),
+                name
+            ));
+        }
+        table
+    }
+
+    fn write_tree(&self, report: &mut String, node: usize, depth: usize) {
+        let total = self.tree_total(node);
+        report.push_str(&format!(
+            "{:>12} {:>6} {:>12} {:>8}  {}{}\n",
+            total,
+            self.percent(total),
+            self.nodes[node].own,
+            self.nodes[node].calls,
+            "  ".repeat(depth),
+            self.nodes[node].function
+        ));
+        let mut children: Vec<(u64, usize)> = self.nodes[node]
+            .children
+            .values()
+            .map(|child| (self.tree_total(*child), *child))
+            .collect();
+        children.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        for (_, child) in children {
+            self.write_tree(report, child, depth + 1);
+        }
+    }
+
+    fn tree_total(&self, node: usize) -> u64 {
+        self.nodes[node].own
+            + self.nodes[node]
+                .children
+                .values()
+                .map(|child| self.tree_total(*child))
+                .sum::<u64>()
+    }
+
+    fn label_of(&self, address: u16) -> String {
+        label_at(&self.labels, address)
+    }
+
+    fn percent(&self, count: u64) -> String {
+        format!("{:.1}%", count as f64 * 100.0 / self.total.max(1) as f64)
+    }
+}
+
+// The labels of the program, by address.
+pub(crate) fn labels(program: &Program) -> Vec<(String, u16)> {
+    program
+        .symbols
+        .labels()
+        .into_iter()
+        .map(|(label, address)| (label.to_string(), address))
+        .collect()
+}
+
+// The label whose code holds the address.
+pub(crate) fn label_at(labels: &[(String, u16)], address: u16) -> String {
+    // Of several labels at the same address, the first by name, like the debugger.
+    let index = labels.partition_point(|(_, start)| *start <= address);
+    match index.checked_sub(1) {
+        Some(index) => {
+            let start = labels[index].1;
+            let first = labels.partition_point(|(_, other)| *other < start);
+            labels[first].0.clone()
+        }
+        None => "(start)".to_string(),
+    }
+}
+
+// Kind of the VM command every address of the program was translated from.
+pub(crate) fn vm_commands(program: &Program) -> Vec<Option<String>> {
+    (0..program.words.len() as u16)
+        .map(|address| {
+            let location = program.source_map.vm_location(address)?;
+            location
+                .command
+                .split_whitespace()
+                .next()
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+// Source text without comments, or the disassembly, of every address of the program.
+pub(crate) fn code_lines(program: &Program) -> Vec<String> {
+    (0..program.words.len() as u16)
+        .map(|address| match program.source_map.asm_line(address) {
+            Some((_, text)) => text.split("//").next().unwrap_or("").trim().to_string(),
+            None => disassemble(program.words[address as usize]).unwrap_or_default(),
+        })
+        .collect()
+}
+
+impl Observer for Profiler {
+    fn after_step(&mut self, cpu: &Cpu, step: &Step) -> Result<(), String> {
+        self.counts[step.pc as usize] += 1;
+        self.total += 1;
+        let node = self.current_node();
+        self.nodes[node].own += 1;
+
+        if self
+            .stack
+            .last()
+            .is_some_and(|(_, return_address)| *return_address == cpu.pc)
+        {
+            self.stack.pop();
+        } else if step.jumped {
+            if let Some(return_address) =
+                vm::call_return_address(step.instruction, step.pc, &self.returns)
+            {
+                let function = self
+                    .functions
+                    .containing(cpu.pc)
+                    .unwrap_or(ROOT)
+                    .to_string();
+                self.enter(&function, return_address);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use std::fs;
+
+use crate::cpu::{Cpu, Step};
+use crate::run::Observer;
+
+// Keyboard input as the program saw it: the cycles at which the KBD register changed.
+// Feeding the same events to the same program reproduces a run exactly, since the
+// keyboard is the only input of the Hack computer. Replay files hold one event per line:
+//
+//   # cycle key
+//   1200345 131
+//   1210000 0
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    // The key is set when this many instructions have run, before the next one.
+    pub cycle: u64,
+    pub key: u16,
+}
+
+pub struct Replay {
+    pub events: Vec<KeyEvent>,
+    // Index of the next event to apply.
+    pub position: usize,
+}
+
+impl Replay {
+    pub fn new(events: Vec<KeyEvent>) -> Self {
+        Replay {
+            events,
+            position: 0,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+        let events = parse_events(&text).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Replay::new(events))
+    }
+
+    // Sets the key of every event that is due.
+    pub fn apply(&mut self, cpu: &mut Cpu) {
+        while let Some(event) = self.events.get(self.position) {
+            if event.cycle > cpu.cycles {
+                break;
+            }
+            cpu.set_key(event.key);
+            self.position += 1;
+        }
+    }
+}
+
+impl Observer for Replay {
+    fn before_step(&mut self, cpu: &mut Cpu) -> Result<(), String> {
+        self.apply(cpu);
+        Ok(())
+    }
+
+    fn after_step(&mut self, _cpu: &Cpu, _step: &Step) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub fn parse_events(text: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut events: Vec<KeyEvent> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let event = line
+            .split_once(char::is_whitespace)
+            .and_then(|(cycle, key)| {
+                Some(KeyEvent {
+                    cycle: cycle.parse().ok()?,
+                    key: key.trim().parse().ok()?,
+                })
+            })
+            .ok_or_else(|| format!("Line {}: expected 'CYCLE KEY', found '{}'", index + 1, line))?;
+        if events.last().is_some_and(|last| last.cycle > event.cycle) {
+            return Err(format!("Line {}: events must be in cycle order", index + 1));
+        }
+        events.push(event);
+    }
+    Ok(events)
+}
+
+pub fn format_events(events: &[KeyEvent]) -> String {
+    let mut text = String::from("# cycle key\n");
+    for event in events {
+        text.push_str(&format!("{} {}\n", event.cycle, event.key));
+    }
+    text
+}
+
+// Collects the changes of the KBD register made by a live front end.
+#[derive(Default)]
+pub struct Recorder {
+    pub events: Vec<KeyEvent>,
+}
+
+impl Recorder {
+    pub fn record(&mut self, cpu: &Cpu, key: u16) {
+        let current = self.events.last().map_or(0, |event| event.key);
+        if key != current {
+            self.events.push(KeyEvent {
+                cycle: cpu.cycles,
+                key,
+            });
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::cpu::{Cpu, Step};
+use crate::halt::{HaltDetector, Stop};
+
+// Running a program headlessly, with tools observing every executed instruction.
+
+// Something that watches the program run, one instruction at a time.
+pub trait Observer {
+    // Called before every step. Observers that feed input, like keyboard replays, set it here.
+    fn before_step(&mut self, _cpu: &mut Cpu) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn after_step(&mut self, cpu: &Cpu, step: &Step) -> Result<(), String>;
+
+    // Checked after every step, true to end the run, e.g. when a watchpoint was hit.
+    fn should_stop(&self) -> bool {
+        false
+    }
+
+    // Called once when the run ends.
+    fn finish(&mut self, _cpu: &Cpu, _stop: Stop) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Runs until the program halts, an observer asks to stop or `max_cycles` instructions
+// have been executed in total.
+pub fn run(
+    cpu: &mut Cpu,
+    max_cycles: u64,
+    observers: &mut [&mut dyn Observer],
+) -> Result<Stop, String> {
+    let mut stop = Stop::CycleLimit;
+    let mut halt = HaltDetector::new();
+    while cpu.cycles < max_cycles {
+        for observer in observers.iter_mut() {
+            observer.before_step(cpu)?;
+        }
+        let step = cpu.step();
+        for observer in observers.iter_mut() {
+            observer.after_step(cpu, &step)?;
+        }
+        if halt.is_halted(cpu, &step) {
+            stop = Stop::Halted;
+            break;
+        }
+        if observers.iter().any(|observer| observer.should_stop()) {
+            stop = Stop::Requested;
+            break;
+        }
+    }
+    for observer in observers.iter_mut() {
+        observer.finish(cpu, stop)?;
+    }
+    Ok(stop)
+}
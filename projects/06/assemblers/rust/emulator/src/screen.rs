@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::{Cpu, Step, SCREEN_WORDS};
+use crate::halt::Stop;
+use crate::run::Observer;
+
+// Images of the 512x256 monochrome Hack screen. Each row takes 32 words and the
+// least significant bit of a word is the leftmost of its 16 pixels. A set bit is black.
+
+pub const WIDTH: usize = 512;
+pub const HEIGHT: usize = 256;
+
+pub fn pixel(screen: &[u16], row: usize, column: usize) -> bool {
+    screen[row * 32 + column / 16] & (1 << (column % 16)) != 0
+}
+
+// Writes the screen as .png or .pbm, depending on the extension of the path.
+pub fn write_image(path: &Path, screen: &[u16]) -> Result<(), String> {
+    let bytes = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => to_png(screen),
+        Some("pbm") => to_pbm(screen),
+        _ => return Err(format!("'{}' should end in .png or .pbm", path.display())),
+    };
+    fs::write(path, bytes).map_err(|e| format!("Failed to write '{}': {}", path.display(), e))
+}
+
+// Rows of pixels packed 8 to a byte, leftmost pixel in the most significant bit.
+fn packed_rows(screen: &[u16], set_is_one: bool) -> Vec<[u8; WIDTH / 8]> {
+    assert_eq!(screen.len(), SCREEN_WORDS);
+    (0..HEIGHT)
+        .map(|row| {
+            let mut bytes = [0u8; WIDTH / 8];
+            for (index, byte) in bytes.iter_mut().enumerate() {
+                for bit in 0..8 {
+                    if pixel(screen, row, index * 8 + bit) == set_is_one {
+                        *byte |= 0x80 >> bit;
+                    }
+                }
+            }
+            bytes
+        })
+        .collect()
+}
+
+// Binary PBM (P4), where 1 is black.
+pub fn to_pbm(screen: &[u16]) -> Vec<u8> {
+    let mut bytes = format!("P4\n{} {}\n", WIDTH, HEIGHT).into_bytes();
+    for row in packed_rows(screen, true) {
+        bytes.extend_from_slice(&row);
+    }
+    bytes
+}
+
+// 1-bit grayscale PNG, where 0 is black. The image data is stored uncompressed,
+// which keeps the encoder small and still gives files of about 17KB.
+pub fn to_png(screen: &[u16]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(HEIGHT * (WIDTH / 8 + 1));
+    for row in packed_rows(screen, false) {
+        // Filter type 0, no filtering.
+        raw.push(0);
+        raw.extend_from_slice(&row);
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+    header.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+    // Bit depth 1, grayscale, deflate, standard filtering, no interlacing.
+    header.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// A zlib stream made of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(65_535).collect();
+    for (index, block) in blocks.iter().enumerate() {
+        let last = index + 1 == blocks.len();
+        stream.push(last as u8);
+        let length = block.len() as u16;
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
+
+// Saves images of the screen while a program runs: at chosen cycles and every N cycles,
+// named <stem>-<cycle>.<ext>, and at the end of the run under the path itself.
+pub struct ScreenCapture {
+    pub path: PathBuf,
+    pub at: Vec<u64>,
+    pub every: Option<u64>,
+}
+
+impl ScreenCapture {
+    fn numbered_path(&self, cycle: u64) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("screen");
+        let extension = self
+            .path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("png");
+        self.path
+            .with_file_name(format!("{}-{:09}.{}", stem, cycle, extension))
+    }
+}
+
+impl Observer for ScreenCapture {
+    fn after_step(&mut self, cpu: &Cpu, _step: &Step) -> Result<(), String> {
+        let cycle = cpu.cycles;
+        let periodic = self
+            .every
+            .is_some_and(|every| every > 0 && cycle.is_multiple_of(every));
+        if periodic || self.at.contains(&cycle) {
+            write_image(&self.numbered_path(cycle), cpu.screen())?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, cpu: &Cpu, _stop: Stop) -> Result<(), String> {
+        write_image(&self.path, cpu.screen())
+    }
+}
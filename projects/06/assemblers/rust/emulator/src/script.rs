@@ -0,0 +1,564 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assembler::comparer;
+
+use crate::coverage::Coverage;
+use crate::cpu::{Cpu, RAM_SIZE, ROM_SIZE};
+use crate::loader;
+use crate::run::Observer;
+
+// Interpreter for the CPU emulator test scripts of the course (.tst files), e.g.
+//
+//   load BasicTest.asm, output-file BasicTest.out, compare-to BasicTest.cmp,
+//   set RAM[0] 256,
+//   repeat 600 { ticktock; }
+//   output-list RAM[256]%D1.6.1; output;
+//
+// Supported commands: load, output-file, compare-to, output-list, output, set,
+// repeat, while, ticktock, tick, tock, echo and clear-echo. Commands end with `,`, `;` or `!`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    A,
+    D,
+    Pc,
+    Time,
+    Ram(u16),
+    Rom(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Radix {
+    Decimal,
+    Binary,
+    Hex,
+    Text,
+}
+
+// One column of the output-list, e.g. RAM[256]%D1.6.1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    name: String,
+    location: Location,
+    radix: Radix,
+    left: usize,
+    width: usize,
+    right: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Load(Option<String>),
+    OutputFile(String),
+    CompareTo(String),
+    OutputList(Vec<Column>),
+    Output,
+    Set(Location, u16),
+    Repeat(u64, Vec<Command>),
+    While(Location, Condition, u16, Vec<Command>),
+    Tick,
+    Tock,
+    TickTock,
+    Echo(String),
+    ClearEcho,
+}
+
+// Result of running a script.
+pub struct ScriptReport {
+    pub output_file: Option<PathBuf>,
+    pub compare_file: Option<PathBuf>,
+    // Err holds the first miscompared line.
+    pub comparison: Option<Result<(), String>>,
+    // Coverage of the program the script loaded last, when asked for.
+    pub coverage: Option<Coverage>,
+}
+
+impl ScriptReport {
+    pub fn passed(&self) -> bool {
+        !matches!(self.comparison, Some(Err(_)))
+    }
+}
+
+// Runs the script, writes its output file and compares it with the compare file.
+pub fn run_script(path: &Path) -> Result<ScriptReport, String> {
+    run(path, false)
+}
+
+// Like run_script, also recording the coverage of the program the script tests.
+pub fn cover_script(path: &Path) -> Result<ScriptReport, String> {
+    run(path, true)
+}
+
+fn run(path: &Path, cover: bool) -> Result<ScriptReport, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let commands = parse_script(&source)?;
+    let directory = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let mut runner = Runner {
+        cpu: Cpu::new(),
+        directory,
+        script_name: path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_string(),
+        columns: Vec::new(),
+        output: String::new(),
+        output_file: None,
+        compare_file: None,
+        cover,
+        coverage: None,
+    };
+    runner.execute(&commands)?;
+
+    if let Some(output_file) = &runner.output_file {
+        fs::write(output_file, &runner.output)
+            .map_err(|e| format!("Failed to write '{}': {}", output_file.display(), e))?;
+    }
+    let comparison = match &runner.compare_file {
+        Some(compare_file) => {
+            let expected = fs::read_to_string(compare_file)
+                .map_err(|e| format!("Failed to read '{}': {}", compare_file.display(), e))?;
+            Some(comparer::compare_table(&runner.output, &expected))
+        }
+        None => None,
+    };
+    Ok(ScriptReport {
+        output_file: runner.output_file,
+        compare_file: runner.compare_file,
+        comparison,
+        coverage: runner.coverage,
+    })
+}
+
+struct Runner {
+    cpu: Cpu,
+    directory: PathBuf,
+    script_name: String,
+    columns: Vec<Column>,
+    output: String,
+    output_file: Option<PathBuf>,
+    compare_file: Option<PathBuf>,
+    cover: bool,
+    coverage: Option<Coverage>,
+}
+
+impl Runner {
+    fn execute(&mut self, commands: &[Command]) -> Result<(), String> {
+        for command in commands {
+            match command {
+                Command::Load(file) => {
+                    let file = match file {
+                        Some(file) => file.clone(),
+                        None => format!("{}.hack", self.script_name),
+                    };
+                    let path = self.directory.join(&file);
+                    let program = loader::load_file(&path.to_string_lossy())?;
+                    self.cpu = Cpu::new();
+                    self.cpu.load_rom(&program.words)?;
+                    if self.cover {
+                        self.coverage = Some(Coverage::new(&program));
+                    }
+                }
+                Command::OutputFile(file) => {
+                    self.output_file = Some(self.directory.join(file));
+                    self.output.clear();
+                }
+                Command::CompareTo(file) => self.compare_file = Some(self.directory.join(file)),
+                Command::OutputList(columns) => {
+                    self.columns = columns.clone();
+                    let header: String = columns.iter().map(Column::header).collect();
+                    self.output.push_str(&format!("|{}\n", header));
+                }
+                Command::Output => {
+                    let row: String = self
+                        .columns
+                        .iter()
+                        .map(|column| column.format(self.read(column.location)))
+                        .collect();
+                    self.output.push_str(&format!("|{}\n", row));
+                }
+                Command::Set(location, value) => self.write(*location, *value),
+                Command::Repeat(count, body) => {
+                    for _ in 0..*count {
+                        self.execute(body)?;
+                    }
+                }
+                Command::While(location, condition, value, body) => {
+                    while condition.holds(self.read(*location), *value) {
+                        self.execute(body)?;
+                    }
+                }
+                // The emulator executes a whole instruction per clock cycle, on the tock.
+                Command::Tick => {}
+                Command::Tock | Command::TickTock => {
+                    let step = self.cpu.step();
+                    if let Some(coverage) = self.coverage.as_mut() {
+                        coverage.after_step(&self.cpu, &step)?;
+                    }
+                }
+                Command::Echo(text) => println!("{}", text),
+                Command::ClearEcho => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, location: Location) -> u16 {
+        match location {
+            Location::A => self.cpu.a,
+            Location::D => self.cpu.d,
+            Location::Pc => self.cpu.pc,
+            Location::Time => self.cpu.cycles as u16,
+            Location::Ram(address) => self.cpu.ram[address as usize],
+            Location::Rom(address) => self.cpu.rom[address as usize],
+        }
+    }
+
+    fn write(&mut self, location: Location, value: u16) {
+        match location {
+            Location::A => self.cpu.a = value,
+            Location::D => self.cpu.d = value,
+            Location::Pc => self.cpu.pc = value & 0x7FFF,
+            Location::Time => self.cpu.cycles = value as u64,
+            Location::Ram(address) => self.cpu.ram[address as usize] = value,
+            Location::Rom(address) => self.cpu.rom[address as usize] = value,
+        }
+    }
+}
+
+impl Column {
+    fn total_width(&self) -> usize {
+        self.left + self.width + self.right
+    }
+
+    // The column name centered in the column, cut to fit like the course tools do.
+    fn header(&self) -> String {
+        let total = self.total_width();
+        let name: String = self.name.chars().take(total).collect();
+        let left = (total - name.len()) / 2;
+        let right = total - name.len() - left;
+        format!("{}{}{}|", " ".repeat(left), name, " ".repeat(right))
+    }
+
+    fn format(&self, value: u16) -> String {
+        let digits = match self.radix {
+            Radix::Decimal => format!("{:>width$}", value as i16, width = self.width),
+            Radix::Binary => last_chars(&format!("{:016b}", value), self.width),
+            Radix::Hex => last_chars(&format!("{:04X}", value), self.width),
+            Radix::Text => {
+                let c = char::from_u32(value as u32).unwrap_or(' ');
+                format!("{:<width$}", c, width = self.width)
+            }
+        };
+        format!(
+            "{}{}{}|",
+            " ".repeat(self.left),
+            digits,
+            " ".repeat(self.right)
+        )
+    }
+}
+
+// Keeps the last `width` characters, padding with zeros when the text is shorter.
+fn last_chars(text: &str, width: usize) -> String {
+    if text.len() >= width {
+        text[text.len() - width..].to_string()
+    } else {
+        format!("{}{}", "0".repeat(width - text.len()), text)
+    }
+}
+
+impl Condition {
+    fn holds(&self, left: u16, right: u16) -> bool {
+        let (left, right) = (left as i16, right as i16);
+        match self {
+            Condition::Equal => left == right,
+            Condition::NotEqual => left != right,
+            Condition::Less => left < right,
+            Condition::Greater => left > right,
+            Condition::LessOrEqual => left <= right,
+            Condition::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+fn parse_script(source: &str) -> Result<Vec<Command>, String> {
+    let tokens = tokenize(source)?;
+    let mut position = 0;
+    let commands = parse_block(&tokens, &mut position, false)?;
+    Ok(commands)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+// Splits the script into words, quoted strings and the punctuation , ; ! { }.
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c == '"' {
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(format!("Line {}: unterminated string", line));
+            }
+            tokens.push(Token {
+                text: format!("\"{}", chars[start..i].iter().collect::<String>()),
+                line,
+            });
+            i += 1;
+        } else if ",;!{}".contains(c) {
+            tokens.push(Token {
+                text: c.to_string(),
+                line,
+            });
+            i += 1;
+        } else {
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() && !",;!{}\"".contains(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Token {
+                text: chars[start..i].iter().collect(),
+                line,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_block(
+    tokens: &[Token],
+    position: &mut usize,
+    nested: bool,
+) -> Result<Vec<Command>, String> {
+    let mut commands = Vec::new();
+    while *position < tokens.len() {
+        let token = &tokens[*position];
+        let error = |message: String| format!("Line {}: {}", token.line, message);
+        *position += 1;
+        // Words up to the end of the command.
+        let mut words: Vec<&Token> = Vec::new();
+        match token.text.as_str() {
+            "," | ";" | "!" => continue,
+            "}" if nested => return Ok(commands),
+            "}" => return Err(error("unexpected '}'".to_string())),
+            _ => words.push(token),
+        }
+        while *position < tokens.len()
+            && !matches!(tokens[*position].text.as_str(), "," | ";" | "!" | "{" | "}")
+        {
+            words.push(&tokens[*position]);
+            *position += 1;
+        }
+        let opens_block = tokens.get(*position).is_some_and(|t| t.text == "{");
+        if opens_block {
+            *position += 1;
+        }
+        let arguments: Vec<&str> = words[1..].iter().map(|t| t.text.as_str()).collect();
+
+        let command = match (token.text.as_str(), opens_block) {
+            ("repeat", true) => {
+                let count = match arguments.as_slice() {
+                    [count] => count
+                        .parse::<u64>()
+                        .map_err(|_| error(format!("invalid repeat count '{}'", count)))?,
+                    // An endless repeat only makes sense in the interactive tools.
+                    _ => return Err(error("repeat needs a count to run headlessly".to_string())),
+                };
+                Command::Repeat(count, parse_block(tokens, position, true)?)
+            }
+            ("while", true) => {
+                let (location, condition, value) = match arguments.as_slice() {
+                    [location, condition, value] => (
+                        parse_location(location).map_err(&error)?,
+                        parse_condition(condition).map_err(&error)?,
+                        parse_value(value).map_err(&error)?,
+                    ),
+                    _ => {
+                        return Err(error(
+                            "expected while <variable> <op> <value> {".to_string(),
+                        ))
+                    }
+                };
+                Command::While(
+                    location,
+                    condition,
+                    value,
+                    parse_block(tokens, position, true)?,
+                )
+            }
+            (_, true) => return Err(error(format!("unexpected '{{' after {}", token.text))),
+            ("load", false) => Command::Load(arguments.first().map(|file| file.to_string())),
+            ("output-file", false) => Command::OutputFile(single(&arguments).map_err(&error)?),
+            ("compare-to", false) => Command::CompareTo(single(&arguments).map_err(&error)?),
+            ("output-list", false) => Command::OutputList(
+                arguments
+                    .iter()
+                    .map(|column| parse_column(column))
+                    .collect::<Result<Vec<Column>, String>>()
+                    .map_err(&error)?,
+            ),
+            ("output", false) => Command::Output,
+            ("set", false) => match arguments.as_slice() {
+                [location, value] => Command::Set(
+                    parse_location(location).map_err(&error)?,
+                    parse_value(value).map_err(&error)?,
+                ),
+                _ => return Err(error("expected set <variable> <value>".to_string())),
+            },
+            ("tick", false) => Command::Tick,
+            ("tock", false) => Command::Tock,
+            ("ticktock", false) => Command::TickTock,
+            ("echo", false) => Command::Echo(
+                arguments
+                    .iter()
+                    .map(|word| word.trim_start_matches('"'))
+                    .collect::<Vec<&str>>()
+                    .join(" "),
+            ),
+            ("clear-echo", false) => Command::ClearEcho,
+            (other, false) => return Err(error(format!("unknown command '{}'", other))),
+        };
+        commands.push(command);
+    }
+    if nested {
+        return Err("Missing '}' at the end of the script".to_string());
+    }
+    Ok(commands)
+}
+
+fn single(arguments: &[&str]) -> Result<String, String> {
+    match arguments {
+        [argument] => Ok(argument.to_string()),
+        _ => Err("expected a single file name".to_string()),
+    }
+}
+
+fn parse_location(text: &str) -> Result<Location, String> {
+    let indexed = |prefix: &str, size: usize| -> Option<Result<u16, String>> {
+        let index = text.strip_prefix(prefix)?.strip_suffix(']')?;
+        Some(
+            index
+                .parse::<u16>()
+                .ok()
+                .filter(|index| (*index as usize) < size)
+                .ok_or_else(|| format!("invalid address in '{}'", text)),
+        )
+    };
+    if let Some(address) = indexed("RAM[", RAM_SIZE) {
+        return Ok(Location::Ram(address?));
+    }
+    if let Some(address) = indexed("ROM[", ROM_SIZE) {
+        return Ok(Location::Rom(address?));
+    }
+    match text {
+        "A" => Ok(Location::A),
+        "D" => Ok(Location::D),
+        "PC" => Ok(Location::Pc),
+        "time" => Ok(Location::Time),
+        _ => Err(format!("unknown variable '{}'", text)),
+    }
+}
+
+// Values are decimal, or %B, %X and %D prefixed.
+fn parse_value(text: &str) -> Result<u16, String> {
+    let (digits, radix) = match text.get(..2) {
+        Some("%B") => (&text[2..], 2),
+        Some("%X") => (&text[2..], 16),
+        Some("%D") => (&text[2..], 10),
+        _ => (text, 10),
+    };
+    let value =
+        i32::from_str_radix(digits, radix).map_err(|_| format!("invalid value '{}'", text))?;
+    if !(-32_768..65_536).contains(&value) {
+        return Err(format!("value '{}' does not fit in 16 bits", text));
+    }
+    Ok(value as u16)
+}
+
+fn parse_condition(text: &str) -> Result<Condition, String> {
+    match text {
+        "=" => Ok(Condition::Equal),
+        "<>" => Ok(Condition::NotEqual),
+        "<" => Ok(Condition::Less),
+        ">" => Ok(Condition::Greater),
+        "<=" => Ok(Condition::LessOrEqual),
+        ">=" => Ok(Condition::GreaterOrEqual),
+        _ => Err(format!("unknown condition '{}'", text)),
+    }
+}
+
+// NAME%Rl.w.r, where the format defaults to %B1.16.1 like the course tools.
+fn parse_column(text: &str) -> Result<Column, String> {
+    let (name, format) = match text.split_once('%') {
+        Some((name, format)) => (name, format),
+        None => (text, "B1.16.1"),
+    };
+    let location = parse_location(name)?;
+    let mut chars = format.chars();
+    let radix = match chars.next() {
+        Some('D') => Radix::Decimal,
+        Some('B') => Radix::Binary,
+        Some('X') => Radix::Hex,
+        Some('S') => Radix::Text,
+        _ => return Err(format!("invalid output format in '{}'", text)),
+    };
+    let sizes: Vec<usize> = chars
+        .as_str()
+        .split('.')
+        .map(|size| size.parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| format!("invalid output format in '{}'", text))?;
+    let [left, width, right] = sizes[..] else {
+        return Err(format!("invalid output format in '{}'", text));
+    };
+    Ok(Column {
+        name: name.to_string(),
+        location,
+        radix,
+        left,
+        width,
+        right,
+    })
+}
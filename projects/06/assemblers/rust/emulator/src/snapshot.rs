@@ -0,0 +1,72 @@
+use std::fs;
+
+use crate::cpu::{Cpu, RAM_SIZE, ROM_SIZE};
+
+// The complete state of the machine in a file: registers, cycle count, position in the
+// keyboard replay, ROM and RAM. All numbers are big-endian.
+//
+//   "HACKSNAP" version:u32 A:u16 D:u16 PC:u16 cycles:u64 replay-position:u64
+//   ROM: 32768 x u16, RAM: 32768 x u16
+
+const MAGIC: &[u8; 8] = b"HACKSNAP";
+const VERSION: u32 = 1;
+const HEADER: usize = 8 + 4 + 3 * 2 + 2 * 8;
+
+pub struct Snapshot {
+    pub cpu: Cpu,
+    // Index of the next event of the keyboard replay.
+    pub replay_position: usize,
+}
+
+pub fn encode(cpu: &Cpu, replay_position: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER + 2 * (ROM_SIZE + RAM_SIZE));
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_be_bytes());
+    for register in [cpu.a, cpu.d, cpu.pc] {
+        bytes.extend_from_slice(&register.to_be_bytes());
+    }
+    bytes.extend_from_slice(&cpu.cycles.to_be_bytes());
+    bytes.extend_from_slice(&(replay_position as u64).to_be_bytes());
+    for word in cpu.rom.iter().chain(cpu.ram.iter()) {
+        bytes.extend_from_slice(&word.to_be_bytes());
+    }
+    bytes
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Snapshot, String> {
+    if bytes.len() < HEADER || &bytes[..8] != MAGIC {
+        return Err("Not a Hack snapshot".to_string());
+    }
+    let u16_at = |offset: usize| u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+    let u64_at = |offset: usize| u64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap());
+    let version = u32::from_be_bytes(bytes[8..12].try_into().unwrap());
+    if version != VERSION {
+        return Err(format!("Unsupported snapshot version {}", version));
+    }
+    if bytes.len() != HEADER + 2 * (ROM_SIZE + RAM_SIZE) {
+        return Err("Snapshot is truncated".to_string());
+    }
+    let mut cpu = Cpu::new();
+    cpu.a = u16_at(12);
+    cpu.d = u16_at(14);
+    cpu.pc = u16_at(16);
+    cpu.cycles = u64_at(18);
+    let replay_position = u64_at(26) as usize;
+    for (index, word) in cpu.rom.iter_mut().chain(cpu.ram.iter_mut()).enumerate() {
+        *word = u16_at(HEADER + 2 * index);
+    }
+    Ok(Snapshot {
+        cpu,
+        replay_position,
+    })
+}
+
+pub fn save(path: &str, cpu: &Cpu, replay_position: usize) -> Result<(), String> {
+    fs::write(path, encode(cpu, replay_position))
+        .map_err(|e| format!("Failed to write '{}': {}", path, e))
+}
+
+pub fn load(path: &str) -> Result<Snapshot, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    decode(&bytes).map_err(|e| format!("{}: {}", path, e))
+}
@@ -0,0 +1,97 @@
+// Where the words of a program came from: the line of the .asm source and, for the
+// output of the VM translator, the VM command the line was generated from.
+//
+// The translator marks the code of every VM command with a comment such as
+//
+//   // vm SimpleAdd.vm:3 add
+//
+// and the instructions that follow belong to that command, up to the next marker.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmLocation {
+    pub file: String,
+    pub line: usize,
+    pub command: String,
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    // Lines of the .asm source, empty for programs loaded from .hack files.
+    asm: Vec<String>,
+    // 1-based source line of every ROM address.
+    lines: Vec<Option<usize>>,
+    // Index into `vm_locations` of the VM command in effect at every source line.
+    vm_at_line: Vec<Option<usize>>,
+    vm_locations: Vec<VmLocation>,
+}
+
+impl SourceMap {
+    // `lines` holds the source line of every word, as given by the assembler.
+    pub fn new(source: &str, lines: Vec<Option<usize>>) -> Self {
+        let asm: Vec<String> = source.lines().map(str::to_string).collect();
+        let mut vm_locations = Vec::new();
+        let mut vm_at_line = Vec::with_capacity(asm.len());
+        let mut current = None;
+        for line in &asm {
+            if let Some(location) = parse_vm_marker(line) {
+                vm_locations.push(location);
+                current = Some(vm_locations.len() - 1);
+            }
+            vm_at_line.push(current);
+        }
+        SourceMap {
+            asm,
+            lines,
+            vm_at_line,
+            vm_locations,
+        }
+    }
+
+    // Line number and text of the source line the word at `address` came from.
+    pub fn asm_line(&self, address: u16) -> Option<(usize, &str)> {
+        let line = (*self.lines.get(address as usize)?)?;
+        Some((line, self.asm.get(line - 1)?.as_str()))
+    }
+
+    pub fn vm_location(&self, address: u16) -> Option<&VmLocation> {
+        let (line, _) = self.asm_line(address)?;
+        let index = (*self.vm_at_line.get(line - 1)?)?;
+        self.vm_locations.get(index)
+    }
+
+    // The source lines, for listings.
+    pub fn source(&self) -> &[String] {
+        &self.asm
+    }
+
+    // ROM address of the first word translated from a line of a .vm file, if any.
+    pub fn address_of_vm_line(&self, file: &str, line: usize) -> Option<u16> {
+        (0..self.lines.len() as u16).find(|address| {
+            self.vm_location(*address)
+                .is_some_and(|location| location.file == file && location.line == line)
+        })
+    }
+
+    // ROM address of the first word assembled from the line, if any.
+    pub fn address_of_line(&self, line: usize) -> Option<u16> {
+        self.lines
+            .iter()
+            .position(|source_line| *source_line == Some(line))
+            .map(|address| address as u16)
+    }
+}
+
+fn parse_vm_marker(line: &str) -> Option<VmLocation> {
+    let marker = line
+        .trim()
+        .strip_prefix("//")?
+        .trim_start()
+        .strip_prefix("vm ")?;
+    let (position, command) = marker.trim().split_once(' ').unwrap_or((marker.trim(), ""));
+    let (file, line) = position.rsplit_once(':')?;
+    Some(VmLocation {
+        file: file.to_string(),
+        line: line.parse().ok()?,
+        command: command.trim().to_string(),
+    })
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::cpu::{Cpu, Step, SCREEN};
+use crate::loader::Program;
+use crate::run::Observer;
+use crate::vm::{self, Functions};
+
+// Checks that a program keeps to the memory conventions of the VM while it runs:
+//
+//   RAM[0..5]       SP, LCL, ARG, THIS and THAT
+//   RAM[256..2048]  the stack
+//   RAM[2048..]     the heap, up to the screen at 16384
+//
+// It stops at the first of these, the bugs code generators for call and return make most:
+//
+// - a pop taking SP below the current frame, or below 256 outside of any call
+// - the stack growing into the heap, or SP going past 16383
+// - THIS or THAT set to an address in the stack
+// - a call whose frame is not where LCL points, or a return that does not go back to
+//   the innermost call or does not restore the caller's frame
+//
+// Calls are recognized by the return labels of the VM translator, see vm.rs, and checked
+// when they jump straight to the function. Calls through a shared routine, as compact
+// translators make them, and programs without return labels get the checks on SP, THIS
+// and THAT only.
+
+pub const SP: u16 = 0;
+pub const LCL: u16 = 1;
+pub const ARG: u16 = 2;
+pub const THIS: u16 = 3;
+pub const THAT: u16 = 4;
+pub const STACK_BASE: u16 = 256;
+pub const HEAP_BASE: u16 = 2048;
+
+const POINTERS: [&str; 5] = ["SP", "LCL", "ARG", "THIS", "THAT"];
+
+// The first broken convention, found after the instruction at `pc` ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub pc: u16,
+    pub cycle: u64,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (ROM[{}], cycle {})",
+            self.message, self.pc, self.cycle
+        )
+    }
+}
+
+// A call in progress: where it returns to, the callee's LCL and ARG, and the LCL, ARG,
+// THIS and THAT of the caller the return has to restore.
+struct Frame {
+    return_address: u16,
+    lcl: u16,
+    arg: u16,
+    caller: [u16; 4],
+}
+
+pub struct StackChecker {
+    returns: BTreeSet<u16>,
+    functions: Functions,
+    frames: Vec<Frame>,
+    pub violation: Option<Violation>,
+}
+
+impl StackChecker {
+    pub fn new(program: &Program) -> Self {
+        StackChecker {
+            returns: vm::return_addresses(&program.symbols),
+            functions: Functions::new(&program.symbols),
+            frames: Vec::new(),
+            violation: None,
+        }
+    }
+
+    // Forgets the calls in progress, for when the machine state changed behind the
+    // checker's back. Returns to calls it never saw are not checked.
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.violation = None;
+    }
+
+    fn check(&mut self, cpu: &Cpu, step: &Step) -> Result<(), String> {
+        if let Some(write) = step.write {
+            match write.address {
+                SP => self.check_sp(write.old, write.new)?,
+                THIS | THAT if (STACK_BASE..HEAP_BASE).contains(&write.new) => {
+                    return Err(format!(
+                        "{} points into the stack at {}",
+                        POINTERS[write.address as usize], write.new
+                    ));
+                }
+                _ => {}
+            }
+        }
+        if !step.jumped {
+            return Ok(());
+        }
+        // A goto to a label at a return address is no return: it loads the address
+        // itself, right before the jump.
+        let goto = step.pc > 0 && cpu.rom[step.pc as usize - 1] == cpu.pc;
+        match vm::call_return_address(step.instruction, step.pc, &self.returns) {
+            Some(return_address) if self.functions.starting_at(cpu.pc).is_some() => {
+                self.enter(cpu, return_address)
+            }
+            Some(_) => Ok(()),
+            None if self.returns.contains(&cpu.pc) && !goto => self.leave(cpu),
+            None => Ok(()),
+        }
+    }
+
+    fn check_sp(&self, old: u16, sp: u16) -> Result<(), String> {
+        if sp >= SCREEN {
+            return Err(format!("SP went past 16383 to {}", sp));
+        }
+        if sp > HEAP_BASE && old <= HEAP_BASE {
+            return Err(format!("The stack grew into the heap, SP is {}", sp));
+        }
+        // Pops take one word at a time, returns drop the whole frame at once.
+        if sp.wrapping_add(1) == old {
+            match self.frames.last() {
+                Some(frame) if sp < frame.lcl => {
+                    return Err(format!(
+                        "Stack underflow: SP went down to {}, below the frame at LCL {}",
+                        sp, frame.lcl
+                    ));
+                }
+                None if sp < STACK_BASE => {
+                    return Err(format!(
+                        "Stack underflow: SP went down to {}, below the stack at {}",
+                        sp, STACK_BASE
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Right after the jump of a call, LCL = SP and the saved frame lies below it.
+    fn enter(&mut self, cpu: &Cpu, return_address: u16) -> Result<(), String> {
+        let ram = |address: u16| cpu.ram[address as usize];
+        let lcl = ram(LCL);
+        if ram(SP) != lcl {
+            return Err(format!("The call set LCL to {} but SP is {}", lcl, ram(SP)));
+        }
+        if lcl < STACK_BASE + 5 || ram(lcl - 5) != return_address {
+            return Err(format!(
+                "The call did not save its return address ROM[{}] at LCL - 5",
+                return_address
+            ));
+        }
+        self.frames.push(Frame {
+            return_address,
+            lcl,
+            arg: ram(ARG),
+            caller: [ram(lcl - 4), ram(lcl - 3), ram(lcl - 2), ram(lcl - 1)],
+        });
+        Ok(())
+    }
+
+    // Right after the jump of a return, SP is past the return value and the caller's
+    // pointers are back.
+    fn leave(&mut self, cpu: &Cpu) -> Result<(), String> {
+        let Some(frame) = self.frames.pop() else {
+            return Ok(());
+        };
+        if frame.return_address != cpu.pc {
+            return Err(format!(
+                "Returned to ROM[{}] but the innermost call returns to ROM[{}]",
+                cpu.pc, frame.return_address
+            ));
+        }
+        let sp = cpu.ram[SP as usize];
+        if sp != frame.arg.wrapping_add(1) {
+            return Err(format!(
+                "The return left SP at {} instead of ARG + 1 = {}",
+                sp,
+                frame.arg.wrapping_add(1)
+            ));
+        }
+        for (index, saved) in frame.caller.iter().enumerate() {
+            let pointer = LCL as usize + index;
+            if cpu.ram[pointer] != *saved {
+                return Err(format!(
+                    "The return restored {} to {}, the call saved {}",
+                    POINTERS[pointer], cpu.ram[pointer], saved
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Observer for StackChecker {
+    fn after_step(&mut self, cpu: &Cpu, step: &Step) -> Result<(), String> {
+        if self.violation.is_none() {
+            if let Err(message) = self.check(cpu, step) {
+                self.violation = Some(Violation {
+                    pc: step.pc,
+                    cycle: cpu.cycles,
+                    message,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn should_stop(&self) -> bool {
+        self.violation.is_some()
+    }
+}
@@ -0,0 +1,88 @@
+use crate::cpu::Cpu;
+use crate::halt::Stop;
+use crate::stack::Violation;
+use crate::watch::Hit;
+
+// The outcome of a headless run as JSON, for CI jobs to check without parsing the
+// human readable output:
+//
+//   {
+//     "status": "halted",
+//     "cycles": 1234,
+//     "registers": {"A": 0, "D": 5, "PC": 17},
+//     "watchpoint": null,
+//     "check": null,
+//     "ram": [
+//       {"start": 0, "end": 3, "values": [6, 7, 42]}
+//     ]
+//   }
+//
+// The status is halted, timeout when the cycle limit was reached, watchpoint, or check
+// when a stack check failed. Values are signed like in the dumps, RAM ranges leave out
+// their end.
+
+// How a run ended, in the JSON and in the text report alike.
+pub fn status(stop: Stop, check_failed: bool) -> &'static str {
+    match stop {
+        Stop::Halted => "halted",
+        Stop::CycleLimit => "timeout",
+        Stop::Requested if check_failed => "check",
+        Stop::Requested => "watchpoint",
+    }
+}
+
+pub fn to_json(
+    stop: Stop,
+    cpu: &Cpu,
+    hit: Option<&Hit>,
+    violation: Option<&Violation>,
+    ranges: &[(u16, u16)],
+) -> String {
+    let status = status(stop, violation.is_some());
+    let watchpoint = match hit {
+        Some(hit) => json_string(&hit.to_string()),
+        None => "null".to_string(),
+    };
+    let check = match violation {
+        Some(violation) => json_string(&violation.to_string()),
+        None => "null".to_string(),
+    };
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|(start, end)| {
+            let values: Vec<String> = cpu.ram[*start as usize..(*end).max(*start) as usize]
+                .iter()
+                .map(|value| (*value as i16).to_string())
+                .collect();
+            format!(
+                "    {{\"start\": {}, \"end\": {}, \"values\": [{}]}}",
+                start,
+                end,
+                values.join(", ")
+            )
+        })
+        .collect();
+    let ram = if ranges.is_empty() {
+        "[]".to_string()
+    } else {
+        format!("[\n{}\n  ]", ranges.join(",\n"))
+    };
+    format!(
+        "{{\n  \"status\": \"{}\",\n  \"cycles\": {},\n  \"registers\": {{\"A\": {}, \"D\": {}, \"PC\": {}}},\n  \"watchpoint\": {},\n  \"check\": {},\n  \"ram\": {}\n}}\n",
+        status, cpu.cycles, cpu.a as i16, cpu.d as i16, cpu.pc, watchpoint, check, ram
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,472 @@
+use std::io::Write;
+use std::time::Duration;
+
+use crate::console::Console;
+use crate::cpu::Cpu;
+use crate::replay::{Recorder, Replay};
+use crate::screen::{pixel, HEIGHT, WIDTH};
+
+#[cfg(unix)]
+use {
+    crate::cpu::KBD,
+    crate::halt::HaltDetector,
+    crate::snapshot,
+    std::ffi::c_int,
+    std::fs::File,
+    std::io::Read,
+    std::process::{Command, Stdio},
+    std::sync::atomic::{AtomicBool, Ordering},
+    std::time::Instant,
+};
+
+// A live terminal front end: draws the Hack screen with Unicode braille or half-block
+// characters and forwards pressed keys to the KBD register.
+// The terminal is switched to raw mode with stty, so running live needs a Unix terminal.
+// Rendering and decoding keys work everywhere.
+
+// Hack key codes of the keys that are not printable characters.
+pub const NEWLINE: u16 = 128;
+pub const BACKSPACE: u16 = 129;
+pub const LEFT: u16 = 130;
+pub const UP: u16 = 131;
+pub const RIGHT: u16 = 132;
+pub const DOWN: u16 = 133;
+pub const HOME: u16 = 134;
+pub const END: u16 = 135;
+pub const PAGE_UP: u16 = 136;
+pub const PAGE_DOWN: u16 = 137;
+pub const INSERT: u16 = 138;
+pub const DELETE: u16 = 139;
+pub const ESCAPE: u16 = 140;
+// F1 is 141 and F12 is 152.
+pub const F1: u16 = 141;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    // 2x4 pixels per character.
+    Braille,
+    // 1x2 pixels per character.
+    HalfBlocks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Key(u16),
+    // Ctrl-C, which raw mode no longer turns into a signal.
+    Quit,
+    // Ctrl-S saves a snapshot.
+    Snapshot,
+}
+
+pub struct Options {
+    pub style: Style,
+    // Terminals only report key presses, so a key stays down this long after the last one.
+    // Holding a key down keeps it pressed through the terminal's auto-repeat.
+    pub key_hold: Duration,
+    // Instructions per second, as fast as possible when None.
+    pub speed: Option<u64>,
+    // Stops after this many instructions in total.
+    pub max_cycles: Option<u64>,
+    // Keys come from the replay instead of the keyboard.
+    pub replay: Option<Replay>,
+    // Records the keys that were pressed.
+    pub recorder: Option<Recorder>,
+    // Where Ctrl-S saves snapshots.
+    pub snapshot: Option<String>,
+    // The debug console, writing to a file since the screen takes the terminal.
+    pub console: Option<Console<Box<dyn Write>>>,
+}
+
+// Decodes the bytes a terminal sends for key presses into Hack key codes.
+// Unknown escape sequences are dropped.
+pub fn decode_keys(bytes: &[u8]) -> Vec<Input> {
+    let mut inputs = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        index += 1;
+        let key = match byte {
+            0x03 => {
+                inputs.push(Input::Quit);
+                continue;
+            }
+            0x13 => {
+                inputs.push(Input::Snapshot);
+                continue;
+            }
+            b'\r' | b'\n' => NEWLINE,
+            0x08 | 0x7F => BACKSPACE,
+            0x1B => {
+                let (key, length) = decode_escape(&bytes[index..]);
+                index += length;
+                match key {
+                    Some(key) => key,
+                    None => continue,
+                }
+            }
+            32..=126 => byte as u16,
+            _ => continue,
+        };
+        inputs.push(Input::Key(key));
+    }
+    inputs
+}
+
+// Decodes what follows an ESC byte, returning the key and the number of bytes used.
+// A lone ESC is the escape key itself.
+fn decode_escape(bytes: &[u8]) -> (Option<u16>, usize) {
+    match bytes.first() {
+        Some(b'O') => {
+            // SS3 sequences: F1-F4, and arrows/home/end in application cursor mode.
+            let key = bytes.get(1).and_then(|&byte| final_key(byte));
+            (key, bytes.len().min(2))
+        }
+        Some(b'[') => {
+            // CSI sequences: parameters followed by a final byte.
+            let Some(length) = bytes[1..]
+                .iter()
+                .position(|byte| (0x40..=0x7E).contains(byte))
+            else {
+                return (None, bytes.len());
+            };
+            let parameters = &bytes[1..1 + length];
+            let final_byte = bytes[1 + length];
+            let key = if final_byte == b'~' {
+                let number = std::str::from_utf8(parameters)
+                    .ok()
+                    .and_then(|text| text.split(';').next())
+                    .and_then(|text| text.parse::<u16>().ok());
+                number.and_then(tilde_key)
+            } else {
+                final_key(final_byte)
+            };
+            (key, length + 2)
+        }
+        _ => (Some(ESCAPE), 0),
+    }
+}
+
+fn final_key(byte: u8) -> Option<u16> {
+    match byte {
+        b'A' => Some(UP),
+        b'B' => Some(DOWN),
+        b'C' => Some(RIGHT),
+        b'D' => Some(LEFT),
+        b'H' => Some(HOME),
+        b'F' => Some(END),
+        b'P'..=b'S' => Some(F1 + (byte - b'P') as u16),
+        _ => None,
+    }
+}
+
+// The `ESC [ n ~` sequences of the editing and function keys.
+fn tilde_key(number: u16) -> Option<u16> {
+    match number {
+        1 | 7 => Some(HOME),
+        2 => Some(INSERT),
+        3 => Some(DELETE),
+        4 | 8 => Some(END),
+        5 => Some(PAGE_UP),
+        6 => Some(PAGE_DOWN),
+        11..=15 => Some(F1 + number - 11),
+        17..=21 => Some(F1 + 5 + number - 17),
+        23 | 24 => Some(F1 + 10 + number - 23),
+        _ => None,
+    }
+}
+
+// Draws the screen in at most `columns` x `rows` characters. The screen is scaled down
+// by a whole factor when it does not fit, and a character cell is lit when any of the
+// pixels it covers is black, so thin lines do not disappear.
+pub fn render(screen: &[u16], style: Style, columns: usize, rows: usize) -> Vec<String> {
+    let (cell_width, cell_height) = match style {
+        Style::Braille => (2, 4),
+        Style::HalfBlocks => (1, 2),
+    };
+    let scale = WIDTH
+        .div_ceil(cell_width * columns.max(1))
+        .max(HEIGHT.div_ceil(cell_height * rows.max(1)))
+        .max(1);
+    let lit = |x: usize, y: usize| {
+        (y * scale..((y + 1) * scale).min(HEIGHT))
+            .any(|row| (x * scale..((x + 1) * scale).min(WIDTH)).any(|c| pixel(screen, row, c)))
+    };
+    let width = WIDTH.div_ceil(scale);
+    let height = HEIGHT.div_ceil(scale);
+
+    let mut lines = Vec::new();
+    for y in (0..height).step_by(cell_height) {
+        let mut line = String::new();
+        for x in (0..width).step_by(cell_width) {
+            let dot =
+                |dx: usize, dy: usize| x + dx < width && y + dy < height && lit(x + dx, y + dy);
+            let character = match style {
+                Style::Braille => {
+                    // Dot numbering of the braille block: 1-3 and 7 on the left, 4-6 and 8 on the right.
+                    const BITS: [[u32; 4]; 2] =
+                        [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+                    let mut bits = 0;
+                    for (dx, column) in BITS.iter().enumerate() {
+                        for (dy, bit) in column.iter().enumerate() {
+                            if dot(dx, dy) {
+                                bits |= bit;
+                            }
+                        }
+                    }
+                    char::from_u32(0x2800 + bits).unwrap()
+                }
+                Style::HalfBlocks => match (dot(0, 0), dot(0, 1)) {
+                    (false, false) => ' ',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (true, true) => '█',
+                },
+            };
+            line.push(character);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// Puts the controlling terminal in raw mode and restores it when dropped.
+#[cfg(unix)]
+struct RawTerminal {
+    tty: File,
+    saved: String,
+    // Rows and columns, asked again only after the terminal was resized.
+    size: (usize, usize),
+    // The SIGWINCH handler from before, put back when dropped.
+    previous_handler: usize,
+}
+
+// The signal terminals send when they are resized, the same number on Linux and the BSDs.
+#[cfg(unix)]
+const SIGWINCH: c_int = 28;
+
+// Set by the SIGWINCH handler, cleared when the new size was read.
+#[cfg(unix)]
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" {
+    fn signal(signal: c_int, handler: usize) -> usize;
+}
+
+#[cfg(unix)]
+extern "C" fn on_resize(_signal: c_int) {
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+impl RawTerminal {
+    fn enter() -> Result<Self, String> {
+        let tty = File::options()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .map_err(|e| format!("Failed to open the terminal: {}", e))?;
+        let saved = stty(&tty, &["-g"])?;
+        // Reads return at once, with whatever bytes are available.
+        stty(&tty, &["raw", "-echo", "min", "0", "time", "0"])?;
+        let size = query_size(&tty);
+        // Only stores to an atomic, which is safe in a signal handler.
+        let handler = on_resize as extern "C" fn(c_int) as usize;
+        let previous_handler = unsafe { signal(SIGWINCH, handler) };
+        let mut terminal = RawTerminal {
+            tty,
+            saved: saved.trim().to_string(),
+            size,
+            previous_handler,
+        };
+        // Alternate screen, hidden cursor.
+        terminal.write("\x1b[?1049h\x1b[?25l\x1b[2J");
+        Ok(terminal)
+    }
+
+    // Rows and columns of the terminal.
+    fn size(&mut self) -> (usize, usize) {
+        if RESIZED.swap(false, Ordering::Relaxed) {
+            self.size = query_size(&self.tty);
+        }
+        self.size
+    }
+
+    fn read_available(&mut self) -> Vec<u8> {
+        let mut buffer = [0u8; 256];
+        match self.tty.read(&mut buffer) {
+            Ok(length) => buffer[..length].to_vec(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        let _ = self.tty.write_all(text.as_bytes());
+        let _ = self.tty.flush();
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        self.write("\x1b[?25h\x1b[?1049l");
+        let saved = self.saved.clone();
+        let _ = stty(&self.tty, &[saved.as_str()]);
+        unsafe { signal(SIGWINCH, self.previous_handler) };
+    }
+}
+
+// Runs stty, so only when starting and after a resize, not for every frame.
+#[cfg(unix)]
+fn query_size(tty: &File) -> (usize, usize) {
+    stty(tty, &["size"])
+        .ok()
+        .and_then(|size| {
+            let (rows, columns) = size.trim().split_once(' ')?;
+            Some((rows.parse().ok()?, columns.parse().ok()?))
+        })
+        .unwrap_or((24, 80))
+}
+
+#[cfg(unix)]
+fn stty(tty: &File, args: &[&str]) -> Result<String, String> {
+    let stdin = tty
+        .try_clone()
+        .map_err(|e| format!("Failed to use the terminal: {}", e))?;
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(stdin))
+        .output()
+        .map_err(|e| format!("Failed to run stty: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "stty failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(unix)]
+const FRAME: Duration = Duration::from_millis(33);
+
+// Runs the program in the terminal until Ctrl-C is pressed or the cycle limit is reached.
+// A halted program stays on screen until Ctrl-C.
+#[cfg(unix)]
+pub fn run(cpu: &mut Cpu, options: &mut Options) -> Result<(), String> {
+    let mut terminal = RawTerminal::enter()?;
+    let started = Instant::now();
+    let first_cycle = cpu.cycles;
+    let mut released_at: Option<Instant> = None;
+    let mut halted = false;
+    let mut halt = HaltDetector::new();
+    let mut message = String::new();
+    let mut last_frame: Vec<String> = Vec::new();
+
+    loop {
+        let frame_start = Instant::now();
+
+        for input in decode_keys(&terminal.read_available()) {
+            match input {
+                Input::Quit => return Ok(()),
+                Input::Snapshot => {
+                    message = match &options.snapshot {
+                        Some(path) => {
+                            let position = options.replay.as_ref().map_or(0, |r| r.position);
+                            match snapshot::save(path, cpu, position) {
+                                Ok(()) => format!("saved {} at cycle {}", path, cpu.cycles),
+                                Err(e) => e,
+                            }
+                        }
+                        None => "no --snapshot file given".to_string(),
+                    };
+                }
+                // A replay owns the keyboard.
+                Input::Key(_) if options.replay.is_some() => {}
+                Input::Key(key) => {
+                    set_key(cpu, options, key);
+                    released_at = Some(frame_start + options.key_hold);
+                }
+            }
+        }
+        if released_at.is_some_and(|time| frame_start >= time) {
+            set_key(cpu, options, 0);
+            released_at = None;
+        }
+
+        let limit_reached = options.max_cycles.is_some_and(|max| cpu.cycles >= max);
+        if !halted && !limit_reached {
+            // Either the share of instructions up to now, or as many as fit in a frame.
+            let budget = match options.speed {
+                Some(speed) => {
+                    let due = (started.elapsed().as_secs_f64() * speed as f64) as u64;
+                    (first_cycle + due).saturating_sub(cpu.cycles)
+                }
+                None => u64::MAX,
+            };
+            let budget = options
+                .max_cycles
+                .map_or(budget, |max| budget.min(max - cpu.cycles));
+            let mut executed = 0;
+            while executed < budget {
+                if let Some(replay) = options.replay.as_mut() {
+                    replay.apply(cpu);
+                }
+                let step = cpu.step();
+                executed += 1;
+                if let (Some(console), Some(write)) = (options.console.as_mut(), step.write) {
+                    console.write(write.address, write.new)?;
+                }
+                if halt.is_halted(cpu, &step) {
+                    halted = true;
+                    break;
+                }
+                if executed.is_multiple_of(4096) && frame_start.elapsed() >= FRAME {
+                    break;
+                }
+            }
+        }
+
+        let (rows, columns) = terminal.size();
+        let mut frame = render(cpu.screen(), options.style, columns, rows.saturating_sub(1));
+        let state = if halted {
+            "halted"
+        } else if limit_reached {
+            "cycle limit reached"
+        } else {
+            "running"
+        };
+        frame.push(format!(
+            "{}  cycles: {}  PC: {}  KBD: {}  (Ctrl-C quits, Ctrl-S saves a snapshot)  {}",
+            state, cpu.cycles, cpu.pc, cpu.ram[KBD as usize], message
+        ));
+        if frame != last_frame {
+            let mut text = String::from("\x1b[H");
+            for line in &frame {
+                text.push_str(line);
+                text.push_str("\x1b[K\r\n");
+            }
+            text.truncate(text.len() - 2);
+            text.push_str("\x1b[J");
+            terminal.write(&text);
+            last_frame = frame;
+        }
+
+        if let Some(rest) = FRAME.checked_sub(frame_start.elapsed()) {
+            std::thread::sleep(rest);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn run(_cpu: &mut Cpu, _options: &mut Options) -> Result<(), String> {
+    Err("--terminal needs a Unix terminal".to_string())
+}
+
+#[cfg(unix)]
+fn set_key(cpu: &mut Cpu, options: &mut Options, key: u16) {
+    cpu.set_key(key);
+    if let Some(recorder) = options.recorder.as_mut() {
+        recorder.record(cpu, key);
+    }
+}
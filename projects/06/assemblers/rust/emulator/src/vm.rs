@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+use assembler::symbol_table::SymbolTable;
+
+// Conventions of the VM translator's output that the tools rely on:
+//
+//   (File.function)          starts the code of a VM function
+//   (RET_File.function.n)    follows the jump of a call, the callee returns there
+//
+// Labels of VM `label` commands have no dot, and the labels the translator makes up for
+// comparisons end in a number (END_LT.3), so neither looks like a function.
+
+pub fn is_return_label(label: &str) -> bool {
+    label.starts_with("RET_")
+}
+
+pub fn is_function_label(label: &str) -> bool {
+    let Some((_, name)) = label.rsplit_once('.') else {
+        return false;
+    };
+    !is_return_label(label)
+        && !label.contains('$')
+        && !name.is_empty()
+        && !name.chars().all(|c| c.is_ascii_digit())
+}
+
+// The ROM addresses calls return to.
+pub fn return_addresses(symbols: &SymbolTable) -> BTreeSet<u16> {
+    symbols
+        .labels()
+        .into_iter()
+        .filter(|(label, _)| is_return_label(label))
+        .map(|(_, address)| address)
+        .collect()
+}
+
+// The return address when `instruction` at `address` is the jump of a call:
+// an unconditional jump followed by a return label.
+pub fn call_return_address(instruction: u16, address: u16, returns: &BTreeSet<u16>) -> Option<u16> {
+    let unconditional_jump = instruction & 0x8000 != 0 && instruction & 0x7 == 0x7;
+    let return_address = address.checked_add(1)?;
+    (unconditional_jump && returns.contains(&return_address)).then_some(return_address)
+}
+
+// The VM functions of a program, by address.
+pub struct Functions {
+    starts: Vec<(u16, String)>,
+}
+
+impl Functions {
+    pub fn new(symbols: &SymbolTable) -> Self {
+        let starts = symbols
+            .labels()
+            .into_iter()
+            .filter(|(label, _)| is_function_label(label))
+            .map(|(label, address)| (address, label.to_string()))
+            .collect();
+        Functions { starts }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    // The function starting at the address.
+    pub fn starting_at(&self, address: u16) -> Option<&str> {
+        let index = self
+            .starts
+            .binary_search_by_key(&address, |(start, _)| *start)
+            .ok()?;
+        Some(self.starts[index].1.as_str())
+    }
+
+    // The function whose code holds the address, None before the first function.
+    pub fn containing(&self, address: u16) -> Option<&str> {
+        let index = self.starts.partition_point(|(start, _)| *start <= address);
+        index
+            .checked_sub(1)
+            .map(|index| self.starts[index].1.as_str())
+    }
+}
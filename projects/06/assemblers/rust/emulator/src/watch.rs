@@ -0,0 +1,199 @@
+use std::fmt;
+use std::io::Write;
+
+use crate::cpu::{Cpu, Step};
+use crate::halt::Stop;
+use crate::loader::Program;
+use crate::run::Observer;
+
+// Watchpoints on RAM addresses and a trace of every memory access.
+//
+//   write:R13..R15     any write to R13 or R14 (ranges leave out their end, like --dump)
+//   change:THAT        a write that changes THAT
+//   read:SCREEN..KBD   any read of the screen memory map
+//
+// A spec without an access kind watches writes.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    // A write storing a value different from the old one.
+    Change,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub access: Access,
+    pub start: u16,
+    pub end: u16,
+}
+
+// A memory access that triggered a watchpoint. Reads have the same old and new value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    pub pc: u16,
+    pub access: Access,
+    pub address: u16,
+    pub old: u16,
+    pub new: u16,
+}
+
+impl Watchpoint {
+    pub fn parse(spec: &str, program: &Program) -> Result<Self, String> {
+        let (access, range) = match spec.split_once(':') {
+            Some(("read", range)) => (Access::Read, range),
+            Some(("write", range)) => (Access::Write, range),
+            Some(("change", range)) => (Access::Change, range),
+            Some((kind, _)) => {
+                return Err(format!(
+                    "Unknown access '{}', expected read, write or change",
+                    kind
+                ))
+            }
+            None => (Access::Write, spec),
+        };
+        let (start, end) = match range.split_once("..") {
+            Some((start, end)) => (program.ram_address(start)?, program.ram_address(end)?),
+            None => {
+                let address = program.ram_address(range)?;
+                (address, address + 1)
+            }
+        };
+        if end <= start {
+            return Err(format!("Empty range in '{}'", spec));
+        }
+        Ok(Watchpoint { access, start, end })
+    }
+
+    // The access of the step that triggers this watchpoint, if any.
+    pub fn check(&self, step: &Step, cpu: &Cpu) -> Option<Hit> {
+        let watched = |address: u16| (self.start..self.end).contains(&address);
+        match self.access {
+            Access::Read => {
+                let address = step.read.filter(|address| watched(*address))?;
+                let value = read_value(step, cpu)?;
+                Some(Hit {
+                    pc: step.pc,
+                    access: Access::Read,
+                    address,
+                    old: value,
+                    new: value,
+                })
+            }
+            Access::Write | Access::Change => {
+                let write = step.write.filter(|write| watched(write.address))?;
+                if self.access == Access::Change && write.old == write.new {
+                    return None;
+                }
+                Some(Hit {
+                    pc: step.pc,
+                    access: self.access,
+                    address: write.address,
+                    old: write.old,
+                    new: write.new,
+                })
+            }
+        }
+    }
+}
+
+// The value the step read through M. A write by the same instruction happens after the read.
+pub fn read_value(step: &Step, cpu: &Cpu) -> Option<u16> {
+    let address = step.read?;
+    match step.write {
+        Some(write) if write.address == address => Some(write.old),
+        _ => Some(cpu.ram[address as usize]),
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match self.access {
+            Access::Read => "read",
+            Access::Write => "write",
+            Access::Change => "change",
+        };
+        if self.end == self.start + 1 {
+            write!(f, "{} RAM[{}]", access, self.start)
+        } else {
+            write!(f, "{} RAM[{}..{}]", access, self.start, self.end)
+        }
+    }
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.access {
+            Access::Read => write!(
+                f,
+                "ROM[{}] read RAM[{}] = {}",
+                self.pc, self.address, self.old as i16
+            ),
+            Access::Write | Access::Change => write!(
+                f,
+                "ROM[{}] wrote RAM[{}]: {} -> {}",
+                self.pc, self.address, self.old as i16, self.new as i16
+            ),
+        }
+    }
+}
+
+// Stops a headless run at the first watchpoint hit.
+pub struct Watcher {
+    pub watchpoints: Vec<Watchpoint>,
+    pub hit: Option<Hit>,
+}
+
+impl Observer for Watcher {
+    fn after_step(&mut self, cpu: &Cpu, step: &Step) -> Result<(), String> {
+        if self.hit.is_none() {
+            self.hit = self
+                .watchpoints
+                .iter()
+                .find_map(|watchpoint| watchpoint.check(step, cpu));
+        }
+        Ok(())
+    }
+
+    fn should_stop(&self) -> bool {
+        self.hit.is_some()
+    }
+}
+
+// Writes one line per memory access:
+//
+//   1234 ROM[56] read RAM[0] 261
+//   1235 ROM[57] write RAM[261] 0 -> 17
+pub struct AccessTrace<W: Write> {
+    pub output: W,
+}
+
+impl<W: Write> Observer for AccessTrace<W> {
+    fn after_step(&mut self, cpu: &Cpu, step: &Step) -> Result<(), String> {
+        let error = |e: std::io::Error| format!("Failed to write the trace: {}", e);
+        if let (Some(address), Some(value)) = (step.read, read_value(step, cpu)) {
+            writeln!(
+                self.output,
+                "{} ROM[{}] read RAM[{}] {}",
+                cpu.cycles, step.pc, address, value as i16
+            )
+            .map_err(error)?;
+        }
+        if let Some(write) = step.write {
+            writeln!(
+                self.output,
+                "{} ROM[{}] write RAM[{}] {} -> {}",
+                cpu.cycles, step.pc, write.address, write.old as i16, write.new as i16
+            )
+            .map_err(error)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _cpu: &Cpu, _stop: Stop) -> Result<(), String> {
+        self.output
+            .flush()
+            .map_err(|e| format!("Failed to write the trace: {}", e))
+    }
+}
@@ -0,0 +1,117 @@
+use emulator::backtrace::{self, Frame};
+use emulator::cpu::Cpu;
+use emulator::debugger::{Debugger, Reply};
+use emulator::halt::Stop;
+use emulator::loader::{self, Program};
+use emulator::run;
+
+// Sys.init calls Main.twice, which parks in a loop with its frame on the stack.
+const CALLS: &str = "
+function Sys.init 0
+push constant 3
+call Main.twice 1
+label END
+goto END
+function Main.twice 2
+push constant 7
+pop local 1
+push argument 0
+label PARK
+goto PARK
+";
+
+fn program() -> Program {
+    let files = [("Sys.vm".to_string(), CALLS.to_string())];
+    let source = vm_translator::translate("Test", &files).unwrap();
+    loader::assemble_source(&source, &Default::default()).unwrap()
+}
+
+fn parked(program: &Program) -> Cpu {
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    assert_eq!(run::run(&mut cpu, 10_000, &mut []).unwrap(), Stop::Halted);
+    cpu
+}
+
+#[test]
+fn walks_the_saved_frames() {
+    let program = program();
+    let cpu = parked(&program);
+    let frames = backtrace::backtrace(&cpu, &program);
+    let return_address = program
+        .symbols
+        .get_address("RET_Test.Main.twice.2")
+        .unwrap();
+    assert_eq!(
+        frames,
+        [
+            Frame {
+                function: Some("Test.Main.twice".to_string()),
+                pc: cpu.pc,
+                return_label: None,
+                arg: 261,
+                lcl: 267,
+                args: vec![3],
+                locals: Some(vec![0, 7]),
+                stack: vec![3],
+            },
+            Frame {
+                function: Some("Test.Sys.init".to_string()),
+                pc: return_address,
+                return_label: Some("RET_Test.Main.twice.2".to_string()),
+                arg: 256,
+                lcl: 261,
+                args: vec![],
+                locals: Some(vec![]),
+                stack: vec![],
+            },
+        ]
+    );
+    assert_eq!(
+        backtrace::format(&frames, &program),
+        format!(
+            "#0  Test.Main.twice  ROM[{}]  Sys.vm:12  goto PARK\n\
+             \x20   args (ARG 261): 3\n\
+             \x20   locals (LCL 267): 0 7\n\
+             \x20   stack: 3\n\
+             #1  Test.Sys.init  ROM[{}] (RET_Test.Main.twice.2)  Sys.vm:4  call Main.twice 1\n\
+             \x20   args (ARG 256): none\n\
+             \x20   locals (LCL 261): none\n\
+             \x20   stack: none\n",
+            cpu.pc, return_address
+        )
+    );
+}
+
+#[test]
+fn needs_vm_functions() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../04/mult/Mult.asm");
+    let program = loader::load_file(path).unwrap();
+    let cpu = parked(&program);
+    let frames = backtrace::backtrace(&cpu, &program);
+    assert_eq!(
+        backtrace::format(&frames, &program),
+        "No VM functions in this program\n"
+    );
+}
+
+#[test]
+fn shows_in_the_debugger() {
+    let program = program();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut debugger = Debugger::new(cpu, program);
+    let mut run = |command: &str| match debugger.execute(command) {
+        Ok(Reply::Text(text)) => text,
+        _ => panic!("{} failed", command),
+    };
+    run("break Sys.vm:12");
+    run("continue");
+    let backtrace = run("bt");
+    assert!(
+        backtrace.starts_with("#0  Test.Main.twice  ROM["),
+        "{}",
+        backtrace
+    );
+    assert!(backtrace.ends_with("    stack: none"), "{}", backtrace);
+}
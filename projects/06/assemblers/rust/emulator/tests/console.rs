@@ -0,0 +1,89 @@
+use emulator::console::{Console, CONSOLE};
+use emulator::cpu::Cpu;
+use emulator::loader;
+use emulator::run;
+
+// Prints "R0 = " and R0 on a line, then every value of R0 as it counts down to 0.
+// The program writes to CONSOLE and the word after it, wherever they are placed.
+
+const PROGRAM: &str = "\
+@82
+D=A
+@CONSOLE
+M=D
+@48
+D=A
+@CONSOLE
+M=D
+@32
+D=A
+@CONSOLE
+M=D
+@61
+D=A
+@CONSOLE
+M=D
+@32
+D=A
+@CONSOLE
+M=D
+@R0
+D=M
+@NUMBER
+M=D
+@128
+D=A
+@CONSOLE
+M=D
+(LOOP)
+@R0
+MD=M-1
+@NUMBER
+M=D
+@LOOP
+D;JGT
+(END)
+@END
+0;JMP
+";
+
+fn run_with_console(address: u16) -> (Cpu, String) {
+    let source = PROGRAM
+        .replace("@CONSOLE", &format!("@{}", address))
+        .replace("@NUMBER", &format!("@{}", address + 1));
+    let program = loader::assemble_source(&source, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu.ram[0] = 3;
+    let mut console = Console {
+        address: CONSOLE,
+        output: Vec::new(),
+    };
+    run::run(&mut cpu, 1_000, &mut [&mut console]).unwrap();
+    (cpu, String::from_utf8(console.output).unwrap())
+}
+
+#[test]
+fn prints_characters_and_numbers() {
+    let (_, output) = run_with_console(CONSOLE);
+    assert_eq!(output, "R0 = 3\n210");
+}
+
+#[test]
+fn leaves_other_addresses_alone() {
+    let (cpu, output) = run_with_console(100);
+    assert_eq!(output, "");
+    assert_eq!((cpu.ram[100], cpu.ram[101]), (128, 0));
+}
+
+#[test]
+fn shows_unprintable_codes() {
+    let mut console = Console {
+        address: 16,
+        output: Vec::new(),
+    };
+    for (address, value) in [(16, 130), (17, 0xffff), (16, 10), (18, 7)] {
+        console.write(address, value).unwrap();
+    }
+    assert_eq!(String::from_utf8(console.output).unwrap(), "<130>-1\n");
+}
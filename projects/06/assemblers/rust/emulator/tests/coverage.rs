@@ -0,0 +1,84 @@
+use emulator::coverage::Coverage;
+use emulator::cpu::Cpu;
+use emulator::loader;
+use emulator::run;
+
+// Counts down R0 from 2: the loop jump goes both ways, the check of R1 only one.
+
+const PROGRAM: &str = "\
+@2
+D=A
+@R0
+M=D
+(LOOP)
+@R0
+MD=M-1
+@LOOP
+D;JGT
+@R1
+D=M
+@NEGATIVE
+D;JLT
+(END)
+@END
+0;JMP
+(NEGATIVE)
+@R2
+M=1
+";
+
+fn cover() -> Coverage {
+    let program = loader::assemble_source(PROGRAM, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut coverage = Coverage::new(&program);
+    run::run(&mut cpu, 1_000, &mut [&mut coverage]).unwrap();
+    coverage
+}
+
+#[test]
+fn counts_instructions_and_jumps() {
+    let coverage = cover();
+    assert_eq!(coverage.executed(4), 2);
+    assert_eq!(coverage.jumps(7), (1, 1));
+    assert_eq!(coverage.jumps(11), (0, 1));
+    assert_eq!(coverage.executed(14), 0);
+}
+
+#[test]
+fn annotates_the_source() {
+    let listing = cover().listing();
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(
+        lines[4..9],
+        [
+            "        -:    5:(LOOP)",
+            "        2:    6:@R0",
+            "        2:    7:MD=M-1",
+            "        2:    8:@LOOP",
+            "        2:    9:D;JGT",
+        ]
+    );
+    assert_eq!(lines[12], "        1:   13:D;JLT  !! jump never taken");
+    assert_eq!(lines[18], "    #####:   19:M=1");
+}
+
+#[test]
+fn reports_labels_and_untested_paths() {
+    let report = cover().report();
+    for line in [
+        "Coverage: 14 of 16 instructions executed (87.5%), 1 of 2 conditional jumps went both ways",
+        "          4/4 100.0%       0/0  (start)",
+        "          8/8 100.0%       1/2  LOOP",
+        "          0/2   0.0%       0/0  NEGATIVE",
+        "  ROM[14..16]      NEGATIVE",
+        "  ROM[11]          LOOP                     D;JLT        jump never taken",
+    ] {
+        assert!(
+            report.lines().any(|l| l == line),
+            "no '{}' in\n{}",
+            line,
+            report
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use assembler::code::comp_to_bin;
+use emulator::cpu::{alu, Cpu};
+use emulator::loader;
+
+// Checks the emulator against the Hack semantics of every comp mnemonic the assembler
+// knows, and runs a few of the course programs.
+
+type Semantics = fn(i16, i16, i16) -> i16;
+
+const COMPS: [(&str, Semantics); 28] = [
+    ("0", |_, _, _| 0),
+    ("1", |_, _, _| 1),
+    ("-1", |_, _, _| -1),
+    ("D", |_, d, _| d),
+    ("A", |a, _, _| a),
+    ("M", |_, _, m| m),
+    ("!D", |_, d, _| !d),
+    ("!A", |a, _, _| !a),
+    ("!M", |_, _, m| !m),
+    ("-D", |_, d, _| d.wrapping_neg()),
+    ("-A", |a, _, _| a.wrapping_neg()),
+    ("-M", |_, _, m| m.wrapping_neg()),
+    ("D+1", |_, d, _| d.wrapping_add(1)),
+    ("A+1", |a, _, _| a.wrapping_add(1)),
+    ("M+1", |_, _, m| m.wrapping_add(1)),
+    ("D-1", |_, d, _| d.wrapping_sub(1)),
+    ("A-1", |a, _, _| a.wrapping_sub(1)),
+    ("M-1", |_, _, m| m.wrapping_sub(1)),
+    ("D+A", |a, d, _| d.wrapping_add(a)),
+    ("D+M", |_, d, m| d.wrapping_add(m)),
+    ("D-A", |a, d, _| d.wrapping_sub(a)),
+    ("D-M", |_, d, m| d.wrapping_sub(m)),
+    ("A-D", |a, d, _| a.wrapping_sub(d)),
+    ("M-D", |_, d, m| m.wrapping_sub(d)),
+    ("D&A", |a, d, _| d & a),
+    ("D&M", |_, d, m| d & m),
+    ("D|A", |a, d, _| d | a),
+    ("D|M", |_, d, m| d | m),
+];
+
+#[test]
+fn alu_matches_every_comp_mnemonic() {
+    let samples: [i16; 7] = [0, 1, -1, 17, -300, i16::MAX, i16::MIN];
+    for (mnemonic, semantics) in COMPS {
+        let comp = u16::from_str_radix(comp_to_bin(mnemonic), 2).unwrap();
+        for a in samples {
+            for d in samples {
+                for m in samples {
+                    let expected = semantics(a, d, m) as u16;
+                    let actual = alu(comp, a as u16, d as u16, m as u16);
+                    assert_eq!(
+                        actual, expected,
+                        "{} with A={} D={} M={}",
+                        mnemonic, a, d, m
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn writes_m_and_jumps_with_the_previous_a() {
+    // AM=M+1 both reads and writes RAM[A] with the old A, A=D;JMP jumps to the old A.
+    let program =
+        loader::assemble_source("@100\nAM=M+1\n@7\nD=A\n@2\nA=D;JMP\n", &Default::default())
+            .unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu.ram[100] = 41;
+    cpu.run(2);
+    assert_eq!(cpu.ram[100], 42);
+    assert_eq!(cpu.a, 42);
+    cpu.run(4);
+    assert_eq!(cpu.a, 7);
+    assert_eq!(cpu.pc, 2);
+}
+
+#[test]
+fn runs_mult() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../04/mult/Mult.asm");
+    let program = loader::load_file(path).unwrap();
+    for (x, y) in [(0, 0), (1, 0), (3, 1), (6, 7), (2, 4)] {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&program.words).unwrap();
+        cpu.ram[0] = x;
+        cpu.ram[1] = y;
+        cpu.ram[2] = 0xFFFF;
+        cpu.run(1_000);
+        assert_eq!(cpu.ram[2], x * y, "{} * {}", x, y);
+    }
+}
+
+#[test]
+fn runs_add_hack() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../06/add/Add.hack");
+    let program = loader::load_file(path).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu.run(6);
+    assert_eq!(cpu.ram[0], 5);
+}
@@ -0,0 +1,88 @@
+use emulator::cpu::Cpu;
+use emulator::cycle_trace::{self, CycleTrace, Record};
+use emulator::halt::Stop;
+use emulator::loader;
+use emulator::run;
+
+// Stores 5 in R0, then halts.
+const STORE: &str = "@5\nD=A\n@R0\nM=D\n(END)\n@END\n0;JMP\n";
+
+fn trace() -> Vec<u8> {
+    let program = loader::assemble_source(STORE, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut trace = CycleTrace::new(Vec::new(), cpu.cycles).unwrap();
+    assert_eq!(
+        run::run(&mut cpu, 100, &mut [&mut trace]).unwrap(),
+        Stop::Halted
+    );
+    trace.output
+}
+
+#[test]
+fn records_every_instruction() {
+    let bytes = trace();
+    let records = cycle_trace::decode(&bytes).unwrap();
+    assert_eq!(records.len(), 6);
+    assert_eq!(
+        records[3],
+        Record {
+            cycle: 4,
+            pc: 3,
+            instruction: 0b1110001100001000,
+            a: 0,
+            d: 5,
+            write: Some((0, 5)),
+        }
+    );
+    assert_eq!(records[5].pc, 5);
+    assert!(records
+        .iter()
+        .filter(|record| record.cycle != 4)
+        .all(|record| record.write.is_none()));
+
+    assert_eq!(
+        cycle_trace::decode(&bytes[..bytes.len() - 1]),
+        Err("Cycle trace is truncated".to_string())
+    );
+    assert_eq!(
+        cycle_trace::decode(b"HACKSNAP"),
+        Err("Not a Hack cycle trace".to_string())
+    );
+}
+
+#[test]
+fn exports_csv() {
+    let records = cycle_trace::decode(&trace()).unwrap();
+    let mut csv = Vec::new();
+    cycle_trace::write_csv(&records[..4], &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "cycle,pc,instruction,code,A,D,writeM,addressM,outM\n\
+         1,0,0000000000000101,@5,5,0,0,,\n\
+         2,1,1110110000010000,D=A,5,5,0,,\n\
+         3,2,0000000000000000,@0,0,5,0,,\n\
+         4,3,1110001100001000,M=D,0,5,1,0,5\n"
+    );
+}
+
+#[test]
+fn exports_value_changes() {
+    let records = cycle_trace::decode(&trace()).unwrap();
+    let mut vcd = Vec::new();
+    cycle_trace::write_vcd(&records[..5], &mut vcd).unwrap();
+    let vcd = String::from_utf8(vcd).unwrap();
+    let (header, changes) = vcd.split_once("$enddefinitions $end\n").unwrap();
+    assert!(header.contains("$var wire 16 # A $end\n"), "{}", header);
+    assert!(header.contains("$var wire 1 % writeM $end\n"), "{}", header);
+    // Only what changed, the write lasting one cycle.
+    assert_eq!(
+        changes,
+        "#1\nb0 !\nb101 \"\nb101 #\nb0 $\n0%\nb0 &\nb0 '\n\
+         #2\nb1 !\nb1110110000010000 \"\nb101 $\n\
+         #3\nb10 !\nb0 \"\nb0 #\n\
+         #4\nb11 !\nb1110001100001000 \"\n1%\nb101 '\n\
+         #5\nb100 !\nb100 \"\nb100 #\n0%\n\
+         #6\n"
+    );
+}
@@ -0,0 +1,220 @@
+use emulator::cpu::Cpu;
+use emulator::debugger::{Debugger, Reply};
+use emulator::loader::{self, Program};
+
+// Drives the debugger through its commands, as typed at the prompt.
+
+fn debugger(program: Program) -> Debugger {
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    Debugger::new(cpu, program)
+}
+
+fn run(debugger: &mut Debugger, command: &str) -> String {
+    match debugger.execute(command) {
+        Ok(Reply::Text(text)) => text,
+        Ok(Reply::Quit) => panic!("{} quit", command),
+        Err(e) => panic!("{} failed: {}", command, e),
+    }
+}
+
+#[test]
+fn stops_at_label_breakpoints_and_reads_symbols() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../04/mult/Mult.asm");
+    let mut debugger = debugger(loader::load_file(path).unwrap());
+    run(&mut debugger, "set R0 6");
+    run(&mut debugger, "set RAM[1] 7");
+
+    assert_eq!(
+        run(&mut debugger, "break MULTIPLY"),
+        "Breakpoint at ROM[12] (MULTIPLY)"
+    );
+    let stop = run(&mut debugger, "continue");
+    assert!(
+        stop.starts_with("Breakpoint reached\nROM[12] (MULTIPLY): @END"),
+        "{}",
+        stop
+    );
+    assert_eq!(run(&mut debugger, "print R2"), "RAM[2] = 0");
+
+    run(&mut debugger, "continue");
+    assert_eq!(run(&mut debugger, "print R2"), "RAM[2] = 6");
+    assert_eq!(debugger.cpu.pc, 12);
+
+    run(&mut debugger, "delete");
+    let stop = run(&mut debugger, "c");
+    assert!(stop.starts_with("Program halted"), "{}", stop);
+    assert_eq!(
+        run(&mut debugger, "p R0 3"),
+        "RAM[0] = 6\nRAM[1] = 7\nRAM[2] = 42"
+    );
+    assert!(debugger.execute("break NOWHERE").is_err());
+}
+
+#[test]
+fn shows_disassembly_for_hack_programs() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../06/add/Add.hack");
+    let mut debugger = debugger(loader::load_file(path).unwrap());
+    assert_eq!(run(&mut debugger, "where"), "ROM[0]: @2");
+    assert_eq!(run(&mut debugger, "step 3"), "ROM[3]: D=D+A");
+    assert_eq!(run(&mut debugger, "print D"), "D = 2");
+    assert_eq!(run(&mut debugger, "set D 40"), "D = 40");
+    run(&mut debugger, "step 3");
+    assert_eq!(run(&mut debugger, "print 0"), "RAM[0] = 43");
+}
+
+// The shape of the VM translator's output: VM markers, and a call that jumps to the
+// function after pointing LCL at the new frame, returning to a RET_ label.
+const VM_PROGRAM: &str = "\
+@256
+D=A
+@SP
+M=D
+// vm Main.vm:3 call Main.twice 0
+@SP
+D=M
+@LCL
+M=D
+@Main.twice
+0;JMP
+(RET_Main.twice.0)
+// vm Main.vm:4 label END
+(END)
+@END
+0;JMP
+// vm Main.vm:6 function Main.twice 0
+(Main.twice)
+@SP
+M=M-1
+@R5
+M=M+1
+M=M+1
+@RET_Main.twice.0
+0;JMP
+";
+
+#[test]
+fn steps_over_vm_commands_and_calls() {
+    let mut debugger = debugger(loader::assemble_source(VM_PROGRAM, &Default::default()).unwrap());
+    assert_eq!(
+        run(&mut debugger, "step 4"),
+        "ROM[4]: @SP  (line 6)\n    Main.vm:3  call Main.twice 0"
+    );
+
+    // The whole call runs, up to the next VM command.
+    let stop = run(&mut debugger, "next");
+    assert_eq!(
+        stop,
+        "ROM[10] (END): @END  (line 15)\n    Main.vm:4  label END"
+    );
+    assert_eq!(run(&mut debugger, "print R5"), "RAM[5] = 2");
+
+    // Breakpoints inside the function still stop the step.
+    run(&mut debugger, "reset");
+    run(&mut debugger, "step 4");
+    run(&mut debugger, "break Main.twice");
+    let stop = run(&mut debugger, "next");
+    assert!(
+        stop.starts_with("Breakpoint reached\nROM[12] (Main.twice)"),
+        "{}",
+        stop
+    );
+}
+
+#[test]
+fn stops_at_watchpoints() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../04/mult/Mult.asm");
+    let mut debugger = debugger(loader::load_file(path).unwrap());
+    run(&mut debugger, "set R0 2");
+    run(&mut debugger, "set R1 5");
+    assert_eq!(
+        run(&mut debugger, "watch change:R2"),
+        "Watchpoint 1: change RAM[2]"
+    );
+    let stop = run(&mut debugger, "continue");
+    assert!(
+        stop.starts_with("Watchpoint: ROM[22] wrote RAM[2]: 0 -> 2\n"),
+        "{}",
+        stop
+    );
+    run(&mut debugger, "unwatch 1");
+    let stop = run(&mut debugger, "continue");
+    assert!(stop.starts_with("Program halted"), "{}", stop);
+    assert_eq!(run(&mut debugger, "print R2"), "RAM[2] = 10");
+}
+
+#[test]
+fn runs_backwards_to_the_last_change() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../04/mult/Mult.asm");
+    let mut debugger = debugger(loader::load_file(path).unwrap());
+    run(&mut debugger, "set R0 2");
+    run(&mut debugger, "set R1 5");
+    let stop = run(&mut debugger, "continue");
+    assert!(stop.starts_with("Program halted"), "{}", stop);
+    let cycles = debugger.cpu.cycles;
+
+    run(&mut debugger, "watch change:R2");
+    let stop = run(&mut debugger, "reverse-continue");
+    assert!(
+        stop.starts_with("Watchpoint: ROM[25] wrote RAM[2]: 2 -> 10\nROM[25]"),
+        "{}",
+        stop
+    );
+    assert_eq!(run(&mut debugger, "print R2"), "RAM[2] = 2");
+    // Mult.asm parks the first number in R2 before adding the sum to it.
+    let stop = run(&mut debugger, "rc");
+    assert!(
+        stop.starts_with("Watchpoint: ROM[22] wrote RAM[2]: 8 -> 2\n"),
+        "{}",
+        stop
+    );
+
+    // Going forward again redoes the same instructions.
+    run(&mut debugger, "unwatch");
+    run(&mut debugger, "back 3");
+    let (a, d) = (debugger.cpu.a, debugger.cpu.d);
+    run(&mut debugger, "step 2");
+    run(&mut debugger, "back 2");
+    assert_eq!((debugger.cpu.a, debugger.cpu.d), (a, d));
+    run(&mut debugger, "continue");
+    assert_eq!(debugger.cpu.cycles, cycles);
+    assert_eq!(run(&mut debugger, "print R2"), "RAM[2] = 10");
+
+    // Only the last instructions of the budget can be undone.
+    run(&mut debugger, "history 3");
+    let stop = run(&mut debugger, "back 5");
+    assert!(
+        stop.starts_with("Reached the oldest recorded instruction"),
+        "{}",
+        stop
+    );
+    assert_eq!(debugger.cpu.cycles, cycles - 3);
+    run(&mut debugger, "set R0 1");
+    assert_eq!(
+        run(&mut debugger, "history"),
+        "0 instructions can be undone, keeping the last 3"
+    );
+}
+
+#[test]
+fn stops_at_vm_line_breakpoints_in_translated_programs() {
+    // The markers come from the VM translator itself, not from hand written comments.
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../../../08/FunctionCalls/FibonacciElement"
+    );
+    let mut debugger = debugger(loader::load_file(path).unwrap());
+    assert_eq!(
+        run(&mut debugger, "break Main.vm:14"),
+        "Breakpoint at ROM[63] (FibonacciElement.Main.fibonacci+10)"
+    );
+    assert_eq!(
+        run(&mut debugger, "continue"),
+        "Breakpoint reached\nROM[63] (FibonacciElement.Main.fibonacci+10): @2  (line 70)\n    \
+         Main.vm:14  push constant 2"
+    );
+    // Sys.init called Main.fibonacci with its argument at RAM[261].
+    assert_eq!(run(&mut debugger, "print ARG"), "RAM[2] = 261");
+    // Comments generate no code to stop at.
+    assert!(debugger.execute("break Main.vm:1").is_err());
+}
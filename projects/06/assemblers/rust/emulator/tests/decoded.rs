@@ -0,0 +1,92 @@
+use emulator::cpu::{alu, Cpu};
+use emulator::decoded::{decode, Comp, Decoded, Op};
+use emulator::halt::Stop;
+use emulator::loader;
+use emulator::run;
+
+// The decoded core must leave the machine exactly like the instruction-by-instruction one.
+
+fn load(path: &str) -> Cpu {
+    let path = format!("{}/../../../../{}", env!("CARGO_MANIFEST_DIR"), path);
+    let program = loader::load_file(&path).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu
+}
+
+fn assert_same_run(path: &str, setup: impl Fn(&mut Cpu), max_cycles: u64) -> Stop {
+    let mut expected = load(path);
+    setup(&mut expected);
+    let stop = run::run(&mut expected, max_cycles, &mut []).unwrap();
+    let mut cpu = load(path);
+    setup(&mut cpu);
+    let decoded = Decoded::new(&cpu.rom);
+    assert_eq!(decoded.run(&mut cpu, max_cycles), stop, "{}", path);
+    assert_eq!(cpu.cycles, expected.cycles, "{}", path);
+    assert_eq!(
+        (cpu.a, cpu.d, cpu.pc),
+        (expected.a, expected.d, expected.pc)
+    );
+    assert!(cpu.ram == expected.ram, "{}: RAM differs", path);
+    stop
+}
+
+#[test]
+fn computes_like_the_alu() {
+    let values: [u16; 9] = [0, 1, 2, 7, 0x7FFF, 0x8000, 0xFFFE, 0xFFFF, 12_345];
+    for comp in 0..128 {
+        let decoded = Comp::decode(comp);
+        for a in values {
+            for d in values {
+                let m = a.wrapping_mul(3) ^ d;
+                assert_eq!(decoded.apply(a, d, m), alu(comp, a, d, m), "{:07b}", comp);
+            }
+        }
+    }
+    assert_eq!(Comp::decode(0b1110111), Comp::MPlusOne);
+    assert_eq!(Comp::decode(0b1101010), Comp::Other(0b1101010));
+}
+
+#[test]
+fn decodes_every_instruction() {
+    let mut rom = vec![0; 32_768];
+    // @5, D=M, D;JGT, @7 at the last address.
+    rom[0] = 5;
+    rom[1] = 0b1111_1100_0001_0000;
+    rom[2] = 0b1110_0011_0000_0001;
+    rom[32_767] = 7;
+    let decoded = Decoded::new(&rom);
+    let Op::Compute(compute) = decode(rom[1]) else {
+        panic!("D=M is not a computation");
+    };
+    assert!(compute.reads_m && compute.dest_d && !compute.dest_m);
+    assert_eq!(decoded.op(0), Op::Load(5));
+    assert_eq!(decoded.op(1), Op::Compute(compute));
+    let Op::Compute(jump) = decoded.op(2) else {
+        panic!("D;JGT is not a computation");
+    };
+    assert_eq!((jump.comp, jump.jump), (Comp::D, 0b001));
+    assert_eq!(decoded.op(32_767), Op::Load(7));
+}
+
+#[test]
+fn runs_programs_like_the_cpu() {
+    let stop = assert_same_run(
+        "04/mult/Mult.asm",
+        |cpu| {
+            cpu.ram[0] = 123;
+            cpu.ram[1] = 45;
+        },
+        1_000_000,
+    );
+    assert_eq!(stop, Stop::Halted);
+    assert_eq!(
+        assert_same_run("06/max/Max.asm", |cpu| cpu.ram[1] = 9, 1_000),
+        Stop::Halted
+    );
+    // Cycle limits falling anywhere, before and after the end loop.
+    for max_cycles in [1, 2, 3, 1_001, 100_000, 100_001] {
+        assert_same_run("06/pong/Pong.asm", |_| {}, max_cycles);
+        assert_same_run("04/fill/Fill.asm", |cpu| cpu.ram[24_576] = 65, max_cycles);
+    }
+}
@@ -0,0 +1,82 @@
+use emulator::cpu::Cpu;
+use emulator::diff::{self, Change, Comparison, Event};
+use emulator::loader;
+
+// Programs that store 6 in RAM[16] and 1 in RAM[17], one the long way round through
+// the temporaries and the stack.
+const DIRECT: &str = "@6\nD=A\n@16\nM=D\n@17\nM=1\n(END)\n@END\n0;JMP\n";
+const ROUNDABOUT: &str =
+    "@3\nD=A\n@R13\nM=D\n@256\nM=D\nD=D+M\n@16\nM=D\n@17\nM=1\n(END)\n@END\n0;JMP\n";
+
+fn compare(first: &str, second: &str, observed: &[(u16, u16)]) -> Comparison {
+    let load = |source: &str| {
+        let program = loader::assemble_source(source, &Default::default()).unwrap();
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&program.words).unwrap();
+        cpu
+    };
+    let (mut first, mut second) = (load(first), load(second));
+    diff::compare(&mut first, &mut second, observed, 1_000)
+}
+
+#[test]
+fn ignores_how_programs_get_there() {
+    let comparison = compare(DIRECT, ROUNDABOUT, &diff::OBSERVED);
+    assert!(!comparison.diverged());
+    assert_eq!(comparison.changes, 2);
+    assert_eq!(
+        comparison.to_string(),
+        "Same: 2 matching changes, both halted, after 8 and 13 cycles"
+    );
+    // The temporaries and the stack are not observed unless asked for.
+    assert!(compare(DIRECT, ROUNDABOUT, &[(0, 16384)]).diverged());
+}
+
+#[test]
+fn reports_the_first_difference() {
+    let wrong = DIRECT.replace("@6", "@7");
+    let comparison = compare(DIRECT, &wrong, &diff::OBSERVED);
+    assert_eq!(
+        comparison,
+        Comparison {
+            changes: 0,
+            first: Event::Change(Change {
+                cycle: 4,
+                pc: 3,
+                address: 16,
+                old: 0,
+                new: 6
+            }),
+            second: Event::Change(Change {
+                cycle: 4,
+                pc: 3,
+                address: 16,
+                old: 0,
+                new: 7
+            }),
+        }
+    );
+    assert_eq!(
+        comparison.to_string(),
+        "Diverged after 0 matching changes\n\
+         \x20 first:  cycle 4: ROM[3] changed RAM[16] from 0 to 6\n\
+         \x20 second: cycle 4: ROM[3] changed RAM[16] from 0 to 7"
+    );
+}
+
+#[test]
+fn compares_halting_and_the_screen() {
+    // Skipping the second store halts early.
+    let early = "@6\nD=A\n@16\nM=D\n(END)\n@END\n0;JMP\n";
+    let comparison = compare(DIRECT, early, &diff::OBSERVED);
+    assert!(comparison.diverged());
+    assert_eq!(comparison.changes, 1);
+    assert_eq!(comparison.second, Event::Halted(6));
+
+    // Drawing forever never halts.
+    let drawing = "(LOOP)\n@SCREEN\nM=!M\n@LOOP\n0;JMP\n";
+    let comparison = compare(drawing, drawing, &diff::OBSERVED);
+    assert!(!comparison.diverged());
+    assert_eq!(comparison.first, Event::CycleLimit(1_000));
+    assert_eq!(comparison.changes, 250);
+}
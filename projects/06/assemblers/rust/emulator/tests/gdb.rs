@@ -0,0 +1,147 @@
+use std::io::Cursor;
+
+use emulator::cpu::Cpu;
+use emulator::gdb;
+use emulator::loader;
+
+// Plays a GDB session against the stub and checks the replies, packet by packet.
+
+fn packet(data: &str) -> Vec<u8> {
+    let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    format!("${}#{:02x}", data, checksum).into_bytes()
+}
+
+// The packets GDB would send, a 0x03 byte standing for Ctrl-C.
+fn session(cpu: Cpu, packets: &[&str]) -> (Cpu, Vec<String>, usize) {
+    let mut input = Vec::new();
+    for data in packets {
+        if *data == "\x03" {
+            input.push(0x03);
+        } else {
+            input.extend(packet(data));
+        }
+    }
+    let mut output = Vec::new();
+    let cpu = gdb::serve(cpu, Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let mut acks = 0;
+    let mut replies = Vec::new();
+    let mut rest = output.as_str();
+    while let Some(start) = rest.find('$') {
+        acks += rest[..start].matches('+').count();
+        let end = start + rest[start..].find('#').unwrap();
+        let data = &rest[start + 1..end];
+        let checksum = u8::from_str_radix(&rest[end + 1..end + 3], 16).unwrap();
+        assert_eq!(
+            checksum,
+            data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte))
+        );
+        replies.push(data.to_string());
+        rest = &rest[end + 3..];
+    }
+    acks += rest.matches('+').count();
+    (cpu, replies, acks)
+}
+
+fn load(source: &str) -> Cpu {
+    let program = loader::assemble_source(source, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu
+}
+
+#[test]
+fn debugs_a_program_with_breakpoints_and_watchpoints() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../04/mult/Mult.asm");
+    let program = loader::load_file(path).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu.ram[0] = 6;
+    cpu.ram[1] = 7;
+
+    let (cpu, replies, acks) = session(
+        cpu,
+        &[
+            "qSupported:multiprocess+;swbreak+",
+            "qXfer:features:read:target.xml:0,5",
+            "?",
+            // MULTIPLY is ROM[12], at byte 0x18.
+            "Z0,18,2",
+            "c",
+            "g",
+            "m10000,6",
+            "Z2,10004,2",
+            "c",
+            "z2,10004,2",
+            "z0,18,2",
+            "P1=0500",
+            "p1",
+            "c",
+            "m10004,2",
+            "m0,2",
+            "m20000,2",
+            "k",
+        ],
+    );
+    assert_eq!(acks, 18);
+    assert!(
+        replies[0].contains("qXfer:features:read+"),
+        "{}",
+        replies[0]
+    );
+    let expected = [
+        "m<?xml",
+        "S05",
+        "OK",
+        "T05swbreak:;",
+        // A = 17, D = 7 and PC at ROM[12].
+        "110007001800",
+        "060007000000",
+        "OK",
+        "T05watch:10004;",
+        "OK",
+        "OK",
+        "OK",
+        "0500",
+        // "Program halted\n" on the GDB console.
+        "O50726f6772616d2068616c7465640a",
+        "T05",
+        "2a00",
+        "0000",
+        "E01",
+    ];
+    assert_eq!(replies[1..], expected.map(String::from));
+    assert_eq!(cpu.ram[2], 42);
+}
+
+#[test]
+fn stops_running_programs_on_ctrl_c() {
+    // Counts in R0 forever.
+    let cpu = load("(LOOP)\n@R0\nM=M+1\n@LOOP\n0;JMP\n");
+    let (cpu, replies, acks) = session(
+        cpu,
+        &[
+            "QStartNoAckMode",
+            "vCont?",
+            "vCont;c:1",
+            "\x03",
+            "vCont;s:1",
+            "M10000,2:0000",
+            "G010002000400",
+            "g",
+            "D",
+        ],
+    );
+    // Only QStartNoAckMode itself is acknowledged.
+    assert_eq!(acks, 1);
+    assert_eq!(
+        replies[..3],
+        ["OK", "vCont;c;C;s;S", "T02"].map(String::from)
+    );
+    assert_eq!(
+        replies[3..],
+        ["T05", "OK", "OK", "010002000400", "OK"].map(String::from)
+    );
+    assert_eq!((cpu.a, cpu.d, cpu.pc), (1, 2, 2));
+    assert!(cpu.cycles > 0);
+}
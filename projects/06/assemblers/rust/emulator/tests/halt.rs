@@ -0,0 +1,77 @@
+use emulator::cpu::Cpu;
+use emulator::halt::Stop;
+use emulator::loader;
+use emulator::run;
+use emulator::summary;
+
+// Runs programs that end in different kinds of loops.
+
+fn run(source: &str, max_cycles: u64) -> (Cpu, Stop) {
+    let program = loader::assemble_source(source, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let stop = run::run(&mut cpu, max_cycles, &mut []).unwrap();
+    (cpu, stop)
+}
+
+#[test]
+fn halts_in_loops_that_change_nothing() {
+    // Rereads R0 forever, and rewrites it with the value it already has.
+    let (cpu, stop) = run(
+        "@7\nD=A\n@R0\nM=D\n(IDLE)\n@5\nD=A\n@R0\nD=M\nM=D\n@IDLE\n0;JMP\n",
+        1_000,
+    );
+    assert_eq!(stop, Stop::Halted);
+    assert_eq!(cpu.cycles, 18);
+
+    // Self-jumps other than the usual idiom.
+    let (cpu, stop) = run("@3\nD=A\n(STOP)\n@STOP\nD;JGT\n", 1_000);
+    assert_eq!(stop, Stop::Halted);
+    assert_eq!(cpu.pc, 2);
+}
+
+#[test]
+fn keeps_running_loops_that_make_progress() {
+    // A delay loop counting down in D, then the usual ending.
+    let (cpu, stop) = run(
+        "@100\nD=A\n(DELAY)\nD=D-1\n@DELAY\nD;JGT\n(END)\n@END\n0;JMP\n",
+        1_000,
+    );
+    assert_eq!(stop, Stop::Halted);
+    assert_eq!(cpu.cycles, 2 + 3 * 100 + 2);
+
+    // Waiting for a key depends on the keyboard, so only the limit ends it.
+    let (cpu, stop) = run("(WAIT)\n@KBD\nD=M\n@WAIT\nD;JEQ\n", 1_000);
+    assert_eq!(stop, Stop::CycleLimit);
+    assert_eq!(cpu.cycles, 1_000);
+
+    // A counter in RAM changes memory on every round.
+    let (_, stop) = run("(COUNT)\n@R0\nM=M+1\n@COUNT\n0;JMP\n", 1_000);
+    assert_eq!(stop, Stop::CycleLimit);
+}
+
+#[test]
+fn writes_the_result_as_json() {
+    let (cpu, stop) = run("@6\nD=A\n@R1\nM=-D\n(END)\n@END\n0;JMP\n", 1_000);
+    assert_eq!(
+        summary::to_json(stop, &cpu, None, None, &[(0, 2), (5, 5)]),
+        "{
+  \"status\": \"halted\",
+  \"cycles\": 6,
+  \"registers\": {\"A\": 4, \"D\": 6, \"PC\": 4},
+  \"watchpoint\": null,
+  \"check\": null,
+  \"ram\": [
+    {\"start\": 0, \"end\": 2, \"values\": [0, -6]},
+    {\"start\": 5, \"end\": 5, \"values\": []}
+  ]
+}
+"
+    );
+    let (cpu, stop) = run("(LOOP)\n@R0\nM=M+1\n@LOOP\n0;JMP\n", 10);
+    assert!(summary::to_json(stop, &cpu, None, None, &[]).contains("\"status\": \"timeout\""));
+    // The text report uses the same words.
+    assert_eq!(summary::status(stop, false), "timeout");
+    assert_eq!(summary::status(Stop::Requested, true), "check");
+    assert_eq!(summary::status(Stop::Requested, false), "watchpoint");
+}
@@ -0,0 +1,90 @@
+use emulator::cpu::Cpu;
+use emulator::keyboard::{self, HOLD};
+use emulator::loader;
+use emulator::replay::{KeyEvent, Replay};
+use emulator::run;
+use emulator::screen::pixel;
+use emulator::terminal::{LEFT, NEWLINE};
+
+// Keyboard scripts, from their text to a game reacting to them.
+
+fn events(script: &str) -> Vec<(u64, u16)> {
+    keyboard::parse_script(script)
+        .unwrap()
+        .into_iter()
+        .map(|KeyEvent { cycle, key }| (cycle, key))
+        .collect()
+}
+
+#[test]
+fn turns_statements_into_key_events() {
+    assert_eq!(
+        events("at cycle 10000 press LEFT for 5000 cycles; type \"hi\\n\" for 10"),
+        vec![
+            (10_000, LEFT),
+            (15_000, 0),
+            (15_000, b'h' as u16),
+            (15_010, 0),
+            (15_020, b'i' as u16),
+            (15_030, 0),
+            (15_040, NEWLINE),
+            (15_050, 0),
+        ]
+    );
+    assert_eq!(
+        events("# waiting for the title screen\nwait 1_000 cycles\npress space\npress \"#\" for 1 cycle\npress F12 for 2\n"),
+        vec![
+            (1_000, b' ' as u16),
+            (1_000 + HOLD, 0),
+            (1_000 + HOLD, b'#' as u16),
+            (1_001 + HOLD, 0),
+            (1_001 + HOLD, 152),
+            (1_003 + HOLD, 0),
+        ]
+    );
+}
+
+#[test]
+fn reports_mistakes_with_their_line() {
+    let error = |script: &str| keyboard::parse_script(script).unwrap_err();
+    assert_eq!(
+        error("press LEFT\npress SHIFT"),
+        "Line 2: Unknown key 'SHIFT'"
+    );
+    assert_eq!(
+        error("at cycle 500 press UP; at cycle 10 press DOWN"),
+        "Line 1: Cycle 10 is before cycle 100500"
+    );
+    assert_eq!(error("type \"é\""), "Line 1: No Hack key for 'é'");
+    assert_eq!(error("type \"open"), "Line 1: Unterminated text");
+    assert_eq!(error("hold UP"), "Line 1: Unknown statement 'hold UP'");
+    assert_eq!(
+        error("press UP for 0 cycles"),
+        "Line 1: Keys must be held for at least one cycle"
+    );
+}
+
+// Column of the left end of Pong's bat, which sits right above the line at the bottom.
+fn bat_column(script: &str) -> usize {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../pong/Pong.asm");
+    let program = loader::load_file(path).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut replay = Replay::new(keyboard::parse_script(script).unwrap());
+    run::run(&mut cpu, 8_000_000, &mut [&mut replay]).unwrap();
+    (0..512)
+        .find(|column| pixel(cpu.screen(), 232, *column))
+        .expect("no bat on the screen")
+}
+
+#[test]
+fn moves_the_pong_bat() {
+    let still = bat_column("");
+    let moved = bat_column("at cycle 6_000_000 press LEFT for 2_000_000 cycles");
+    assert!(
+        moved + 50 < still,
+        "the bat went from {} to {}",
+        still,
+        moved
+    );
+}
@@ -0,0 +1,76 @@
+use std::fs;
+
+use emulator::cpu::Cpu;
+use emulator::halt::Stop;
+use emulator::loader;
+use emulator::run;
+
+// Programs given as VM code, translated in-process before they run.
+
+fn projects(path: &str) -> String {
+    format!("{}/../../../../{}", env!("CARGO_MANIFEST_DIR"), path)
+}
+
+fn run(path: &str, settings: &[(usize, u16)]) -> (Cpu, Stop) {
+    let program = loader::load_file(&projects(path)).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    for (address, value) in settings {
+        cpu.ram[*address] = *value;
+    }
+    let stop = run::run(&mut cpu, 100_000, &mut []).unwrap();
+    (cpu, stop)
+}
+
+#[test]
+fn runs_a_vm_file_to_its_end() {
+    // Without Sys.init the stack is set up like the course's test scripts do.
+    let (cpu, stop) = run("07/StackArithmetic/SimpleAdd/SimpleAdd.vm", &[]);
+    assert_eq!(stop, Stop::Halted);
+    assert_eq!((cpu.ram[0], cpu.ram[256]), (257, 15));
+    assert_eq!((cpu.ram[1], cpu.ram[2]), (300, 400));
+    // Unless it is set up already.
+    let (cpu, _) = run(
+        "07/StackArithmetic/SimpleAdd/SimpleAdd.vm",
+        &[(0, 1000), (1, 1100)],
+    );
+    assert_eq!((cpu.ram[0], cpu.ram[1000]), (1001, 15));
+    assert_eq!((cpu.ram[1], cpu.ram[2]), (1100, 0));
+}
+
+#[test]
+fn runs_a_directory_from_sys_init() {
+    let (cpu, stop) = run("08/FunctionCalls/FibonacciElement", &[]);
+    assert_eq!(stop, Stop::Halted);
+    assert_eq!((cpu.ram[0], cpu.ram[261]), (262, 3));
+    // Statics of different files stay apart.
+    let (cpu, _) = run("08/FunctionCalls/StaticsTest", &[]);
+    assert_eq!((cpu.ram[261], cpu.ram[262]), (-2i16 as u16, 8));
+}
+
+#[test]
+fn maps_code_to_vm_lines() {
+    let program = loader::load_file(&projects("08/FunctionCalls/FibonacciElement")).unwrap();
+    let address = program
+        .source_map
+        .address_of_vm_line("Main.vm", 14)
+        .unwrap();
+    let location = program.source_map.vm_location(address).unwrap();
+    assert_eq!(location.command, "push constant 2");
+    assert_eq!(program.source_map.address_of_vm_line("Main.vm", 1), None);
+}
+
+#[test]
+fn reports_errors_in_vm_files() {
+    let path = std::env::temp_dir().join(format!("emulator-loader-{}.vm", std::process::id()));
+    fs::write(&path, "push constant 1\n// pushes a local\npush locals 2\n").unwrap();
+    let error = loader::load_file(&path.to_string_lossy()).err().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        error,
+        format!(
+            "{}:3: Unknown segment 'locals'",
+            path.file_name().unwrap().to_string_lossy()
+        )
+    );
+}
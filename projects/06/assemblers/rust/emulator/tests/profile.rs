@@ -0,0 +1,140 @@
+use emulator::cpu::Cpu;
+use emulator::loader;
+use emulator::profile::Profiler;
+use emulator::run;
+use emulator::vm::is_function_label;
+
+// Profiles a program shaped like the VM translator's output: Main.main calls Main.twice twice.
+// The calls keep their return address in R14 instead of a stack frame.
+
+const PROGRAM: &str = "\
+@256
+D=A
+@SP
+M=D
+// vm Main.vm:1 function Main.main 0
+(Main.main)
+// vm Main.vm:2 call Main.twice 0
+@RET_Main.twice.0
+D=A
+@R14
+M=D
+@SP
+D=M
+@LCL
+M=D
+@Main.twice
+0;JMP
+(RET_Main.twice.0)
+// vm Main.vm:3 call Main.twice 0
+@RET_Main.twice.1
+D=A
+@R14
+M=D
+@SP
+D=M
+@LCL
+M=D
+@Main.twice
+0;JMP
+(RET_Main.twice.1)
+// vm Main.vm:4 label END
+(END)
+@END
+0;JMP
+// vm Main.vm:6 function Main.twice 0
+(Main.twice)
+@R5
+M=M+1
+M=M+1
+// vm Main.vm:7 return
+@R14
+A=M
+0;JMP
+";
+
+fn profile() -> Profiler {
+    let program = loader::assemble_source(PROGRAM, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut profiler = Profiler::new(&program);
+    run::run(&mut cpu, 1_000, &mut [&mut profiler]).unwrap();
+    profiler
+}
+
+#[test]
+fn recognizes_function_labels() {
+    assert!(is_function_label("Main.main"));
+    assert!(is_function_label("Sys.Sys.init"));
+    for label in ["RET_Main.main.3", "END_LT.3", "LOOP", "Main.main$IF_TRUE0"] {
+        assert!(!is_function_label(label), "{}", label);
+    }
+}
+
+#[test]
+fn folds_call_stacks() {
+    // Main.main is where the program starts rather than a call, so it runs in the root.
+    // The root runs 4 bootstrap words, 10 per call and the 2 words of the final loop.
+    assert_eq!(
+        profile().folded(),
+        "(program) 26\n(program);Main.twice 12\n"
+    );
+}
+
+#[test]
+fn reports_functions_commands_and_the_call_tree() {
+    let report = profile().report();
+    assert!(
+        report.starts_with("Profile of 38 instructions\n"),
+        "{}",
+        report
+    );
+    for line in [
+        "          22  57.9%  Main.main",
+        "          12  31.6%  Main.twice",
+        "           4  10.5%  (program)",
+        "          20  52.6%  call",
+        "           6  15.8%  function",
+        "           6  15.8%  return",
+        "           2   5.3%  label",
+        "          38 100.0%           26        1  (program)",
+        "          12  31.6%           12        2    Main.twice",
+    ] {
+        assert!(
+            report.lines().any(|l| l == line),
+            "no '{}' in\n{}",
+            line,
+            report
+        );
+    }
+}
+
+#[test]
+fn reports_the_commands_of_translated_programs() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../../../08/FunctionCalls/FibonacciElement"
+    );
+    let program = loader::load_file(path).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut profiler = Profiler::new(&program);
+    run::run(&mut cpu, 100_000, &mut [&mut profiler]).unwrap();
+    let report = profiler.report();
+    // Every command of the VM files is counted under its own name, only the bootstrap
+    // is not VM code.
+    for line in [
+        "         522  29.8%  return",
+        "         441  25.2%  call",
+        "         346  19.7%  push",
+        "         171   9.8%  lt",
+        "          53   3.0%  (program)",
+    ] {
+        assert!(
+            report.lines().any(|l| l == line),
+            "no '{}' in\n{}",
+            line,
+            report
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use emulator::cpu::Cpu;
+use emulator::loader;
+use emulator::replay::{self, KeyEvent, Recorder, Replay};
+use emulator::run;
+use emulator::snapshot;
+
+// Keyboard replays and snapshots must reproduce a run exactly.
+
+// Adds the key pressed at every iteration to R0, so any difference in timing shows.
+const SUM_KEYS: &str = "(LOOP)\n@KBD\nD=M\n@R0\nM=D+M\n@LOOP\n0;JMP\n";
+
+const EVENTS: &str = "# cycle key\n10 65\n30 0\n50 66 # B\n";
+
+fn load() -> Cpu {
+    let program = loader::assemble_source(SUM_KEYS, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu
+}
+
+fn replay() -> Replay {
+    Replay::new(replay::parse_events(EVENTS).unwrap())
+}
+
+#[test]
+fn parses_and_formats_events() {
+    let events = replay::parse_events(EVENTS).unwrap();
+    assert_eq!(
+        events,
+        vec![
+            KeyEvent { cycle: 10, key: 65 },
+            KeyEvent { cycle: 30, key: 0 },
+            KeyEvent { cycle: 50, key: 66 },
+        ]
+    );
+    assert_eq!(
+        replay::format_events(&events),
+        "# cycle key\n10 65\n30 0\n50 66\n"
+    );
+    assert!(replay::parse_events("10\n").is_err());
+    assert!(replay::parse_events("10 x\n").is_err());
+    assert_eq!(
+        replay::parse_events("20 1\n10 0\n"),
+        Err("Line 2: events must be in cycle order".to_string())
+    );
+}
+
+#[test]
+fn recorder_keeps_only_changes() {
+    let mut cpu = load();
+    let mut recorder = Recorder::default();
+    recorder.record(&cpu, 0);
+    cpu.cycles = 5;
+    recorder.record(&cpu, 65);
+    cpu.cycles = 6;
+    recorder.record(&cpu, 65);
+    cpu.cycles = 9;
+    recorder.record(&cpu, 0);
+    assert_eq!(
+        recorder.events,
+        vec![
+            KeyEvent { cycle: 5, key: 65 },
+            KeyEvent { cycle: 9, key: 0 }
+        ]
+    );
+}
+
+#[test]
+fn snapshot_and_replay_resume_a_run_exactly() {
+    let mut full = load();
+    run::run(&mut full, 100, &mut [&mut replay()]).unwrap();
+    assert_ne!(full.ram[0], 0);
+
+    let mut first = load();
+    let mut first_replay = replay();
+    run::run(&mut first, 40, &mut [&mut first_replay]).unwrap();
+    let bytes = snapshot::encode(&first, first_replay.position);
+
+    let restored = snapshot::decode(&bytes).unwrap();
+    let mut resumed = restored.cpu;
+    let mut resumed_replay = replay();
+    resumed_replay.position = restored.replay_position;
+    assert_eq!(resumed_replay.position, 2);
+    run::run(&mut resumed, 100, &mut [&mut resumed_replay]).unwrap();
+
+    assert_eq!(resumed.cycles, full.cycles);
+    assert_eq!(resumed.pc, full.pc);
+    assert_eq!((resumed.a, resumed.d), (full.a, full.d));
+    assert_eq!(resumed.ram[..], full.ram[..]);
+}
+
+#[test]
+fn rejects_broken_snapshots() {
+    let bytes = snapshot::encode(&load(), 0);
+    assert!(snapshot::decode(&bytes[..bytes.len() - 1]).is_err());
+    assert!(snapshot::decode(b"NOTASNAP").is_err());
+    let mut future = bytes.clone();
+    future[11] = 2;
+    assert_eq!(
+        snapshot::decode(&future).err(),
+        Some("Unsupported snapshot version 2".to_string())
+    );
+}
@@ -0,0 +1,54 @@
+use emulator::cpu::Cpu;
+use emulator::halt::Stop;
+use emulator::loader;
+use emulator::run;
+use emulator::screen::{self, HEIGHT, WIDTH};
+
+// Runs Rect until it halts and checks the drawn screen and its images.
+
+fn run_rect(rows: u16) -> Cpu {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../../06/rect/Rect.asm");
+    let program = loader::load_file(path).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    cpu.ram[0] = rows;
+    assert_eq!(run::run(&mut cpu, 100_000, &mut []).unwrap(), Stop::Halted);
+    cpu
+}
+
+#[test]
+fn rect_draws_a_16_pixel_wide_rectangle() {
+    let cpu = run_rect(50);
+    for row in 0..HEIGHT {
+        for column in 0..WIDTH {
+            let expected = row < 50 && column < 16;
+            assert_eq!(
+                screen::pixel(cpu.screen(), row, column),
+                expected,
+                "({}, {})",
+                row,
+                column
+            );
+        }
+    }
+}
+
+#[test]
+fn writes_pbm_and_png_images() {
+    let cpu = run_rect(3);
+
+    let pbm = screen::to_pbm(cpu.screen());
+    let header = format!("P4\n{} {}\n", WIDTH, HEIGHT);
+    assert!(pbm.starts_with(header.as_bytes()));
+    assert_eq!(pbm.len(), header.len() + WIDTH * HEIGHT / 8);
+    let pixels = &pbm[header.len()..];
+    assert_eq!(&pixels[..3], &[0xFF, 0xFF, 0x00]);
+    assert_eq!(pixels[2 * WIDTH / 8], 0xFF);
+    assert_eq!(pixels[3 * WIDTH / 8], 0x00);
+
+    let png = screen::to_png(cpu.screen());
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], &[0, 0, 2, 0, 0, 0, 1, 0]);
+    assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+}
@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use emulator::script::{cover_script, run_script};
+
+// Runs the CPU emulator test scripts of the course projects on copies of their
+// directories, so the .out files in the repository are left alone.
+
+static COPIES: AtomicUsize = AtomicUsize::new(0);
+
+fn copy_project(project: &str) -> PathBuf {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../../..")
+        .join(project);
+    let target = std::env::temp_dir()
+        .join(format!("emulator-script-{}", std::process::id()))
+        .join(format!(
+            "{}-{}",
+            project.replace('/', "-"),
+            COPIES.fetch_add(1, Ordering::SeqCst)
+        ));
+    fs::create_dir_all(&target).unwrap();
+    for entry in fs::read_dir(source).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_file() {
+            fs::copy(&path, target.join(path.file_name().unwrap())).unwrap();
+        }
+    }
+    target
+}
+
+fn assert_script_passes(project: &str, script: &str) {
+    let directory = copy_project(project);
+    let report = run_script(&directory.join(script)).unwrap();
+    assert_eq!(report.comparison, Some(Ok(())), "{}/{}", project, script);
+    assert!(report.output_file.unwrap().exists());
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn runs_project_04_scripts() {
+    assert_script_passes("04/mult", "Mult.tst");
+    assert_script_passes("04/fill", "FillAutomatic.tst");
+}
+
+#[test]
+fn runs_project_07_scripts() {
+    for (project, script) in [
+        ("07/StackArithmetic/SimpleAdd", "SimpleAdd.tst"),
+        ("07/StackArithmetic/StackTest", "StackTest.tst"),
+        ("07/MemoryAccess/BasicTest", "BasicTest.tst"),
+        ("07/MemoryAccess/PointerTest", "PointerTest.tst"),
+        ("07/MemoryAccess/StaticTest", "StaticTest.tst"),
+    ] {
+        assert_script_passes(project, script);
+    }
+}
+
+#[test]
+fn reports_the_first_mismatch() {
+    let directory = copy_project("04/mult");
+    let cmp = directory.join("Mult.cmp");
+    let expected = fs::read_to_string(&cmp).unwrap().replace("42  |", "43  |");
+    fs::write(&cmp, expected).unwrap();
+    let report = run_script(&directory.join("Mult.tst")).unwrap();
+    let message = report.comparison.unwrap().unwrap_err();
+    assert!(message.contains("line 7"), "{}", message);
+    assert!(message.contains("RAM[2]"), "{}", message);
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn covers_the_program_under_test() {
+    let directory = copy_project("07/StackArithmetic/StackTest");
+    let report = cover_script(&directory.join("StackTest.tst")).unwrap();
+    assert_eq!(report.comparison, Some(Ok(())));
+    let coverage = report.coverage.unwrap().report();
+    // Every comparison runs once, so each of its jumps goes a single way.
+    assert!(
+        coverage.starts_with("Coverage: 411 of 474 instructions executed (86.7%), 0 of 9 conditional jumps went both ways\n"),
+        "{}",
+        coverage
+    );
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,98 @@
+use emulator::cpu::Cpu;
+use emulator::halt::Stop;
+use emulator::loader;
+use emulator::run;
+use emulator::stack::StackChecker;
+
+// VM programs with the kinds of bugs the stack checker is for, some of them made by
+// breaking the translator's output on purpose.
+
+fn check(vm: &str, patch: Option<(&str, &str)>) -> (Stop, Option<String>) {
+    let files = [("Sys.vm".to_string(), vm.to_string())];
+    let mut source = vm_translator::translate("Test", &files).unwrap();
+    if let Some((code, broken)) = patch {
+        assert!(source.contains(code));
+        source = source.replace(code, broken);
+    }
+    let program = loader::assemble_source(&source, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut checker = StackChecker::new(&program);
+    let stop = run::run(&mut cpu, 100_000, &mut [&mut checker]).unwrap();
+    (stop, checker.violation.map(|violation| violation.message))
+}
+
+const CALLS: &str = "
+function Sys.init 0
+push constant 3
+call Sys.twice 1
+pop temp 0
+label END
+goto END
+function Sys.twice 1
+push argument 0
+push argument 0
+add
+return
+";
+
+#[test]
+fn passes_correct_programs() {
+    assert_eq!(check(CALLS, None), (Stop::Halted, None));
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../../../08/FunctionCalls/FibonacciElement"
+    );
+    let program = loader::load_file(path).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    let mut checker = StackChecker::new(&program);
+    let stop = run::run(&mut cpu, 100_000, &mut [&mut checker]).unwrap();
+    assert_eq!((stop, checker.violation), (Stop::Halted, None));
+}
+
+#[test]
+fn finds_stack_overflows_and_underflows() {
+    let (stop, message) = check("function Sys.init 0\npop temp 0\n", None);
+    assert_eq!(stop, Stop::Requested);
+    assert_eq!(
+        message.unwrap(),
+        "Stack underflow: SP went down to 260, below the frame at LCL 261"
+    );
+    let (_, message) = check(
+        "function Sys.init 0\nlabel LOOP\npush constant 1\ngoto LOOP\n",
+        None,
+    );
+    assert_eq!(message.unwrap(), "The stack grew into the heap, SP is 2049");
+}
+
+#[test]
+fn finds_pointers_into_the_stack() {
+    let (_, message) = check(
+        "function Sys.init 0\npush constant 300\npop pointer 1\n",
+        None,
+    );
+    assert_eq!(message.unwrap(), "THAT points into the stack at 300");
+}
+
+#[test]
+fn finds_broken_returns() {
+    // LCL = *(frame - 3) instead of *(frame - 4), the caller gets its ARG as LCL.
+    let (_, message) = check(
+        CALLS,
+        Some(("@4\nD=D-A\nA=D\nD=M\n@LCL", "@3\nD=D-A\nA=D\nD=M\n@LCL")),
+    );
+    assert_eq!(
+        message.unwrap(),
+        "The return restored LCL to 256, the call saved 261"
+    );
+    // SP = ARG instead of ARG + 1, the return value is lost.
+    let (_, message) = check(
+        CALLS,
+        Some(("@ARG\nD=M\n@SP\nM=D+1", "@ARG\nD=M\n@SP\nM=D")),
+    );
+    assert_eq!(
+        message.unwrap(),
+        "The return left SP at 261 instead of ARG + 1 = 262"
+    );
+}
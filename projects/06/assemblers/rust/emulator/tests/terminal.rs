@@ -0,0 +1,69 @@
+use emulator::cpu::SCREEN_WORDS;
+use emulator::terminal::{self, decode_keys, Input, Style};
+
+// Checks the key decoding and the scaling of the terminal renderer.
+
+#[test]
+fn decodes_hack_key_codes() {
+    let expected: Vec<Input> = [97, 65, 32, 128, 129, 130, 131, 132, 133, 134, 135]
+        .into_iter()
+        .map(Input::Key)
+        .collect();
+    assert_eq!(
+        decode_keys(b"aA \r\x7f\x1b[D\x1b[A\x1b[C\x1b[B\x1b[H\x1b[F"),
+        expected
+    );
+    let expected: Vec<Input> = [136, 137, 138, 139, 140]
+        .into_iter()
+        .map(Input::Key)
+        .collect();
+    assert_eq!(decode_keys(b"\x1b[5~\x1b[6~\x1b[2~\x1b[3~\x1b"), expected);
+}
+
+#[test]
+fn decodes_function_keys() {
+    let sequences: [&[u8]; 12] = [
+        b"\x1bOP",
+        b"\x1bOQ",
+        b"\x1bOR",
+        b"\x1bOS",
+        b"\x1b[15~",
+        b"\x1b[17~",
+        b"\x1b[18~",
+        b"\x1b[19~",
+        b"\x1b[20~",
+        b"\x1b[21~",
+        b"\x1b[23~",
+        b"\x1b[24~",
+    ];
+    for (index, sequence) in sequences.iter().enumerate() {
+        assert_eq!(decode_keys(sequence), [Input::Key(141 + index as u16)]);
+    }
+    // Modifiers are ignored, unknown sequences dropped, Ctrl-C quits.
+    assert_eq!(
+        decode_keys(b"\x1b[1;2A\x1b[99~x\x03"),
+        [Input::Key(131), Input::Key(120), Input::Quit]
+    );
+}
+
+#[test]
+fn renders_at_full_size_and_scaled() {
+    let mut screen = vec![0u16; SCREEN_WORDS];
+    // Top left and bottom right pixels.
+    screen[0] = 1;
+    screen[SCREEN_WORDS - 1] = 0x8000;
+
+    let lines = terminal::render(&screen, Style::Braille, 256, 64);
+    assert_eq!(lines.len(), 64);
+    assert!(lines.iter().all(|line| line.chars().count() == 256));
+    assert_eq!(lines[0].chars().next(), Some('\u{2801}'));
+    assert_eq!(lines[63].chars().last(), Some('\u{2880}'));
+    assert_eq!(lines[1].chars().next(), Some('\u{2800}'));
+
+    let lines = terminal::render(&screen, Style::HalfBlocks, 80, 23);
+    // Scaled down by 7 to fit 80 columns: 74 x 37 pixels.
+    assert_eq!(lines.len(), 19);
+    assert!(lines.iter().all(|line| line.chars().count() == 74));
+    assert_eq!(lines[0].chars().next(), Some('▀'));
+    assert_eq!(lines[18].chars().last(), Some('▀'));
+}
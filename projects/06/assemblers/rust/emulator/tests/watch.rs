@@ -0,0 +1,74 @@
+use emulator::cpu::Cpu;
+use emulator::halt::Stop;
+use emulator::loader::{self, Program};
+use emulator::run;
+use emulator::watch::{Access, AccessTrace, Hit, Watcher, Watchpoint};
+
+// Runs small programs with watchpoints and the access trace attached.
+
+fn load(source: &str) -> (Program, Cpu) {
+    let program = loader::assemble_source(source, &Default::default()).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.load_rom(&program.words).unwrap();
+    (program, cpu)
+}
+
+fn first_hit(source: &str, spec: &str) -> (Option<Hit>, Stop) {
+    let (program, mut cpu) = load(source);
+    let mut watcher = Watcher {
+        watchpoints: vec![Watchpoint::parse(spec, &program).unwrap()],
+        hit: None,
+    };
+    let stop = run::run(&mut cpu, 1_000, &mut [&mut watcher]).unwrap();
+    (watcher.hit, stop)
+}
+
+const CLOBBER: &str = "@THAT\nM=0\n@7\nD=A\n@THAT\nM=D\nAM=M+1\n(END)\n@END\n0;JMP\n";
+
+#[test]
+fn parses_specs() {
+    let (program, _) = load(CLOBBER);
+    let watchpoint = Watchpoint::parse("read:SCREEN..KBD", &program).unwrap();
+    assert_eq!(watchpoint.access, Access::Read);
+    assert_eq!((watchpoint.start, watchpoint.end), (16_384, 24_576));
+    assert_eq!(watchpoint.to_string(), "read RAM[16384..24576]");
+    assert_eq!(
+        Watchpoint::parse("R13", &program).unwrap().to_string(),
+        "write RAM[13]"
+    );
+    assert!(Watchpoint::parse("poke:R13", &program).is_err());
+    assert!(Watchpoint::parse("R14..R13", &program).is_err());
+}
+
+#[test]
+fn stops_at_the_first_matching_access() {
+    let (hit, stop) = first_hit(CLOBBER, "THAT");
+    assert_eq!(stop, Stop::Requested);
+    assert_eq!(hit.unwrap().to_string(), "ROM[1] wrote RAM[4]: 0 -> 0");
+
+    // Writing the 0 that was already there is not a change.
+    let (hit, _) = first_hit(CLOBBER, "change:THAT");
+    assert_eq!(hit.unwrap().to_string(), "ROM[5] wrote RAM[4]: 0 -> 7");
+
+    // AM=M+1 reads the old value before writing the new one.
+    let (hit, _) = first_hit(CLOBBER, "read:THAT");
+    assert_eq!(hit.unwrap().to_string(), "ROM[6] read RAM[4] = 7");
+
+    let (hit, stop) = first_hit(CLOBBER, "R13..R15");
+    assert_eq!((hit, stop), (None, Stop::Halted));
+}
+
+#[test]
+fn traces_every_access() {
+    let (_, mut cpu) = load(CLOBBER);
+    let mut trace = AccessTrace { output: Vec::new() };
+    run::run(&mut cpu, 1_000, &mut [&mut trace]).unwrap();
+    let trace = String::from_utf8(trace.output).unwrap();
+    assert_eq!(
+        trace,
+        "2 ROM[1] write RAM[4] 0 -> 0\n\
+         6 ROM[5] write RAM[4] 0 -> 7\n\
+         7 ROM[6] read RAM[4] 7\n\
+         7 ROM[6] write RAM[4] 7 -> 8\n"
+    );
+}
@@ -1,7 +1,5 @@
 use std::fmt::{self};
 
-use crate::exiting_sequence;
-
 #[derive(Debug, Clone)]
 pub enum VmCommand {
     PushPop {
@@ -31,47 +29,50 @@ impl fmt::Display for VmCommand {
     }
 }
 
-pub fn categorize_commands(command: &str) -> VmCommand {
-    let vm_command: VmCommand;
-    if command.starts_with("push") || command.starts_with("pop") {
-        let splitted_command: Vec<&str> = command.split(" ").collect();
-        let d_type = splitted_command[0];
-        let segment = splitted_command[1];
-        let value = splitted_command[2];
-        vm_command = VmCommand::PushPop {
-            d_type: d_type.to_string(),
-            segment: segment.to_string(),
-            value: value.to_string(),
-        };
-    } else if command.starts_with("add")
-        || command.starts_with("sub")
-        || command.starts_with("neg")
-        || command.starts_with("eq")
-        || command.starts_with("gt")
-        || command.starts_with("lt")
-        || command.starts_with("and")
-        || command.starts_with("or")
-        || command.starts_with("not")
-    {
-        vm_command = VmCommand::AirthLogic(command.to_string());
-    } else if command.starts_with("function")
-        || command.starts_with("return")
-        || command.starts_with("call")
-    {
-        vm_command = VmCommand::Function(command.to_string());
-    } else {
+// Sorts a line of VM code into its kind of command, checking its arguments.
+pub fn categorize_commands(command: &str) -> Result<VmCommand, String> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let is_number = |word: &str| word.parse::<u16>().is_ok();
+    match words[..] {
+        [d_type @ ("push" | "pop"), segment, value] => {
+            let valid = match segment {
+                "argument" | "local" | "static" | "this" | "that" | "temp" => is_number(value),
+                "constant" => d_type == "push" && is_number(value),
+                "pointer" => value == "0" || value == "1",
+                _ => return Err(format!("Unknown segment '{}'", segment)),
+            };
+            if !valid {
+                return Err(format!("Invalid '{}'", command));
+            }
+            Ok(VmCommand::PushPop {
+                d_type: d_type.to_string(),
+                segment: segment.to_string(),
+                value: value.to_string(),
+            })
+        }
+        ["add" | "sub" | "neg" | "eq" | "gt" | "lt" | "and" | "or" | "not"] => {
+            Ok(VmCommand::AirthLogic(command.to_string()))
+        }
+        ["function" | "call", _, count] if is_number(count) => {
+            Ok(VmCommand::Function(words.join(" ")))
+        }
+        ["return"] => Ok(VmCommand::Function(command.to_string())),
         // VM Branching commands are, goto, if-goto and label
-        // Anything else should be invalid VM Command.
-        vm_command = VmCommand::Branching(command.to_string());
+        ["goto" | "if-goto" | "label", _] => Ok(VmCommand::Branching(words.join(" "))),
+        _ => Err(format!("Invalid VM command '{}'", command)),
     }
-    return vm_command;
 }
 
+// Translates the commands of a .vm file, each with its line number. Every command is
+// preceded by a `// vm File.vm:LINE command` comment so tools can map the code back.
+// Labels are numbered from `first_label_index` on, which keeps them unique across files.
 pub fn generate_machine_code(
-    vm_command: Vec<VmCommand>,
+    vm_command: Vec<(usize, VmCommand)>,
     output_file_name: String,
     input_file_name: String,
     include_bootstrap_code: bool,
+    vm_file_name: &str,
+    first_label_index: usize,
 ) -> Vec<String> {
     let mut machine_code = Vec::new();
     let f_name = input_file_name.as_str();
@@ -83,7 +84,9 @@ pub fn generate_machine_code(
         machine_code.extend(call_sys_init(0, output_file_name.clone()));
     }
 
-    for (i, command) in vm_command.iter().enumerate() {
+    for (i, (line, command)) in vm_command.iter().enumerate() {
+        let i = first_label_index + i;
+        machine_code.push(format!("// vm {}:{} {}", vm_file_name, line, command));
         let mut line_asm_code: String;
         match command {
             VmCommand::AirthLogic(command) => {
@@ -189,27 +192,22 @@ pub fn generate_machine_code(
                             );
                             machine_code.push(line_asm_code);
                         }
-                        "pointer" => {
-                            match value.as_str() {
-                                "0" => {
-                                    line_asm_code = format!(
-                                        // this is RAM[3]
-                                        "@3\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1"
-                                    );
-                                    machine_code.push(line_asm_code);
-                                }
-                                "1" => {
-                                    line_asm_code = format!("@4\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
-                                    machine_code.push(line_asm_code);
-                                }
-                                _ => {
-                                    println!(
-                                        "This should not be printed in console. Investigate!{}",
-                                        command
-                                    );
-                                }
+                        "pointer" => match value.as_str() {
+                            "0" => {
+                                line_asm_code = "@3\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
+                                machine_code.push(line_asm_code);
                             }
-                        }
+                            "1" => {
+                                line_asm_code = "@4\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
+                                machine_code.push(line_asm_code);
+                            }
+                            _ => {
+                                println!(
+                                    "This should not be printed in console. Investigate!{}",
+                                    command
+                                );
+                            }
+                        },
                         "temp" => {
                             line_asm_code = format!(
                                 "@5\nD=A\n@{}\nD=D+A\nA=D\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1",
@@ -264,11 +262,11 @@ pub fn generate_machine_code(
                         }
                         "pointer" => match value.as_str() {
                             "0" => {
-                                line_asm_code = format!("@SP\nM=M-1\nA=M\nD=M\n@3\nM=D");
+                                line_asm_code = "@SP\nM=M-1\nA=M\nD=M\n@3\nM=D".to_string();
                                 machine_code.push(line_asm_code);
                             }
                             "1" => {
-                                line_asm_code = format!("@SP\nM=M-1\nA=M\nD=M\n@4\nM=D");
+                                line_asm_code = "@SP\nM=M-1\nA=M\nD=M\n@4\nM=D".to_string();
                                 machine_code.push(line_asm_code);
                             }
                             _ => {}
@@ -300,7 +298,7 @@ pub fn generate_machine_code(
                         while n_vars > 0 {
                             n_vars -= 1;
                             // Push zeros to the stack.
-                            line_asm_code = format!("@0\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+                            line_asm_code = "@0\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
                             machine_code.push(line_asm_code);
                         }
                     }
@@ -317,19 +315,19 @@ pub fn generate_machine_code(
                         machine_code.push(line_asm_code);
 
                         // push LCL
-                        line_asm_code = format!("@LCL\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+                        line_asm_code = "@LCL\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
                         machine_code.push(line_asm_code);
 
                         // push ARG
-                        line_asm_code = format!("@ARG\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+                        line_asm_code = "@ARG\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
                         machine_code.push(line_asm_code);
 
                         // push THIS
-                        line_asm_code = format!("@THIS\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+                        line_asm_code = "@THIS\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
                         machine_code.push(line_asm_code);
 
                         // push THAT
-                        line_asm_code = format!("@THAT\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+                        line_asm_code = "@THAT\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
                         machine_code.push(line_asm_code);
 
                         // reposition ARG = SP-5-n_args
@@ -338,7 +336,7 @@ pub fn generate_machine_code(
                         machine_code.push(line_asm_code);
 
                         // reposition LCL = SP
-                        line_asm_code = format!("@SP\nD=M\n@LCL\nM=D");
+                        line_asm_code = "@SP\nD=M\n@LCL\nM=D".to_string();
                         machine_code.push(line_asm_code);
 
                         // then goto the calee..
@@ -352,39 +350,39 @@ pub fn generate_machine_code(
                     }
                     "return" => {
                         // frame = LCL ;; frame is a temporary variable.
-                        line_asm_code = format!("@LCL\nD=M\n@13\nM=D"); // 13 is temporary variable frame.
+                        line_asm_code = "@LCL\nD=M\n@13\nM=D".to_string(); // 13 is temporary variable frame.
                         machine_code.push(line_asm_code);
 
                         // returnAddress = *(frame - 5) ;; puts the return address in temporary variable.
-                        line_asm_code = format!("@13\nD=M\n@5\nD=D-A\nA=D\nD=M\n@14\nM=D"); // 14 is temporary return address.
+                        line_asm_code = "@13\nD=M\n@5\nD=D-A\nA=D\nD=M\n@14\nM=D".to_string(); // 14 is temporary return address.
                         machine_code.push(line_asm_code);
 
                         // *ARG = pop()
-                        line_asm_code = format!("@SP\nM=M-1\nA=M\nD=M\n@ARG\nA=M\nM=D");
+                        line_asm_code = "@SP\nM=M-1\nA=M\nD=M\n@ARG\nA=M\nM=D".to_string();
                         machine_code.push(line_asm_code);
 
                         // SP = ARG + 1
-                        line_asm_code = format!("@ARG\nD=M\n@SP\nM=D+1");
+                        line_asm_code = "@ARG\nD=M\n@SP\nM=D+1".to_string();
                         machine_code.push(line_asm_code);
 
                         // THAT = frame - 1
-                        line_asm_code = format!("@13\nD=M\n@1\nD=D-A\nA=D\nD=M\n@THAT\nM=D");
+                        line_asm_code = "@13\nD=M\n@1\nD=D-A\nA=D\nD=M\n@THAT\nM=D".to_string();
                         machine_code.push(line_asm_code);
 
                         // THIS = frame - 2
-                        line_asm_code = format!("@13\nD=M\n@2\nD=D-A\nA=D\nD=M\n@THIS\nM=D");
+                        line_asm_code = "@13\nD=M\n@2\nD=D-A\nA=D\nD=M\n@THIS\nM=D".to_string();
                         machine_code.push(line_asm_code);
 
                         // ARG = frame - 3
-                        line_asm_code = format!("@13\nD=M\n@3\nD=D-A\nA=D\nD=M\n@ARG\nM=D");
+                        line_asm_code = "@13\nD=M\n@3\nD=D-A\nA=D\nD=M\n@ARG\nM=D".to_string();
                         machine_code.push(line_asm_code);
 
                         // LCL = frame - 4
-                        line_asm_code = format!("@13\nD=M\n@4\nD=D-A\nA=D\nD=M\n@LCL\nM=D");
+                        line_asm_code = "@13\nD=M\n@4\nD=D-A\nA=D\nD=M\n@LCL\nM=D".to_string();
                         machine_code.push(line_asm_code);
 
                         // goto returnAddress
-                        line_asm_code = format!("@14\nA=M\n0;JMP");
+                        line_asm_code = "@14\nA=M\n0;JMP".to_string();
                         machine_code.push(line_asm_code);
                     }
                     _ => {
@@ -411,15 +409,12 @@ pub fn generate_machine_code(
                         line_asm_code = format!("({})", post_op);
                         machine_code.push(line_asm_code);
                     }
-                    _ => {
-                        println!("WRONG INPUT COMMAND FOUND, BRANCHING {}", command);
-                        exiting_sequence(true);
-                    }
+                    _ => unreachable!("Invalid branching command {}", command),
                 }
             }
         }
     }
-    return machine_code;
+    machine_code
 }
 
 fn call_sys_init(n_args: u32, file_name: String) -> Vec<String> {
@@ -435,19 +430,19 @@ fn call_sys_init(n_args: u32, file_name: String) -> Vec<String> {
     machine_code.push(line_asm_code);
 
     // push LCL
-    line_asm_code = format!("@LCL\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+    line_asm_code = "@LCL\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
     machine_code.push(line_asm_code);
 
     // push ARG
-    line_asm_code = format!("@ARG\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+    line_asm_code = "@ARG\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
     machine_code.push(line_asm_code);
 
     // push THIS
-    line_asm_code = format!("@THIS\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+    line_asm_code = "@THIS\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
     machine_code.push(line_asm_code);
 
     // push THAT
-    line_asm_code = format!("@THAT\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1");
+    line_asm_code = "@THAT\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1".to_string();
     machine_code.push(line_asm_code);
 
     // reposition ARG = SP-5-n_args
@@ -455,7 +450,7 @@ fn call_sys_init(n_args: u32, file_name: String) -> Vec<String> {
     machine_code.push(line_asm_code);
 
     // reposition LCL = SP
-    line_asm_code = format!("@SP\nD=M\n@LCL\nM=D");
+    line_asm_code = "@SP\nD=M\n@LCL\nM=D".to_string();
     machine_code.push(line_asm_code);
 
     // then goto the calee..
@@ -466,5 +461,5 @@ fn call_sys_init(n_args: u32, file_name: String) -> Vec<String> {
     line_asm_code = format!("(RET_{}.{})", file_name, function_name);
     machine_code.push(line_asm_code);
 
-    return machine_code;
+    machine_code
 }
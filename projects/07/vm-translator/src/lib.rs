@@ -0,0 +1,54 @@
+pub mod code_writer;
+pub mod parser;
+
+use crate::code_writer::{VmCommand, generate_machine_code};
+
+// Translates the .vm files of a program, given by file name and contents, into one
+// Hack assembly program. Functions are named after the program and statics after
+// their file. The bootstrap code that sets SP and calls Sys.init comes first when one
+// of the files defines Sys.init. Otherwise the code starts with the first file's and
+// ends in an infinite loop, so the program halts after its last command. That code first
+// gives SP, LCL and ARG the values of the course's test scripts, 256, 300 and 400, unless
+// SP was set already, so the values of a script or of the emulator's settings win.
+// Errors name the file and line, e.g. "Main.vm:12: Unknown segment 'locals'".
+pub fn translate(program_name: &str, files: &[(String, String)]) -> Result<String, String> {
+    let mut parsed = Vec::new();
+    for (file_name, contents) in files {
+        let commands = parser::parse_lines(contents.clone())
+            .map_err(|e| format!("{}:{}", file_name, e.trim_start_matches("Line ")))?;
+        parsed.push((file_name, commands));
+    }
+    let has_sys_init = parsed
+        .iter()
+        .flat_map(|(_, commands)| commands)
+        .any(|(_, command)| matches!(command, VmCommand::Function(text) if text.starts_with("function Sys.init ")));
+
+    let mut machine_code = Vec::new();
+    if !has_sys_init {
+        machine_code.push("// No Sys.init, set up the stack unless it already is".to_string());
+        machine_code.push("@SP\nD=M\n@VM_START\nD;JNE".to_string());
+        machine_code
+            .push("@256\nD=A\n@SP\nM=D\n@300\nD=A\n@LCL\nM=D\n@400\nD=A\n@ARG\nM=D".to_string());
+        machine_code.push("(VM_START)".to_string());
+    }
+    let mut first_label_index = 0;
+    for (index, (file_name, commands)) in parsed.into_iter().enumerate() {
+        machine_code.push(format!("// Following is conversion of {}", file_name));
+        let count = commands.len();
+        machine_code.extend(generate_machine_code(
+            commands,
+            program_name.to_string(),
+            file_name.trim_end_matches(".vm").to_string(),
+            has_sys_init && index == 0,
+            file_name,
+            first_label_index,
+        ));
+        first_label_index += count;
+    }
+    if !has_sys_init {
+        machine_code.push("(VM_END)\n@VM_END\n0;JMP".to_string());
+    }
+    let mut source = machine_code.join("\n");
+    source.push('\n');
+    Ok(source)
+}
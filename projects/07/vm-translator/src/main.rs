@@ -1,6 +1,3 @@
-mod code_writer;
-mod parser;
-
 use regex::Regex;
 use std::env;
 use std::fs;
@@ -9,7 +6,8 @@ use std::io::{Read, Write};
 use std::path::Path;
 use std::time::Instant;
 
-use crate::code_writer::generate_machine_code;
+use vm_translator::code_writer::{VmCommand, generate_machine_code};
+use vm_translator::parser;
 
 fn main() {
     let start = Instant::now();
@@ -23,7 +21,7 @@ fn main() {
     let argument: &String = &args[1];
     let regex = Regex::new(r"\.[^/\\]+$").unwrap();
 
-    if regex.is_match(&argument) {
+    if regex.is_match(argument) {
         // Argument contains file with extension.
         if argument.ends_with(".vm") {
             println!("Single file detected.");
@@ -98,15 +96,16 @@ fn translate_directory(directory_url: &String) {
             directory_url
         );
         exiting_sequence(true);
-        return;
     } else {
         let mut output_file_name: String = "WRONG_FILENAME".to_string();
         let mut combined_machine_code: Vec<String> = Vec::new();
         let mut first_file: bool = true;
+        // Comparison and return labels are numbered across all files so they stay unique.
+        let mut first_label_index = 0;
         for file in files {
             let full_file_path;
             if directory_url.eq(".") {
-                full_file_path = format!("{}", file);
+                full_file_path = file.to_string();
                 output_file_name = format!("{}.asm", get_output_file_name(directory_url));
             } else if directory_url.ends_with("..") {
                 full_file_path = format!("{}{}{}", directory_url, "/", file);
@@ -135,6 +134,7 @@ fn translate_directory(directory_url: &String) {
                 &output_file_name,
                 &full_file_path,
                 first_file,
+                &mut first_label_index,
             ));
             first_file = false;
         }
@@ -148,7 +148,12 @@ fn translate_directory(directory_url: &String) {
     }
 }
 
-fn handle_multiple_files(output_file: &str, file_path: &str, first_file: bool) -> Vec<String> {
+fn handle_multiple_files(
+    output_file: &str,
+    file_path: &str,
+    first_file: bool,
+    first_label_index: &mut usize,
+) -> Vec<String> {
     let message = format!(
         "Failed to open input file {} please check file path and permissions.",
         &file_path
@@ -159,18 +164,22 @@ fn handle_multiple_files(output_file: &str, file_path: &str, first_file: bool) -
     input_file
         .read_to_string(&mut contents)
         .expect("Failed to read input file.");
-    let parsed_commands = parser::parse_lines(contents);
+    let parsed_commands = parse_or_exit(file_path, contents);
     let mut machine_code: Vec<String> = Vec::new();
     let file_comment = format!("// Following is conversion of {}", file_path);
     machine_code.push(file_comment);
 
+    let command_count = parsed_commands.len();
     machine_code.extend(generate_machine_code(
         parsed_commands,
         output_file.replace(".asm", "").replace("/", ""),
         file_path.replace(".vm", "").replace("/", ""),
         first_file,
+        vm_file_name(file_path),
+        *first_label_index,
     ));
-    return machine_code;
+    *first_label_index += command_count;
+    machine_code
 }
 
 fn process_single_file(file_name: &String) {
@@ -184,14 +193,15 @@ fn process_single_file(file_name: &String) {
             input_file
                 .read_to_string(&mut contents)
                 // Here instead of using arguments.clone display the actual name of the file in question.
-                .expect(format!("Failed to read contents... {}", argument.clone()).as_str());
-            let parsed_commands = parser::parse_lines(contents);
-            let machine_code: Vec<String>;
-            machine_code = generate_machine_code(
+                .unwrap_or_else(|_| panic!("Failed to read contents... {}", argument.clone()));
+            let parsed_commands = parse_or_exit(argument, contents);
+            let machine_code = generate_machine_code(
                 parsed_commands,
                 argument.replace(".vm", "").replace("/", ""),
                 argument.replace(".vm", "").replace("/", ""),
                 true,
+                vm_file_name(argument),
+                0,
             );
             let output_file_name = argument.replace(".vm", ".asm");
             let mut output_file =
@@ -209,6 +219,22 @@ fn process_single_file(file_name: &String) {
     }
 }
 
+fn parse_or_exit(file_path: &str, contents: String) -> Vec<(usize, VmCommand)> {
+    match parser::parse_lines(contents) {
+        Ok(commands) => commands,
+        Err(err) => {
+            println!("{}: {}", file_path, err);
+            exiting_sequence(true);
+            Vec::new()
+        }
+    }
+}
+
+// The name of the .vm file without its directory, for the source markers.
+fn vm_file_name(file_path: &str) -> &str {
+    file_path.rsplit(['/', '\\']).next().unwrap_or(file_path)
+}
+
 fn get_output_file_name(directory_to_process: &String) -> String {
     let parts: Vec<&str> = directory_to_process.split("/").collect();
     let current_directory = env::current_dir().unwrap();
@@ -217,7 +243,7 @@ fn get_output_file_name(directory_to_process: &String) -> String {
             .file_name()
             .and_then(|closure| closure.to_str())
             .unwrap_or("");
-        return dir_name.to_string();
+        dir_name.to_string()
     } else if directory_to_process.eq("..") {
         // Handle this situation as well.
         let parent_directory = current_directory.parent().unwrap_or(Path::new(""));
@@ -225,15 +251,13 @@ fn get_output_file_name(directory_to_process: &String) -> String {
             .file_name()
             .and_then(|name| name.to_str())
             .unwrap_or("");
-        return parent_name.to_string();
-    } else if directory_to_process.ends_with(".") {
-        return parts[parts.len() - 2].to_string();
-    } else if directory_to_process.ends_with("/") {
-        return parts[parts.len() - 2].to_string();
+        parent_name.to_string()
+    } else if directory_to_process.ends_with(".") || directory_to_process.ends_with("/") {
+        parts[parts.len() - 2].to_string()
     } else {
-        if parts[parts.len() - 1].eq("") {
-            return format!("{}", parts[parts.len() - 2]);
+        if parts[parts.len() - 1].is_empty() {
+            return parts[parts.len() - 2].to_string();
         }
-        return format!("{}/{}", directory_to_process, parts[parts.len() - 1]);
+        format!("{}/{}", directory_to_process, parts[parts.len() - 1])
     }
 }
@@ -1,20 +1,19 @@
 use crate::code_writer::{self, VmCommand};
 
-pub fn parse_lines(source: String) -> Vec<VmCommand> {
+// The commands of a .vm file with their line numbers.
+pub fn parse_lines(source: String) -> Result<Vec<(usize, VmCommand)>, String> {
     // read the source files.
     // Strip away comments & empty lines.
     let mut categorized_commands = Vec::new();
-    for line in source.lines() {
-        let mut stripped = strip_comment(&line);
-        stripped = stripped.trim();
-        if stripped.is_empty() {
-            continue;
-        } else {
-            categorized_commands.push(code_writer::categorize_commands(stripped));
+    for (index, line) in source.lines().enumerate() {
+        let stripped = strip_comment(line).trim();
+        if !stripped.is_empty() {
+            let command = code_writer::categorize_commands(stripped)
+                .map_err(|e| format!("Line {}: {}", index + 1, e))?;
+            categorized_commands.push((index + 1, command));
         }
     }
-
-    return categorized_commands;
+    Ok(categorized_commands)
 }
 
 fn strip_comment(line: &str) -> &str {
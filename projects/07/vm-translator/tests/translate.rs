@@ -0,0 +1,95 @@
+use vm_translator::code_writer::{VmCommand, categorize_commands};
+use vm_translator::translate;
+
+// The library entry point used by the emulator, and the checks of the commands it parses.
+
+fn files(files: &[(&str, &str)]) -> Vec<(String, String)> {
+    files
+        .iter()
+        .map(|(name, contents)| (name.to_string(), contents.to_string()))
+        .collect()
+}
+
+#[test]
+fn categorizes_commands() {
+    assert!(matches!(
+        categorize_commands("push constant 7"),
+        Ok(VmCommand::PushPop { .. })
+    ));
+    assert!(matches!(
+        categorize_commands("call Main.twice 1"),
+        Ok(VmCommand::Function(text)) if text == "call Main.twice 1"
+    ));
+    assert!(matches!(
+        categorize_commands("if-goto LOOP"),
+        Ok(VmCommand::Branching(_))
+    ));
+}
+
+#[test]
+fn rejects_invalid_commands() {
+    for (command, error) in [
+        ("push locals 2", "Unknown segment 'locals'"),
+        ("pop constant 1", "Invalid 'pop constant 1'"),
+        ("push pointer 2", "Invalid 'push pointer 2'"),
+        ("push temp x", "Invalid 'push temp x'"),
+        ("push constant 70000", "Invalid 'push constant 70000'"),
+        (
+            "function Main.main",
+            "Invalid VM command 'function Main.main'",
+        ),
+        (
+            "call Main.twice n",
+            "Invalid VM command 'call Main.twice n'",
+        ),
+        ("goto", "Invalid VM command 'goto'"),
+        ("mul", "Invalid VM command 'mul'"),
+    ] {
+        assert_eq!(categorize_commands(command).err().unwrap(), error);
+    }
+}
+
+#[test]
+fn names_the_file_and_line_of_errors() {
+    let error = translate(
+        "Program",
+        &files(&[
+            ("Main.vm", "push constant 1\n"),
+            ("Other.vm", "// comment\n\npush locals 2\n"),
+        ]),
+    )
+    .err()
+    .unwrap();
+    assert_eq!(error, "Other.vm:3: Unknown segment 'locals'");
+}
+
+#[test]
+fn bootstraps_programs_with_sys_init() {
+    let source = translate(
+        "Program",
+        &files(&[
+            ("Main.vm", "function Main.main 0\npush constant 1\nreturn\n"),
+            (
+                "Sys.vm",
+                "function Sys.init 0\ncall Main.main 0\nlabel END\ngoto END\n",
+            ),
+        ]),
+    )
+    .unwrap();
+    assert!(source.starts_with("// Following is conversion of Main.vm\n@256\nD=A\n@SP\nM=D\n"));
+    assert!(source.contains("// vm Main.vm:2 push constant 1\n"));
+    assert!(source.contains("// vm Sys.vm:2 call Main.main 0\n"));
+    assert!(!source.contains("VM_START") && !source.contains("VM_END"));
+}
+
+#[test]
+fn sets_up_the_stack_without_sys_init() {
+    let source = translate("Program", &files(&[("Main.vm", "push constant 7\n")])).unwrap();
+    // Only when SP is still 0, so the settings of a test script are kept.
+    assert!(source.starts_with(
+        "// No Sys.init, set up the stack unless it already is\n@SP\nD=M\n@VM_START\nD;JNE\n\
+         @256\nD=A\n@SP\nM=D\n@300\nD=A\n@LCL\nM=D\n@400\nD=A\n@ARG\nM=D\n(VM_START)\n\
+         // Following is conversion of Main.vm\n// vm Main.vm:1 push constant 7\n"
+    ));
+    assert!(source.ends_with("(VM_END)\n@VM_END\n0;JMP\n"));
+}